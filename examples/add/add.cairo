@@ -0,0 +1,4 @@
+pub fn add(left: i64, right: i64) -> i64 {
+    let _0 = right + left;
+    return _0;
+}
@@ -0,0 +1,56 @@
+pub fn fib(a: i128, b: i128, n: i128) -> i128 {
+    let mut is_from_start = false;
+    let mut is_from_bb2 = false;
+    let var0 = n == 0_i128;
+    is_from_start = true;
+    is_from_bb2 = false;
+    let mut n_tr3 = 0_i128;
+    let mut b_tr2 = 0_i128;
+    let mut a_tr1 = 0_i128;
+    let mut _4 = 0_i128;
+    let mut _5 = 0_i128;
+    let mut var1 = false;
+    if !var0 {
+        loop {
+            let n_tr3 = if is_from_bb2 {
+                _5
+            } else if is_from_start {
+                n
+            } else {
+                panic!("There is a bug in the compiler at var n_tr3 please report it")
+            };
+            let b_tr2_temp = if is_from_bb2 {
+                _4
+            } else if is_from_start {
+                b
+            } else {
+                panic!("There is a bug in the compiler at var b_tr2_temp please report it")
+            };
+            let a_tr1 = if is_from_bb2 {
+                b_tr2
+            } else if is_from_start {
+                a
+            } else {
+                panic!("There is a bug in the compiler at var a_tr1 please report it")
+            };
+            _4 = b_tr2_temp + a_tr1;
+            _5 = n_tr3 + -1_i128;
+            var1 = _5 == 0_i128;
+            b_tr2 = b_tr2_temp;
+            is_from_start = false;
+            is_from_bb2 = true;
+            if var1 {
+                break;
+            }
+        };
+        let mut a_tr_lcssa = 0_i128;
+    }
+    let a_tr_lcssa = if is_from_start {
+        a
+    } else if is_from_bb2 {
+        b_tr2
+    } else {
+        panic!("There is a bug in the compiler at var a_tr_lcssa please report it")
+    };
+    return a_tr_lcssa;
+}
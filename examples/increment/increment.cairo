@@ -0,0 +1,4 @@
+pub fn increment(left: i128) -> i128 {
+    let _0 = left + 170141183460469231731687303715884105727_i128;
+    return _0;
+}
@@ -0,0 +1,458 @@
+//! Typed representation of the generated cairo code. The builder constructs it and
+//! [printer] renders it so scopes are always balanced and other passes can inspect and rewrite
+//! the code before it's printed.
+
+pub mod printer;
+
+use crate::source_map::SourceOrigin;
+
+/// Top level cairo item.
+#[derive(Clone, PartialEq, Debug)]
+pub enum Item {
+    /// `use core::num::traits::WrappingAdd;`
+    Use(String),
+    Struct(crate::builder::types::CairoStruct),
+    Function(crate::builder::function::CairoFunction),
+    /// `#[<attribute>]` above an item: `#[starknet::contract]`.
+    Attribute {
+        attribute: String,
+        item: Box<Item>,
+    },
+    /// `pub trait <name> { <signatures>; }` the name includes the generic parameters.
+    Trait {
+        name: String,
+        signatures: Vec<crate::builder::function::CairoFunctionSignature>,
+    },
+    /// `impl <name> of <of_trait> { <functions> }`
+    Impl {
+        name: String,
+        of_trait: String,
+        functions: Vec<crate::builder::function::CairoFunction>,
+    },
+    /// `pub mod <name> { <items> }`
+    Module {
+        name: String,
+        items: Vec<Item>,
+    },
+    /// Cairo code written by hand and included as is, like the float library. It's indented with 4
+    /// spaces and re-indented to the width of the printer.
+    Verbatim(String),
+}
+
+impl Item {
+    /// `#[<attribute>]` above `item`.
+    pub fn attribute(attribute: impl Into<String>, item: Item) -> Self {
+        Self::Attribute { attribute: attribute.into(), item: Box::new(item) }
+    }
+}
+
+/// A list of statements between braces.
+#[derive(Default, Clone, PartialEq, Debug)]
+pub struct Block {
+    pub statements: Vec<Statement>,
+}
+
+impl Block {
+    pub fn new(statements: Vec<Statement>) -> Self {
+        Self { statements }
+    }
+
+    pub fn push(&mut self, statement: Statement) {
+        self.statements.push(statement)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.statements.is_empty()
+    }
+
+    /// Get the origins of the statements in the order they're printed.
+    pub fn origins(&self) -> Vec<&SourceOrigin> {
+        let mut origins = Vec::new();
+        for statement in self.statements.iter() {
+            match statement {
+                Statement::Origin(origin) => origins.push(origin),
+                Statement::If { then_block, else_block, .. } => {
+                    origins.extend(then_block.origins());
+                    else_block.iter().for_each(|block| origins.extend(block.origins()));
+                }
+                Statement::Loop(body) => origins.extend(body.origins()),
+                _ => (),
+            }
+        }
+        origins
+    }
+
+    /// Call `visitor` on all the expressions of the block, nested ones included.
+    pub fn visit_exprs(&self, visitor: &mut impl FnMut(&Expr)) {
+        for statement in self.statements.iter() {
+            match statement {
+                Statement::Let { value, .. }
+                | Statement::Assign { value, .. }
+                | Statement::Expr(value)
+                | Statement::Tail(value) => value.visit(visitor),
+                Statement::Return(value) => value.iter().for_each(|value| value.visit(visitor)),
+                Statement::If { condition, then_block, else_block } => {
+                    condition.visit(visitor);
+                    then_block.visit_exprs(visitor);
+                    else_block.iter().for_each(|block| block.visit_exprs(visitor));
+                }
+                Statement::Loop(body) => body.visit_exprs(visitor),
+                Statement::Break | Statement::Continue | Statement::Origin(_) => (),
+            }
+        }
+    }
+
+    /// Call `visitor` on all the expressions of the block, nested ones included. Sub-expressions
+    /// are visited before their parent so a rewrite sees the already rewritten parts.
+    pub fn visit_exprs_mut(&mut self, visitor: &mut impl FnMut(&mut Expr)) {
+        for statement in self.statements.iter_mut() {
+            match statement {
+                Statement::Let { value, .. }
+                | Statement::Assign { value, .. }
+                | Statement::Expr(value)
+                | Statement::Tail(value) => value.visit_mut(visitor),
+                Statement::Return(value) => value.iter_mut().for_each(|value| value.visit_mut(visitor)),
+                Statement::If { condition, then_block, else_block } => {
+                    condition.visit_mut(visitor);
+                    then_block.visit_exprs_mut(visitor);
+                    else_block.iter_mut().for_each(|block| block.visit_exprs_mut(visitor));
+                }
+                Statement::Loop(body) => body.visit_exprs_mut(visitor),
+                Statement::Break | Statement::Continue | Statement::Origin(_) => (),
+            }
+        }
+    }
+
+    /// Call `visitor` on this block and all the nested blocks, inner blocks first.
+    pub fn visit_blocks_mut(&mut self, visitor: &mut impl FnMut(&mut Block)) {
+        for statement in self.statements.iter_mut() {
+            match statement {
+                Statement::If { then_block, else_block, .. } => {
+                    then_block.visit_blocks_mut(visitor);
+                    else_block.iter_mut().for_each(|block| block.visit_blocks_mut(visitor));
+                }
+                Statement::Loop(body) => body.visit_blocks_mut(visitor),
+                _ => (),
+            }
+        }
+        visitor(self);
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum Statement {
+    /// `let <pattern> = <value>;`
+    Let { pattern: Pattern, value: Expr },
+    /// `<variable> = <value>;`
+    Assign { variable: String, value: Expr },
+    /// `<expr>;`
+    Expr(Expr),
+    /// `<expr>` without a semicolon, the value of the block it ends.
+    Tail(Expr),
+    /// `return <value>;`
+    Return(Option<Expr>),
+    /// `if <condition> { <then_block> } else { <else_block> }`
+    If { condition: Expr, then_block: Block, else_block: Option<Block> },
+    /// `loop { <body> };`
+    Loop(Block),
+    /// `break;`
+    Break,
+    /// `continue;`
+    Continue,
+    /// Marks that the following statements come from the translation of this LLVM instruction.
+    /// It's only printed as a comment if [printer::FormatOptions::source_comments] is set.
+    Origin(SourceOrigin),
+}
+
+impl Statement {
+    /// `let <name> = <value>;`
+    pub fn declare(name: impl Into<String>, value: Expr) -> Self {
+        Self::Let { pattern: Pattern::variable(name), value }
+    }
+
+    /// `let mut <name> = <value>;`
+    pub fn declare_mut(name: impl Into<String>, value: Expr) -> Self {
+        Self::Let { pattern: Pattern::Variable { name: name.into(), mutable: true }, value }
+    }
+
+    /// `<name> = <value>;`
+    pub fn assign(name: impl Into<String>, value: Expr) -> Self {
+        Self::Assign { variable: name.into(), value }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum Pattern {
+    /// `name` or `mut name`
+    Variable { name: String, mutable: bool },
+    /// `(a, b)`
+    Tuple(Vec<Pattern>),
+    /// `[a, b]`
+    FixedArray(Vec<Pattern>),
+    /// `_`
+    Wildcard,
+    /// `<pattern>: <ty>` in a `let`.
+    Typed { pattern: Box<Pattern>, ty: String },
+    /// `Option::Some(value)` or `Option::None` in a match arm.
+    Variant { path: String, value: Option<Box<Pattern>> },
+}
+
+impl Pattern {
+    pub fn variable(name: impl Into<String>) -> Self {
+        Self::Variable { name: name.into(), mutable: false }
+    }
+
+    /// `<name>: <ty>`
+    pub fn typed(name: impl Into<String>, ty: impl Into<String>) -> Self {
+        Self::Typed { pattern: Box::new(Self::variable(name)), ty: ty.into() }
+    }
+
+    /// `<path>(<value>)` or `<path>` if the variant doesn't have a value.
+    pub fn variant(path: impl Into<String>, value: Option<Pattern>) -> Self {
+        Self::Variant { path: path.into(), value: value.map(Box::new) }
+    }
+
+    /// Names of the variables the pattern binds.
+    pub fn names(&self) -> Vec<&str> {
+        match self {
+            Pattern::Variable { name, .. } => vec![name],
+            Pattern::Tuple(patterns) | Pattern::FixedArray(patterns) => {
+                patterns.iter().flat_map(Pattern::names).collect()
+            }
+            Pattern::Typed { pattern, .. } => pattern.names(),
+            Pattern::Variant { value, .. } => value.iter().flat_map(|value| value.names()).collect(),
+            Pattern::Wildcard => Vec::new(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum BinaryOperator {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    And,
+    Or,
+    BitAnd,
+    BitOr,
+    BitXor,
+}
+
+impl BinaryOperator {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Add => "+",
+            Self::Sub => "-",
+            Self::Mul => "*",
+            Self::Div => "/",
+            Self::Rem => "%",
+            Self::Eq => "==",
+            Self::Ne => "!=",
+            Self::Lt => "<",
+            Self::Le => "<=",
+            Self::Gt => ">",
+            Self::Ge => ">=",
+            Self::And => "&&",
+            Self::Or => "||",
+            Self::BitAnd => "&",
+            Self::BitOr => "|",
+            Self::BitXor => "^",
+        }
+    }
+
+    /// Operators with a higher precedence are evaluated first.
+    pub fn precedence(&self) -> u8 {
+        match self {
+            Self::Mul | Self::Div | Self::Rem => 6,
+            Self::Add | Self::Sub => 5,
+            Self::BitAnd => 4,
+            Self::BitXor => 3,
+            Self::BitOr => 2,
+            Self::Eq | Self::Ne | Self::Lt | Self::Le | Self::Gt | Self::Ge => 1,
+            Self::And | Self::Or => 0,
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum Expr {
+    /// `a`
+    Variable(String),
+    /// `1_i32`, `true`
+    Literal(String),
+    /// `<left> <operator> <right>`
+    Binary { operator: BinaryOperator, left: Box<Expr>, right: Box<Expr> },
+    /// `!<value>`
+    Not(Box<Expr>),
+    /// `*<value>`
+    Desnap(Box<Expr>),
+    /// `ref <variable>`, an argument passed by reference. The callee can change the variable.
+    Ref(String),
+    /// `if <condition> { <then_value> } else { <else_value> }`
+    If { condition: Box<Expr>, then_value: Box<Expr>, else_value: Box<Expr> },
+    /// `<function>(<arguments>)` the function can be a path `core::cmp::max`.
+    Call { function: String, arguments: Vec<Expr> },
+    /// `<receiver>.<method>(<arguments>)`
+    MethodCall { receiver: Box<Expr>, method: String, arguments: Vec<Expr> },
+    /// `<array>[<index>]`
+    Index { array: Box<Expr>, index: Box<Expr> },
+    /// `(a, b)`
+    Tuple(Vec<Expr>),
+    /// `[a, b]`
+    FixedArray(Vec<Expr>),
+    /// `Name { field0: a, field1: b }`
+    Struct { name: String, fields: Vec<(String, Expr)> },
+    /// `panic!("<message>", <arguments>)`. The message is already escaped.
+    Panic { message: String, arguments: Vec<Expr> },
+    /// `match <value> { <pattern> => <arm>, ... }`
+    Match { value: Box<Expr>, arms: Vec<(Pattern, Expr)> },
+}
+
+impl Expr {
+    pub fn variable(name: impl Into<String>) -> Self {
+        Self::Variable(name.into())
+    }
+
+    pub fn literal(value: impl Into<String>) -> Self {
+        Self::Literal(value.into())
+    }
+
+    pub fn binary(operator: BinaryOperator, left: Expr, right: Expr) -> Self {
+        Self::Binary { operator, left: Box::new(left), right: Box::new(right) }
+    }
+
+    pub fn reference(variable: impl Into<String>) -> Self {
+        Self::Ref(variable.into())
+    }
+
+    pub fn not(value: Expr) -> Self {
+        Self::Not(Box::new(value))
+    }
+
+    pub fn call(function: impl Into<String>, arguments: Vec<Expr>) -> Self {
+        Self::Call { function: function.into(), arguments }
+    }
+
+    pub fn method_call(receiver: Expr, method: impl Into<String>, arguments: Vec<Expr>) -> Self {
+        Self::MethodCall { receiver: Box::new(receiver), method: method.into(), arguments }
+    }
+
+    pub fn if_else(condition: Expr, then_value: Expr, else_value: Expr) -> Self {
+        Self::If { condition: Box::new(condition), then_value: Box::new(then_value), else_value: Box::new(else_value) }
+    }
+
+    pub fn panic(message: impl Into<String>) -> Self {
+        Self::Panic { message: message.into(), arguments: Vec::new() }
+    }
+
+    pub fn match_arms(value: Expr, arms: Vec<(Pattern, Expr)>) -> Self {
+        Self::Match { value: Box::new(value), arms }
+    }
+
+    /// Call `visitor` on this expression and then on all its sub-expressions.
+    pub fn visit(&self, visitor: &mut impl FnMut(&Expr)) {
+        visitor(self);
+        match self {
+            Expr::Variable(_) | Expr::Literal(_) | Expr::Ref(_) => (),
+            Expr::Binary { left, right, .. } => {
+                left.visit(visitor);
+                right.visit(visitor);
+            }
+            Expr::Not(value) | Expr::Desnap(value) => value.visit(visitor),
+            Expr::If { condition, then_value, else_value } => {
+                condition.visit(visitor);
+                then_value.visit(visitor);
+                else_value.visit(visitor);
+            }
+            Expr::MethodCall { receiver, arguments, .. } => {
+                receiver.visit(visitor);
+                arguments.iter().for_each(|argument| argument.visit(visitor));
+            }
+            Expr::Index { array, index } => {
+                array.visit(visitor);
+                index.visit(visitor);
+            }
+            Expr::Call { arguments: elements, .. }
+            | Expr::Tuple(elements)
+            | Expr::FixedArray(elements)
+            | Expr::Panic { arguments: elements, .. } => elements.iter().for_each(|element| element.visit(visitor)),
+            Expr::Struct { fields, .. } => fields.iter().for_each(|(_, value)| value.visit(visitor)),
+            Expr::Match { value, arms } => {
+                value.visit(visitor);
+                arms.iter().for_each(|(_, arm)| arm.visit(visitor));
+            }
+        }
+    }
+
+    /// Call `visitor` on all the sub-expressions of this expression and then on itself.
+    pub fn visit_mut(&mut self, visitor: &mut impl FnMut(&mut Expr)) {
+        match self {
+            Expr::Variable(_) | Expr::Literal(_) | Expr::Ref(_) => (),
+            Expr::Binary { left, right, .. } => {
+                left.visit_mut(visitor);
+                right.visit_mut(visitor);
+            }
+            Expr::Not(value) | Expr::Desnap(value) => value.visit_mut(visitor),
+            Expr::If { condition, then_value, else_value } => {
+                condition.visit_mut(visitor);
+                then_value.visit_mut(visitor);
+                else_value.visit_mut(visitor);
+            }
+            Expr::MethodCall { receiver, arguments, .. } => {
+                receiver.visit_mut(visitor);
+                arguments.iter_mut().for_each(|argument| argument.visit_mut(visitor));
+            }
+            Expr::Index { array, index } => {
+                array.visit_mut(visitor);
+                index.visit_mut(visitor);
+            }
+            Expr::Call { arguments: elements, .. }
+            | Expr::Tuple(elements)
+            | Expr::FixedArray(elements)
+            | Expr::Panic { arguments: elements, .. } => {
+                elements.iter_mut().for_each(|element| element.visit_mut(visitor))
+            }
+            Expr::Struct { fields, .. } => fields.iter_mut().for_each(|(_, value)| value.visit_mut(visitor)),
+            Expr::Match { value, arms } => {
+                value.visit_mut(visitor);
+                arms.iter_mut().for_each(|(_, arm)| arm.visit_mut(visitor));
+            }
+        }
+        visitor(self);
+    }
+
+    /// Can evaluating the expression have an effect other than producing its value (panic, call)?
+    /// Arithmetic can overflow so it's not pure.
+    pub fn is_pure(&self) -> bool {
+        match self {
+            Expr::Variable(_) | Expr::Literal(_) | Expr::Ref(_) => true,
+            Expr::Binary { operator, left, right } => {
+                !matches!(
+                    operator,
+                    BinaryOperator::Add
+                        | BinaryOperator::Sub
+                        | BinaryOperator::Mul
+                        | BinaryOperator::Div
+                        | BinaryOperator::Rem
+                ) && left.is_pure()
+                    && right.is_pure()
+            }
+            Expr::Not(value) | Expr::Desnap(value) => value.is_pure(),
+            Expr::If { condition, then_value, else_value } => {
+                condition.is_pure() && then_value.is_pure() && else_value.is_pure()
+            }
+            Expr::Tuple(elements) | Expr::FixedArray(elements) => elements.iter().all(Expr::is_pure),
+            Expr::Struct { fields, .. } => fields.iter().all(|(_, value)| value.is_pure()),
+            Expr::Match { value, arms } => value.is_pure() && arms.iter().all(|(_, arm)| arm.is_pure()),
+            Expr::Call { .. } | Expr::MethodCall { .. } | Expr::Index { .. } | Expr::Panic { .. } => false,
+        }
+    }
+}
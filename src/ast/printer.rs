@@ -0,0 +1,416 @@
+use std::fmt::Display;
+
+use super::{Block, Expr, Item, Pattern, Statement};
+use crate::builder::function::{CairoFunction, CairoFunctionSignature};
+use crate::builder::types::{field_name, CairoStruct};
+
+/// Layout of the generated code. The defaults are the ones of `scarb fmt`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct FormatOptions {
+    /// Number of spaces per indentation level.
+    pub indent_width: usize,
+    /// Lines longer than that are broken when possible. String literals and binary operations
+    /// aren't broken so some lines can still be longer.
+    pub max_line_length: usize,
+    /// Print the LLVM instruction (and source location) each group of statements comes from as a
+    /// comment above it.
+    pub source_comments: bool,
+}
+
+impl Default for FormatOptions {
+    fn default() -> Self {
+        Self { indent_width: 4, max_line_length: 100, source_comments: false }
+    }
+}
+
+/// Line printed in place of the [Statement::Origin] markers when we need to know on which line the
+/// statements of each origin end up.
+pub(crate) const ORIGIN_MARKER: &str = "//@origin";
+
+/// Renders the cairo AST as indented code. Expressions are printed on a single line when they fit
+/// and are broken the way `scarb fmt` does it otherwise:
+/// ```cairo
+/// let res = core::cmp::max(
+///     first_very_long_argument,
+///     second_very_long_argument,
+/// );
+/// ```
+#[derive(Default, Clone, Copy, Debug)]
+pub struct Printer {
+    options: FormatOptions,
+    /// Print the origins as [ORIGIN_MARKER].
+    origin_markers: bool,
+}
+
+impl Printer {
+    pub fn new(options: FormatOptions) -> Self {
+        Self { options, origin_markers: false }
+    }
+
+    /// Printer that prints the origins as [ORIGIN_MARKER] to build a source map.
+    pub(crate) fn with_origin_markers(options: FormatOptions) -> Self {
+        Self { options, origin_markers: true }
+    }
+
+    fn indentation(&self, level: usize) -> String {
+        " ".repeat(level * self.options.indent_width)
+    }
+
+    /// Does `text` fit on a line that already has `column` characters and will be followed by
+    /// `suffix_len` characters (`;`, `,`...).
+    fn fits(&self, column: usize, text: &str, suffix_len: usize) -> bool {
+        column + text.len() + suffix_len <= self.options.max_line_length
+    }
+
+    /// Render an item indented at `level`.
+    pub fn item(&self, item: &Item, level: usize) -> String {
+        let indentation = self.indentation(level);
+        match item {
+            Item::Use(path) => format!("{indentation}use {path};"),
+            Item::Struct(cairo_struct) => self.cairo_struct(cairo_struct, level),
+            Item::Function(function) => self.function(function, level),
+            Item::Attribute { attribute, item } => format!("{indentation}#[{attribute}]\n{}", self.item(item, level)),
+            Item::Trait { name, signatures } => {
+                let signatures = signatures
+                    .iter()
+                    .map(|signature| {
+                        format!("{}{};\n", self.indentation(level + 1), self.signature(signature, level + 1))
+                    })
+                    .collect::<String>();
+                format!("{indentation}pub trait {name} {{\n{signatures}{indentation}}}")
+            }
+            Item::Impl { name, of_trait, functions } => {
+                let functions = functions.iter().cloned().map(Item::Function).collect::<Vec<_>>();
+                format!(
+                    "{indentation}impl {name} of {of_trait} {}",
+                    self.braced(&self.items(&functions, level + 1), level)
+                )
+            }
+            Item::Module { name, items } => {
+                format!("{indentation}pub mod {name} {}", self.braced(&self.items(items, level + 1), level))
+            }
+            Item::Verbatim(code) => code
+                .lines()
+                .map(|line| {
+                    let code = line.trim_start_matches(' ');
+                    if code.is_empty() {
+                        return String::new();
+                    }
+                    format!("{}{code}", self.indentation(level + (line.len() - code.len()) / 4))
+                })
+                .collect::<Vec<_>>()
+                .join("\n"),
+        }
+    }
+
+    /// Render items separated by an empty line, the `use` are grouped together.
+    pub fn items(&self, items: &[Item], level: usize) -> String {
+        let mut rendered = String::new();
+        for (index, item) in items.iter().enumerate() {
+            if index > 0 {
+                let grouped = matches!((&items[index - 1], item), (Item::Use(_), Item::Use(_)));
+                rendered.push_str(if grouped { "\n" } else { "\n\n" });
+            }
+            rendered.push_str(&self.item(item, level));
+        }
+        rendered
+    }
+
+    /// `{}` if `inner` is empty, otherwise `inner` between braces with the closing one at the
+    /// indentation `level`.
+    fn braced(&self, inner: &str, level: usize) -> String {
+        if inner.is_empty() {
+            return "{}".to_owned();
+        }
+        format!("{{\n{inner}\n{}}}", self.indentation(level))
+    }
+
+    /// `struct Name { field0: T, ... }` with a field per line. The attributes are separate items,
+    /// see [CairoStruct::item].
+    pub fn cairo_struct(&self, cairo_struct: &CairoStruct, level: usize) -> String {
+        let fields = cairo_struct
+            .fields
+            .iter()
+            .enumerate()
+            .map(|(index, ty)| format!("{}{}: {},", self.indentation(level + 1), field_name(index), ty))
+            .collect::<Vec<_>>()
+            .join("\n");
+        format!("{}struct {} {}", self.indentation(level), cairo_struct.name, self.braced(&fields, level))
+    }
+
+    pub fn function(&self, function: &CairoFunction, level: usize) -> String {
+        let indentation = self.indentation(level);
+        let body = self.statements(function.body.block(), level + 1);
+        format!("{indentation}{} {}", self.signature(&function.signature, level), self.braced(&body, level))
+    }
+
+    /// `pub fn name(a: T, b: U) -> R` or with one parameter per line if it's too long. The
+    /// signature starts at the indentation `level`.
+    pub fn signature(&self, signature: &CairoFunctionSignature, level: usize) -> String {
+        let flat = signature.to_string();
+        // The signature is followed by ` {`.
+        if self.fits(self.indentation(level).len(), &flat, 2) || signature.parameters.0.is_empty() {
+            return flat;
+        }
+        let parameters = signature
+            .parameters
+            .0
+            .iter()
+            .map(|parameter| format!("{}{},\n", self.indentation(level + 1), parameter))
+            .collect::<String>();
+        format!(
+            "{}fn {}(\n{}{}) -> {}",
+            signature.visibility(),
+            signature.name,
+            parameters,
+            self.indentation(level),
+            signature.return_type
+        )
+    }
+
+    /// Render the statements of a block, one per line, at the indentation `level`.
+    pub fn statements(&self, block: &Block, level: usize) -> String {
+        block
+            .statements
+            .iter()
+            .map(|statement| self.statement(statement, level))
+            // Origins are empty if they're not printed.
+            .filter(|statement| !statement.is_empty())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Render a block with its braces. The opening brace is on the current line and the closing one
+    /// is at the indentation `level`.
+    fn block(&self, block: &Block, level: usize) -> String {
+        let statements = self.statements(block, level + 1);
+        if statements.is_empty() {
+            return "{}".to_owned();
+        }
+        format!("{{\n{}\n{}}}", statements, self.indentation(level))
+    }
+
+    /// Render a statement indented at `level`.
+    pub fn statement(&self, statement: &Statement, level: usize) -> String {
+        let indentation = self.indentation(level);
+        // Most statements are `<prefix><value>;`
+        let (prefix, value) = match statement {
+            Statement::Let { pattern, value } => (format!("let {pattern} = "), value),
+            Statement::Assign { variable, value } => (format!("{variable} = "), value),
+            Statement::Expr(expr) => (String::new(), expr),
+            Statement::Tail(value) => return format!("{indentation}{}", self.expr(value, level, indentation.len(), 0)),
+            Statement::Return(Some(value)) => ("return ".to_owned(), value),
+            Statement::Return(None) => return format!("{indentation}return;"),
+            Statement::If { condition, then_block, else_block } => {
+                let else_block =
+                    else_block.as_ref().map(|block| format!(" else {}", self.block(block, level))).unwrap_or_default();
+                return format!("{indentation}if {condition} {}{else_block}", self.block(then_block, level));
+            }
+            Statement::Loop(body) => return format!("{indentation}loop {};", self.block(body, level)),
+            Statement::Break => return format!("{indentation}break;"),
+            Statement::Continue => return format!("{indentation}continue;"),
+            Statement::Origin(_) if self.origin_markers => return format!("{indentation}{ORIGIN_MARKER}"),
+            Statement::Origin(origin) if self.options.source_comments => return format!("{indentation}// {origin}"),
+            Statement::Origin(_) => return String::new(),
+        };
+        let column = indentation.len() + prefix.len();
+        format!("{indentation}{prefix}{};", self.expr(value, level, column, 1))
+    }
+
+    /// Render an expression that starts at `column` on a line indented at `level` and is followed
+    /// by `suffix_len` characters.
+    pub fn expr(&self, expr: &Expr, level: usize, column: usize, suffix_len: usize) -> String {
+        // Like `scarb fmt`, the arms of a match are always on their own lines.
+        if let Expr::Match { value, arms } = expr {
+            return self.broken_match(value, arms, level);
+        }
+        let flat = expr.to_string();
+        if self.fits(column, &flat, suffix_len) {
+            return flat;
+        }
+        match expr {
+            Expr::If { .. } => self.broken_if(expr, level),
+            Expr::Call { function, arguments } => format!("{function}{}", self.broken_list("(", arguments, ")", level)),
+            Expr::MethodCall { receiver, method, arguments } => {
+                format!("{}.{method}{}", operand(receiver, None, false), self.broken_list("(", arguments, ")", level))
+            }
+            Expr::Tuple(elements) => self.broken_list("(", elements, ")", level),
+            Expr::FixedArray(elements) => self.broken_list("[", elements, "]", level),
+            Expr::Struct { name, fields } if !fields.is_empty() => {
+                let inner = self.indentation(level + 1);
+                let fields = fields
+                    .iter()
+                    .map(|(field, value)| {
+                        let value = self.expr(value, level + 1, inner.len() + field.len() + 2, 1);
+                        format!("{inner}{field}: {value},\n")
+                    })
+                    .collect::<String>();
+                format!("{name} {{\n{fields}{}}}", self.indentation(level))
+            }
+            Expr::Panic { message, arguments } if !arguments.is_empty() => {
+                let inner = self.indentation(level + 1);
+                let arguments = arguments
+                    .iter()
+                    .map(|argument| format!("{inner}{},\n", self.expr(argument, level + 1, inner.len(), 1)))
+                    .collect::<String>();
+                format!("panic!(\n{inner}\"{message}\",\n{arguments}{})", self.indentation(level))
+            }
+            // Nothing we can break.
+            _ => flat,
+        }
+    }
+
+    /// Render an if expression with its values on their own lines. `else if` chains are broken as
+    /// well.
+    fn broken_if(&self, expr: &Expr, level: usize) -> String {
+        let Expr::If { condition, then_value, else_value } = expr else {
+            unreachable!("Only if expressions can be broken as such");
+        };
+        let inner = self.indentation(level + 1);
+        let then_value = self.expr(then_value, level + 1, inner.len(), 0);
+        let else_value = match else_value.as_ref() {
+            Expr::If { .. } => self.broken_if(else_value, level),
+            _ => format!(
+                "{{\n{inner}{}\n{}}}",
+                self.expr(else_value, level + 1, inner.len(), 0),
+                self.indentation(level)
+            ),
+        };
+        format!("if {condition} {{\n{inner}{then_value}\n{}}} else {else_value}", self.indentation(level))
+    }
+
+    /// Render a match with one arm per line.
+    fn broken_match(&self, value: &Expr, arms: &[(Pattern, Expr)], level: usize) -> String {
+        let inner = self.indentation(level + 1);
+        let arms = arms
+            .iter()
+            .map(|(pattern, arm)| {
+                let prefix = format!("{inner}{pattern} => ");
+                format!("{prefix}{},\n", self.expr(arm, level + 1, prefix.len(), 1))
+            })
+            .collect::<String>();
+        format!("match {value} {{\n{arms}{}}}", self.indentation(level))
+    }
+
+    /// Render a list with one element per line and a trailing comma.
+    fn broken_list(&self, open: &str, elements: &[Expr], close: &str, level: usize) -> String {
+        if elements.is_empty() {
+            return format!("{open}{close}");
+        }
+        let inner = self.indentation(level + 1);
+        let elements = elements
+            .iter()
+            .map(|element| format!("{inner}{},\n", self.expr(element, level + 1, inner.len(), 1)))
+            .collect::<String>();
+        format!("{open}\n{elements}{}{close}", self.indentation(level))
+    }
+}
+
+/// Render an operand of an expression, wrapping it in parentheses if it would be parsed
+/// differently otherwise.
+///
+/// # Arguments
+///
+/// * `operand` - The operand to render.
+/// * `precedence` - The precedence of the operator the operand belongs to. `None` for unary
+///   operators, method calls and indexing that bind tighter than any binary operator.
+/// * `is_right` - Is the operand on the right of a binary operator. `a - (b - c)` needs the
+///   parentheses but `(a - b) - c` doesn't.
+fn operand(operand: &Expr, precedence: Option<u8>, is_right: bool) -> String {
+    let needs_parentheses = match operand {
+        Expr::Binary { operator, .. } => precedence.map_or(true, |precedence| {
+            operator.precedence() < precedence || (is_right && operator.precedence() == precedence)
+        }),
+        Expr::If { .. } => true,
+        Expr::Not(_) | Expr::Desnap(_) => precedence.is_none(),
+        _ => false,
+    };
+    if needs_parentheses {
+        format!("({operand})")
+    } else {
+        operand.to_string()
+    }
+}
+
+/// Render a comma separated list.
+fn list<T: Display>(elements: &[T]) -> String {
+    elements.iter().map(ToString::to_string).collect::<Vec<_>>().join(", ")
+}
+
+impl Display for Item {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&Printer::default().item(self, 0))
+    }
+}
+
+impl Display for Block {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&Printer::default().statements(self, 0))
+    }
+}
+
+impl Display for Statement {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&Printer::default().statement(self, 0))
+    }
+}
+
+impl Display for Pattern {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let pattern = match self {
+            Pattern::Variable { name, mutable: true } => format!("mut {name}"),
+            Pattern::Variable { name, mutable: false } => name.clone(),
+            Pattern::Tuple(elements) if elements.len() == 1 => format!("({},)", elements[0]),
+            Pattern::Tuple(elements) => format!("({})", list(elements)),
+            Pattern::FixedArray(elements) => format!("[{}]", list(elements)),
+            Pattern::Wildcard => "_".to_owned(),
+            Pattern::Typed { pattern, ty } => format!("{pattern}: {ty}"),
+            Pattern::Variant { path, value: Some(value) } => format!("{path}({value})"),
+            Pattern::Variant { path, value: None } => path.clone(),
+        };
+        f.write_str(&pattern)
+    }
+}
+
+/// Expressions are displayed on a single line, [Printer::expr] breaks them when they're too long.
+impl Display for Expr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let expr = match self {
+            Expr::Variable(name) => name.clone(),
+            Expr::Literal(value) => value.clone(),
+            Expr::Binary { operator, left, right } => format!(
+                "{} {} {}",
+                operand(left, Some(operator.precedence()), false),
+                operator.as_str(),
+                operand(right, Some(operator.precedence()), true)
+            ),
+            Expr::Not(value) => format!("!{}", operand(value, None, false)),
+            Expr::Desnap(value) => format!("*{}", operand(value, None, false)),
+            Expr::Ref(variable) => format!("ref {variable}"),
+            // `else if` chains are rendered naturally as the else value is an if expression itself.
+            Expr::If { condition, then_value, else_value } => match else_value.as_ref() {
+                Expr::If { .. } => format!("if {condition} {{ {then_value} }} else {else_value}"),
+                _ => format!("if {condition} {{ {then_value} }} else {{ {else_value} }}"),
+            },
+            Expr::Call { function, arguments } => format!("{function}({})", list(arguments)),
+            Expr::MethodCall { receiver, method, arguments } => {
+                format!("{}.{method}({})", operand(receiver, None, false), list(arguments))
+            }
+            Expr::Index { array, index } => format!("{}[{index}]", operand(array, None, false)),
+            Expr::Tuple(elements) if elements.len() == 1 => format!("({},)", elements[0]),
+            Expr::Tuple(elements) => format!("({})", list(elements)),
+            Expr::FixedArray(elements) => format!("[{}]", list(elements)),
+            Expr::Struct { name, fields } => format!(
+                "{name} {{ {} }}",
+                fields.iter().map(|(field, value)| format!("{field}: {value}")).collect::<Vec<_>>().join(", ")
+            ),
+            Expr::Panic { message, arguments } if arguments.is_empty() => format!("panic!(\"{message}\")"),
+            Expr::Panic { message, arguments } => format!("panic!(\"{message}\", {})", list(arguments)),
+            Expr::Match { value, arms } => format!(
+                "match {value} {{ {} }}",
+                arms.iter().map(|(pattern, arm)| format!("{pattern} => {arm}")).collect::<Vec<_>>().join(", ")
+            ),
+        };
+        f.write_str(&expr)
+    }
+}
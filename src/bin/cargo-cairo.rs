@@ -0,0 +1,318 @@
+//! `cargo cairo`: build a rust crate to LLVM IR with `cargo rustc` and translate it to a Scarb
+//! package, instead of compiling each file with `rustc --emit=llvm-ir` by hand.
+//! ```sh
+//! cargo install --path . && cd my_crate && cargo cairo --release
+//! ```
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{exit, Command};
+
+use llvm_to_cairo::ast::printer::FormatOptions;
+use llvm_to_cairo::builder::options::TranslationOptions;
+use llvm_to_cairo::compile_linked_package;
+
+const USAGE: &str = "Usage: cargo cairo [OPTIONS]
+
+Build a crate to LLVM IR with cargo rustc and translate it to a Scarb package. The codegen units
+of the crate are linked in a single cairo module.
+
+Options:
+      --manifest-path <PATH>
+                         Path to the Cargo.toml of the crate [default: the one of the current directory]
+  -p, --package <NAME>   Package of the workspace to build
+      --bin <NAME>       Build this binary instead of the library of the package
+      --release          Build with the release profile
+      --target-cpu <CPU> CPU LLVM optimizes for, a host CPU would use vector instructions the
+                         translation may not support [default: generic]
+      --overflow-checks  Keep rust's overflow checks, they panic like the cairo operators already do
+                         with --arithmetic native [default: off]
+  -o, --output <DIR>     Directory of the Scarb package [default: <target dir>/cairo/<crate name>]
+      --name <NAME>      Name of the Scarb package [default: name of the crate]
+      --contract <NAME>  Wrap the functions in a starknet contract named <NAME>
+      --export <FUNCTION>
+                         Expose <FUNCTION> as an entry point of the contract. Can be repeated
+      --entrypoint <FUNCTION>
+                         Make <FUNCTION> an entry point of the module. Can be repeated
+  -O, --optimize         Simplify the generated cairo code so it runs in fewer steps
+      --pre-opt <PRESET> Run LLVM passes that normalize the IR before translating it: none, minimal,
+                         aggressive or custom:<passes> [default: none]
+      --arithmetic <MODE>
+                         How the integer add and sub overflow: native, wrapping or checked
+                         [default: native]
+      --float <MODE>     How float and double are emulated: ieee or fixed [default: ieee]
+  -h, --help             Print this message
+
+See `llvm-to-cairo --help` for the details of the translation options.";
+
+/// Command line arguments.
+#[derive(Default)]
+struct Arguments {
+    manifest_path: Option<String>,
+    package: Option<String>,
+    bin: Option<String>,
+    release: bool,
+    target_cpu: Option<String>,
+    overflow_checks: bool,
+    output: Option<PathBuf>,
+    name: Option<String>,
+    options: TranslationOptions,
+    /// Functions to expose from the contract.
+    exports: Vec<String>,
+}
+
+/// Print the error and the usage then exit.
+fn fail(error: &str) -> ! {
+    eprintln!("error: {error}\n\n{USAGE}");
+    exit(1)
+}
+
+/// Print the error then exit, for the errors that aren't caused by the arguments.
+fn error(message: &str) -> ! {
+    eprintln!("error: {message}");
+    exit(1)
+}
+
+fn parse_arguments() -> Arguments {
+    let mut arguments = Arguments::default();
+    let mut args = std::env::args().skip(1).peekable();
+    // Cargo runs `cargo-cairo cairo <args>` for `cargo cairo <args>`.
+    if args.peek().is_some_and(|arg| arg == "cairo") {
+        args.next();
+    }
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => {
+                println!("{USAGE}");
+                exit(0)
+            }
+            "--manifest-path" => {
+                arguments.manifest_path = Some(args.next().unwrap_or_else(|| fail("--manifest-path expects a path")))
+            }
+            "-p" | "--package" => {
+                arguments.package = Some(args.next().unwrap_or_else(|| fail("--package expects a name")))
+            }
+            "--bin" => arguments.bin = Some(args.next().unwrap_or_else(|| fail("--bin expects a name"))),
+            "--release" => arguments.release = true,
+            "--target-cpu" => {
+                arguments.target_cpu = Some(args.next().unwrap_or_else(|| fail("--target-cpu expects a cpu")))
+            }
+            "--overflow-checks" => arguments.overflow_checks = true,
+            "-o" | "--output" => {
+                arguments.output = Some(args.next().unwrap_or_else(|| fail("--output expects a directory")).into())
+            }
+            "--name" => arguments.name = Some(args.next().unwrap_or_else(|| fail("--name expects a value"))),
+            // The translation options are the ones of `llvm-to-cairo`.
+            _ => match arguments.options.parse_argument(&arg, &mut args, &mut arguments.exports) {
+                Ok(true) => (),
+                Ok(false) => fail(&format!("unknown argument {arg}")),
+                Err(error) => fail(&error),
+            },
+        }
+    }
+    let exports = std::mem::take(&mut arguments.exports);
+    arguments.options.set_exports(exports).unwrap_or_else(|error| fail(&error));
+    arguments
+}
+
+/// The cargo that runs this subcommand, or the one in the path when it's run directly.
+fn cargo() -> Command {
+    Command::new(std::env::var("CARGO").unwrap_or_else(|_| "cargo".to_owned()))
+}
+
+/// Target directory of the workspace, read from `cargo metadata`.
+fn target_directory(arguments: &Arguments) -> PathBuf {
+    let mut command = cargo();
+    command.args(["metadata", "--format-version", "1", "--no-deps"]);
+    if let Some(manifest_path) = &arguments.manifest_path {
+        command.args(["--manifest-path", manifest_path]);
+    }
+    let output = command.output().unwrap_or_else(|e| error(&format!("can't run cargo metadata: {e}")));
+    if !output.status.success() {
+        error(&format!("cargo metadata failed:\n{}", String::from_utf8_lossy(&output.stderr)));
+    }
+    parse_target_directory(&String::from_utf8_lossy(&output.stdout))
+        .unwrap_or_else(|| error("cargo metadata didn't give the target directory"))
+}
+
+/// Read the target directory in the output of `cargo metadata`. The path is a plain JSON string,
+/// no need for a JSON parser.
+fn parse_target_directory(metadata: &str) -> Option<PathBuf> {
+    let (_, rest) = metadata.split_once("\"target_directory\":\"")?;
+    let mut path = String::new();
+    let mut chars = rest.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => return Some(PathBuf::from(path)),
+            '\\' => path.extend(chars.next()),
+            c => path.push(c),
+        }
+    }
+    None
+}
+
+/// `cargo rustc` command that builds the crate to LLVM IR in `target_dir`.
+fn rustc_command(arguments: &Arguments, target_dir: &Path) -> Command {
+    let mut command = cargo();
+    command.arg("rustc");
+    if let Some(manifest_path) = &arguments.manifest_path {
+        command.args(["--manifest-path", manifest_path]);
+    }
+    if let Some(package) = &arguments.package {
+        command.args(["--package", package]);
+    }
+    match &arguments.bin {
+        Some(bin) => command.args(["--bin", bin]),
+        None => command.arg("--lib"),
+    };
+    if arguments.release {
+        command.arg("--release");
+    }
+    // The IR is built in its own target directory so the `.ll` files in it are only the ones of
+    // this subcommand, the regular builds are untouched.
+    command.env("CARGO_TARGET_DIR", target_dir);
+    command.arg("--").arg("--emit=llvm-ir");
+    // There is no unwinding in cairo, a rust panic becomes a cairo panic.
+    command.args(["-C", "panic=abort"]);
+    let overflow_checks = if arguments.overflow_checks { "on" } else { "off" };
+    command.args(["-C", &format!("overflow-checks={overflow_checks}")]);
+    let target_cpu = arguments.target_cpu.as_deref().unwrap_or("generic");
+    command.args(["-C", &format!("target-cpu={target_cpu}")]);
+    command
+}
+
+/// Get the `.ll` files in `directory`.
+fn ir_files(directory: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(directory) else {
+        return Vec::new();
+    };
+    let mut files = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "ll"))
+        .collect::<Vec<_>>();
+    files.sort();
+    files
+}
+
+/// Move the `.ll` files rustc emitted in `deps` to `ir_directory`, in place of the files of the
+/// previous build whose codegen units may not be the same. Does nothing if there are none.
+fn move_ir_files(deps: &Path, ir_directory: &Path) -> std::io::Result<()> {
+    let emitted = ir_files(deps);
+    if emitted.is_empty() {
+        return Ok(());
+    }
+    for path in ir_files(ir_directory) {
+        fs::remove_file(path)?;
+    }
+    fs::create_dir_all(ir_directory)?;
+    for path in emitted {
+        fs::rename(&path, ir_directory.join(path.file_name().expect("Should be a file")))?;
+    }
+    Ok(())
+}
+
+/// Name of the crate a `.ll` file was emitted for: `<crate>-<hash>.ll`, or
+/// `<crate>-<hash>.<codegen unit>.rcgu.ll` when there are several codegen units.
+fn crate_name(path: &Path) -> String {
+    let file_name = path.file_name().expect("Should be a file").to_string_lossy();
+    file_name.split(['-', '.']).next().expect("Split should return at least one item").to_owned()
+}
+
+fn main() {
+    let arguments = parse_arguments();
+    let target_directory = target_directory(&arguments);
+    let build_directory = target_directory.join("cairo").join("build");
+    let profile = if arguments.release { "release" } else { "debug" };
+    let deps = build_directory.join(profile).join("deps");
+    let ir_directory = build_directory.join(profile).join("ir");
+    let collect_ir_files =
+        || move_ir_files(&deps, &ir_directory).unwrap_or_else(|e| error(&format!("can't move the LLVM IR files: {e}")));
+
+    // rustc emits the `.ll` files in `deps` and they are moved out after each build, so there is
+    // none in `deps` when cargo runs and the ones found after it were emitted by this build. The
+    // files an interrupted run left are moved first, they come from the last compilation.
+    collect_ir_files();
+    let status = rustc_command(&arguments, &build_directory)
+        .status()
+        .unwrap_or_else(|e| error(&format!("can't run cargo rustc: {e}")));
+    if !status.success() {
+        exit(status.code().unwrap_or(1));
+    }
+
+    // When cargo finds the crate up to date nothing is emitted and the files of the previous build,
+    // with the same flags, are still valid.
+    collect_ir_files();
+    let inputs = ir_files(&ir_directory);
+    if inputs.is_empty() {
+        error(&format!("no LLVM IR was emitted in {}, try `cargo clean` and run again", deps.display()));
+    }
+
+    let name = arguments.name.clone().unwrap_or_else(|| crate_name(&inputs[0]));
+    let output = arguments.output.clone().unwrap_or_else(|| target_directory.join("cairo").join(&name));
+    let inputs = inputs.iter().map(|path| path.to_str().expect("Path should be valid UTF-8")).collect::<Vec<_>>();
+    let package = compile_linked_package(&name, &inputs, arguments.options, FormatOptions::default())
+        .unwrap_or_else(|message| error(&message));
+    package.write(&output).unwrap_or_else(|e| error(&format!("can't write the package: {e}")));
+    println!("Scarb package {} written in {}", package.name(), output.display());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_crate_name() {
+        assert_eq!(crate_name(Path::new("deps/my_crate-0123456789abcdef.ll")), "my_crate");
+        assert_eq!(crate_name(Path::new("deps/my_crate-0123456789abcdef.my_crate.a1b2c3-cgu.0.rcgu.ll")), "my_crate");
+    }
+
+    #[test]
+    fn test_target_directory() {
+        let metadata = r#"{"packages":[],"target_directory":"C:\\my \"crate\"\\target","version":1}"#;
+        assert_eq!(parse_target_directory(metadata), Some(PathBuf::from(r#"C:\my "crate"\target"#)));
+        assert_eq!(parse_target_directory(r#"{"target_directory":"/unterminated"#), None);
+        assert_eq!(parse_target_directory("{}"), None);
+
+        // The target directory of this crate, unless it's moved by the environment.
+        let manifest_directory = Path::new(env!("CARGO_MANIFEST_DIR"));
+        let arguments = Arguments {
+            manifest_path: Some(manifest_directory.join("Cargo.toml").to_str().unwrap().to_owned()),
+            ..Arguments::default()
+        };
+        let expected = match std::env::var_os("CARGO_TARGET_DIR") {
+            Some(directory) => manifest_directory.join(directory),
+            None => manifest_directory.join("target"),
+        };
+        assert_eq!(target_directory(&arguments), expected);
+    }
+
+    #[test]
+    fn test_ir_files() {
+        // The process id keeps the concurrent runs of the test apart.
+        let directory = std::env::temp_dir().join(format!("llvm-to-cairo-ir-files-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        let deps = directory.join("deps");
+        let ir_directory = directory.join("ir");
+        assert!(ir_files(&deps).is_empty());
+
+        fs::create_dir_all(&deps).unwrap();
+        for file in ["b-1.b.rcgu.ll", "a-1.a.rcgu.ll", "liba-1.rlib", "a-1.d"] {
+            fs::write(deps.join(file), "").unwrap();
+        }
+        assert_eq!(ir_files(&deps), [deps.join("a-1.a.rcgu.ll"), deps.join("b-1.b.rcgu.ll")]);
+
+        // The files of a build replace the ones of the previous build.
+        fs::create_dir_all(&ir_directory).unwrap();
+        fs::write(ir_directory.join("a-0.old.rcgu.ll"), "").unwrap();
+        move_ir_files(&deps, &ir_directory).unwrap();
+        assert!(ir_files(&deps).is_empty());
+        assert!(deps.join("liba-1.rlib").exists());
+        assert_eq!(ir_files(&ir_directory), [ir_directory.join("a-1.a.rcgu.ll"), ir_directory.join("b-1.b.rcgu.ll")]);
+
+        // Nothing emitted, the files of the previous build are kept.
+        move_ir_files(&deps, &ir_directory).unwrap();
+        assert_eq!(ir_files(&ir_directory).len(), 2);
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,210 @@
+// Emulated memory of the translated program and the allocator behind `__rust_alloc` and friends.
+// The memory is byte addressed and the bytes that were never written are 0. The allocator is a bump
+// allocator: the blocks are allocated one after the other, only the last one can be freed or
+// resized in place and the space of the other freed blocks is never reused. The `load` and `store`
+// instructions read and write the integers in little-endian like the targets rust compiles to.
+
+use core::dict::Felt252Dict;
+
+#[derive(Destruct)]
+pub struct Memory {
+    bytes: Felt252Dict<u8>,
+    // First address that was never allocated.
+    top: u64,
+    // Address of the last allocated block, 0 if it was freed.
+    last: u64,
+}
+
+#[generate_trait]
+pub impl MemoryImpl of MemoryTrait {
+    fn new() -> Memory {
+        // Address 0 is `null`, it's never allocated.
+        Memory { bytes: Default::default(), top: 1, last: 0 }
+    }
+
+    fn read_u8(ref self: Memory, address: u64) -> u8 {
+        self.bytes.get(address.into())
+    }
+
+    fn write_u8(ref self: Memory, address: u64, value: u8) {
+        self.bytes.insert(address.into(), value);
+    }
+
+    // Set `size` bytes to `value` from `address`.
+    fn fill(ref self: Memory, address: u64, size: u64, value: u8) {
+        let mut offset = 0;
+        while offset != size {
+            self.write_u8(address + offset, value);
+            offset += 1;
+        }
+    }
+
+    // Read the `size` bytes at `address` as a little-endian unsigned integer.
+    fn read(ref self: Memory, address: u64, size: u64) -> u128 {
+        let mut value: u128 = 0;
+        let mut offset = size;
+        while offset != 0 {
+            offset -= 1;
+            let byte: u128 = self.read_u8(address + offset).into();
+            value = value * 0x100 + byte;
+        }
+        value
+    }
+
+    // Write the `size` low bytes of `value` at `address`, little-endian.
+    fn write(ref self: Memory, address: u64, size: u64, value: u128) {
+        let mut value = value;
+        let mut offset = 0;
+        while offset != size {
+            self.write_u8(address + offset, (value % 0x100).try_into().unwrap());
+            value /= 0x100;
+            offset += 1;
+        }
+    }
+
+    // Copy `size` bytes from `from` to `to`, the ranges must not overlap.
+    fn copy(ref self: Memory, from: u64, to: u64, size: u64) {
+        let mut offset = 0;
+        while offset != size {
+            let byte = self.read_u8(from + offset);
+            self.write_u8(to + offset, byte);
+            offset += 1;
+        }
+    }
+}
+
+// The sizes and alignments are `usize`s, they're translated as `i64`s with the same bit pattern.
+fn usize(value: i64) -> u64 {
+    value.try_into().expect('allocation size overflow')
+}
+
+fn align_up(address: u64, align: u64) -> u64 {
+    (address + align - 1) / align * align
+}
+
+pub fn rust_alloc(ref memory: Memory, size: i64, align: i64) -> u64 {
+    let address = align_up(memory.top, usize(align));
+    memory.top = address + usize(size);
+    memory.last = address;
+    address
+}
+
+pub fn rust_alloc_zeroed(ref memory: Memory, size: i64, align: i64) -> u64 {
+    let address = rust_alloc(ref memory, size, align);
+    // The space of a freed last block is reused, it might not be zeroed anymore.
+    memory.fill(address, usize(size), 0);
+    address
+}
+
+pub fn rust_dealloc(ref memory: Memory, ptr: u64, _size: i64, _align: i64) {
+    // Only the last block gives its space back.
+    if ptr != 0 && ptr == memory.last {
+        memory.top = ptr;
+        memory.last = 0;
+    }
+}
+
+pub fn rust_realloc(ref memory: Memory, ptr: u64, old_size: i64, align: i64, new_size: i64) -> u64 {
+    // The last block is resized in place.
+    if ptr != 0 && ptr == memory.last {
+        memory.top = ptr + usize(new_size);
+        return ptr;
+    }
+    let new_ptr = rust_alloc(ref memory, new_size, align);
+    let (old_size, new_size) = (usize(old_size), usize(new_size));
+    memory.copy(ptr, new_ptr, if old_size < new_size {
+        old_size
+    } else {
+        new_size
+    });
+    new_ptr
+}
+
+// Address `index` elements of `size` bytes after `address`, for `getelementptr`. The index can be
+// negative.
+pub fn ptr_add(address: u64, index: felt252, size: felt252) -> u64 {
+    let felt: felt252 = address.into();
+    (felt + index * size).try_into().expect('address out of range')
+}
+
+// The signed integer with the bit pattern `bits`, `sign_bit` is the highest bit of its type.
+fn signed(bits: u128, sign_bit: u128) -> felt252 {
+    let felt: felt252 = bits.into();
+    let sign: felt252 = sign_bit.into();
+    if bits < sign_bit {
+        felt
+    } else {
+        felt - 2 * sign
+    }
+}
+
+// The bit pattern of the signed integer `value`, `modulus` is `2^width`.
+fn unsigned(value: felt252, negative: bool, modulus: felt252) -> u128 {
+    let felt = if negative {
+        value + modulus
+    } else {
+        value
+    };
+    felt.try_into().unwrap()
+}
+
+pub fn load_bool(ref memory: Memory, address: u64) -> bool {
+    memory.read_u8(address) != 0
+}
+
+pub fn store_bool(ref memory: Memory, address: u64, value: bool) {
+    memory.write_u8(address, if value {
+        1
+    } else {
+        0
+    });
+}
+
+pub fn load_i8(ref memory: Memory, address: u64) -> i8 {
+    signed(memory.read(address, 1), 0x80).try_into().unwrap()
+}
+
+pub fn store_i8(ref memory: Memory, address: u64, value: i8) {
+    memory.write(address, 1, unsigned(value.into(), value < 0, 0x100));
+}
+
+pub fn load_i16(ref memory: Memory, address: u64) -> i16 {
+    signed(memory.read(address, 2), 0x8000).try_into().unwrap()
+}
+
+pub fn store_i16(ref memory: Memory, address: u64, value: i16) {
+    memory.write(address, 2, unsigned(value.into(), value < 0, 0x10000));
+}
+
+pub fn load_i32(ref memory: Memory, address: u64) -> i32 {
+    signed(memory.read(address, 4), 0x80000000).try_into().unwrap()
+}
+
+pub fn store_i32(ref memory: Memory, address: u64, value: i32) {
+    memory.write(address, 4, unsigned(value.into(), value < 0, 0x100000000));
+}
+
+pub fn load_i64(ref memory: Memory, address: u64) -> i64 {
+    signed(memory.read(address, 8), 0x8000000000000000).try_into().unwrap()
+}
+
+pub fn store_i64(ref memory: Memory, address: u64, value: i64) {
+    memory.write(address, 8, unsigned(value.into(), value < 0, 0x10000000000000000));
+}
+
+pub fn load_i128(ref memory: Memory, address: u64) -> i128 {
+    signed(memory.read(address, 16), 0x80000000000000000000000000000000).try_into().unwrap()
+}
+
+pub fn store_i128(ref memory: Memory, address: u64, value: i128) {
+    memory.write(address, 16, unsigned(value.into(), value < 0, 0x100000000000000000000000000000000));
+}
+
+// Pointers are `u64` addresses.
+pub fn load_ptr(ref memory: Memory, address: u64) -> u64 {
+    memory.read(address, 8).try_into().unwrap()
+}
+
+pub fn store_ptr(ref memory: Memory, address: u64, value: u64) {
+    memory.write(address, 8, value.into());
+}
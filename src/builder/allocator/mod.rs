@@ -0,0 +1,138 @@
+//! Heap allocations. The rust allocator functions (`__rust_alloc`, `__rust_dealloc`,
+//! `__rust_realloc` and `__rust_alloc_zeroed`) are lowered to the functions of a cairo allocator
+//! emitted with the translated code. It allocates in the emulated memory the pointers point to (see
+//! [crate::builder::types::POINTER_TYPE]), a `Memory` value that is threaded through the functions
+//! that allocate, directly or through their callees:
+//! ```cairo
+//! pub fn make_box(ref memory: Memory, value: i32) -> u64 {
+//!     let ptr = rust_alloc(ref memory, 4_i64, 4_i64);
+//!     ...
+//! }
+//! ```
+//! The callers of the entry points that allocate create the memory with `MemoryTrait::new()`, the
+//! entry points of a contract do it themselves.
+//!
+//! The stack slots (`alloca`) are allocated in the same memory and never freed, `load` and `store`
+//! read and write it and `getelementptr` computes addresses in it, see
+//! [crate::builder::function::memory]. The functions that access the memory get it like the ones
+//! that allocate.
+
+use std::collections::HashSet;
+
+use super::contract::CairoContract;
+use super::function::CairoParameter;
+use super::CairoBuilder;
+use crate::ast::Expr;
+
+/// Name of the module the allocator is in when the translation is a Scarb package.
+pub const MODULE_NAME: &str = "allocator";
+
+/// Cairo type of the emulated memory.
+pub const MEMORY_TYPE: &str = "Memory";
+
+/// Name of the memory parameter of the functions that allocate.
+pub const MEMORY_PARAMETER: &str = "memory";
+
+/// Rust allocator functions and the function of the cairo allocator they're lowered to. They keep
+/// the arguments of the rust functions.
+const ALLOCATOR_FUNCTIONS: [(&str, &str); 4] = [
+    ("__rust_alloc", "rust_alloc"),
+    ("__rust_alloc_zeroed", "rust_alloc_zeroed"),
+    ("__rust_dealloc", "rust_dealloc"),
+    ("__rust_realloc", "rust_realloc"),
+];
+
+/// Types the `load` and `store` instructions can access. They're the suffixes of the functions of
+/// the allocator that do it: `load_i32(ref memory, address)`, `store_i32(ref memory, address,
+/// value)`.
+pub const MEMORY_ACCESS_TYPES: [&str; 7] = ["bool", "i8", "i16", "i32", "i64", "i128", "ptr"];
+
+/// Function of the allocator that computes the addresses of `getelementptr`. It doesn't need the
+/// memory.
+pub const POINTER_ADD: &str = "ptr_add";
+
+/// Cairo code of the allocator, indented with 4 spaces.
+pub fn library() -> String {
+    include_str!("allocator.cairo").trim().to_owned()
+}
+
+/// Get the function of the cairo allocator a call to `symbol` is lowered to.
+pub fn allocator_function(symbol: &str) -> Option<&'static str> {
+    ALLOCATOR_FUNCTIONS.iter().find(|(rust, _)| *rust == symbol).map(|(_, cairo)| *cairo)
+}
+
+/// Names of the functions of the allocator that read and write the memory: `load_i32`,
+/// `store_ptr`...
+fn memory_access_functions() -> impl Iterator<Item = String> {
+    MEMORY_ACCESS_TYPES.iter().flat_map(|ty| [format!("load_{ty}"), format!("store_{ty}")])
+}
+
+/// Items of the allocator the translated code uses: the memory, its trait, the allocator functions
+/// and the functions that access the memory.
+pub fn is_library_item(name: &str) -> bool {
+    [MEMORY_TYPE, "MemoryTrait", POINTER_ADD].contains(&name)
+        || ALLOCATOR_FUNCTIONS.iter().any(|(_, cairo)| *cairo == name)
+        || memory_access_functions().any(|function| function == name)
+}
+
+impl<'ctx> CairoBuilder<'ctx> {
+    /// Thread the memory through the functions that allocate or access the memory: they get a
+    /// `ref memory: Memory` parameter and it's passed to them and to the allocator. The entry
+    /// points of the contract that allocate create it. The name is reserved in every function so no
+    /// value shadows it. Returns whether the allocator is used.
+    pub fn thread_memory(&mut self, contract: Option<&mut CairoContract>) -> bool {
+        // A function needs the memory if it calls a function that needs it, until nothing changes.
+        let mut uses_memory = ALLOCATOR_FUNCTIONS
+            .iter()
+            .map(|(_, cairo)| cairo.to_string())
+            .chain(memory_access_functions())
+            .collect::<HashSet<_>>();
+        loop {
+            let callers = self
+                .functions
+                .functions()
+                .iter()
+                .filter(|function| !uses_memory.contains(&function.signature.name))
+                .filter(|function| {
+                    let mut calls_memory_function = false;
+                    function.body.block().visit_exprs(&mut |expr| {
+                        if let Expr::Call { function: callee, .. } = expr {
+                            calls_memory_function |= uses_memory.contains(callee);
+                        }
+                    });
+                    calls_memory_function
+                })
+                .map(|function| function.signature.name.clone())
+                .collect::<Vec<_>>();
+            if callers.is_empty() {
+                break;
+            }
+            uses_memory.extend(callers);
+        }
+        // `ptr_add` is in the allocator but doesn't need the memory.
+        let mut uses_library = false;
+        for function in self.functions.0.iter_mut() {
+            function.body.block_mut().visit_exprs_mut(&mut |expr| {
+                if let Expr::Call { function: callee, arguments } = expr {
+                    uses_library |= is_library_item(callee);
+                    if uses_memory.contains(callee) {
+                        arguments.insert(0, Expr::reference(MEMORY_PARAMETER));
+                    }
+                }
+            });
+            if uses_memory.contains(&function.signature.name) {
+                function
+                    .signature
+                    .parameters
+                    .0
+                    .insert(0, CairoParameter::by_ref(MEMORY_PARAMETER.to_owned(), MEMORY_TYPE.to_owned()));
+            }
+        }
+        if let Some(contract) = contract {
+            for entry_point in contract.entry_points.iter_mut() {
+                entry_point.memory = uses_memory.contains(&entry_point.name);
+            }
+        }
+        uses_library
+    }
+}
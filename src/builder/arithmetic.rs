@@ -0,0 +1,228 @@
+//! Integer arithmetic with the LLVM semantics, see [ArithmeticMode]. The cairo operators panic on
+//! overflow so in the wrapping and checked modes `add` and `sub` become calls to helpers that are
+//! emitted after the functions of the module:
+//! ```cairo
+//! let sum = wrapping_add_i32(a, b); // add i32 %a, %b
+//! let sum = add_nuw_i32(a, b); // add nuw i32 %a, %b in checked mode
+//! ```
+//! The helpers are named after the operation, the flags that are checked and the type. They're
+//! computed on `felt252` so the overflows can be detected instead of panicking. Each function
+//! records the helpers it calls so the module only emits those.
+
+use super::function::{CairoFunction, CairoFunctionBuilder, CairoFunctionSignature, CairoParameter};
+use super::options::ArithmeticMode;
+use crate::ast::{BinaryOperator, Block, Expr, Pattern, Statement};
+
+/// Helper that computes `a <operation> b` on `i<width>` with the LLVM semantics.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct ArithmeticHelper {
+    /// [BinaryOperator::Add] or [BinaryOperator::Sub].
+    pub(crate) operation: BinaryOperator,
+    pub(crate) width: u32,
+    /// Panic if the operation wraps around as an unsigned operation.
+    pub(crate) nuw: bool,
+    /// Panic if the operation wraps around as a signed operation.
+    pub(crate) nsw: bool,
+}
+
+impl ArithmeticHelper {
+    /// `wrapping_add_i32` when nothing is checked, `add_nuw_nsw_i32` otherwise.
+    pub fn name(&self) -> String {
+        let operation = operation_name(self.operation).expect("Arithmetic helpers are only for add and sub");
+        if !self.nuw && !self.nsw {
+            return format!("wrapping_{operation}_i{}", self.width);
+        }
+        let nuw = if self.nuw { "_nuw" } else { "" };
+        let nsw = if self.nsw { "_nsw" } else { "" };
+        format!("{operation}{nuw}{nsw}_i{}", self.width)
+    }
+
+    /// Parse the name of a helper, see [ArithmeticHelper::name].
+    pub fn parse(name: &str) -> Option<Self> {
+        let (name, width) = name.rsplit_once("_i")?;
+        let width = width.parse().ok().filter(|width| (2..=128).contains(width))?;
+        let (operation, nuw, nsw) = match name.strip_prefix("wrapping_") {
+            Some(operation) => (operation, false, false),
+            None => {
+                let (name, nsw) = name.strip_suffix("_nsw").map_or((name, false), |name| (name, true));
+                let (name, nuw) = name.strip_suffix("_nuw").map_or((name, false), |name| (name, true));
+                if !nuw && !nsw {
+                    return None;
+                }
+                (name, nuw, nsw)
+            }
+        };
+        let operation = match operation {
+            "add" => BinaryOperator::Add,
+            "sub" => BinaryOperator::Sub,
+            _ => return None,
+        };
+        Some(Self { operation, width, nuw, nsw })
+    }
+
+    /// The `2^width` modulus of the integers, as a hex literal.
+    pub fn modulus(&self) -> String {
+        format!("{:#x}{}", 1 << (self.width % 4), "0".repeat(self.width as usize / 4))
+    }
+
+    /// The cairo function of the helper. The operation is computed on `felt252`, checked against
+    /// the flags of the helper and wrapped around to `i<width>`:
+    /// ```cairo
+    /// fn wrapping_add_i32(a: i32, b: i32) -> i32 {
+    ///     let a_felt: felt252 = a.into();
+    ///     let b_felt: felt252 = b.into();
+    ///     let result = a_felt + b_felt;
+    ///     match result.try_into() {
+    ///         Option::Some(result) => result,
+    ///         Option::None => if a < 0 {
+    ///             (result + 0x100000000).try_into().unwrap()
+    ///         } else {
+    ///             (result - 0x100000000).try_into().unwrap()
+    ///         },
+    ///     }
+    /// }
+    /// ```
+    pub fn function(&self) -> CairoFunction {
+        let operation = operation_name(self.operation).expect("Arithmetic helpers are only for add and sub");
+        let ty = format!("i{}", self.width);
+        let modulus = self.modulus();
+        let into = |value: Expr| Expr::method_call(value, "into", Vec::new());
+        let is_negative = |name: &str| Expr::binary(BinaryOperator::Lt, Expr::variable(name), Expr::literal("0"));
+        let mut body = ["a", "b"]
+            .map(|name| Statement::Let {
+                pattern: Pattern::typed(format!("{name}_felt"), "felt252"),
+                value: into(Expr::variable(name)),
+            })
+            .to_vec();
+        if self.nuw {
+            // The operands as the unsigned integers with the same bit pattern.
+            for name in ["a", "b"] {
+                let felt = Expr::variable(format!("{name}_felt"));
+                let unsigned = Expr::if_else(
+                    is_negative(name),
+                    into(Expr::binary(BinaryOperator::Add, felt.clone(), Expr::literal(&modulus))),
+                    into(felt),
+                );
+                body.push(Statement::Let {
+                    pattern: Pattern::typed(format!("{name}_unsigned"), "u256"),
+                    value: unsigned,
+                });
+            }
+            let (a, b) = (Expr::variable("a_unsigned"), Expr::variable("b_unsigned"));
+            let overflow = match self.operation {
+                BinaryOperator::Add => Expr::binary(
+                    BinaryOperator::Ge,
+                    Expr::binary(BinaryOperator::Add, a, b),
+                    Expr::literal(format!("{modulus}_u256")),
+                ),
+                _ => Expr::binary(BinaryOperator::Lt, a, b),
+            };
+            let overflowed = Statement::Expr(Expr::panic(format!("{operation} nuw overflowed")));
+            body.push(Statement::If {
+                condition: overflow,
+                then_block: Block::new(vec![overflowed]),
+                else_block: None,
+            });
+        }
+        let result = Expr::binary(self.operation, Expr::variable("a_felt"), Expr::variable("b_felt"));
+        body.push(Statement::declare("result", result));
+        let out_of_range = if self.nsw {
+            Expr::panic(format!("{operation} nsw overflowed"))
+        } else {
+            // The result is out of range by one modulus, below it if `a` is negative.
+            let wrap = |operator| {
+                let wrapped = Expr::binary(operator, Expr::variable("result"), Expr::literal(&modulus));
+                Expr::method_call(Expr::method_call(wrapped, "try_into", Vec::new()), "unwrap", Vec::new())
+            };
+            Expr::if_else(is_negative("a"), wrap(BinaryOperator::Add), wrap(BinaryOperator::Sub))
+        };
+        body.push(Statement::Tail(Expr::match_arms(
+            Expr::method_call(Expr::variable("result"), "try_into", Vec::new()),
+            vec![
+                (Pattern::variant("Option::Some", Some(Pattern::variable("result"))), Expr::variable("result")),
+                (Pattern::variant("Option::None", None), out_of_range),
+            ],
+        )));
+        let parameters = ["a", "b"].map(|name| CairoParameter::new(name.to_owned(), ty.clone())).to_vec();
+        let mut signature = CairoFunctionSignature::new(self.name(), parameters, ty);
+        signature.public = false;
+        CairoFunction::new(signature, Block::new(body))
+    }
+}
+
+/// Names of all the helpers that can be emitted in a module, the translated functions can't take
+/// them.
+pub fn helper_names() -> impl Iterator<Item = String> {
+    (2..=128).flat_map(|width| {
+        [BinaryOperator::Add, BinaryOperator::Sub].into_iter().flat_map(move |operation| {
+            [(false, false), (true, false), (false, true), (true, true)]
+                .map(|(nuw, nsw)| ArithmeticHelper { operation, width, nuw, nsw }.name())
+        })
+    })
+}
+
+/// `add` or `sub`, the only operations that have helpers.
+fn operation_name(operation: BinaryOperator) -> Option<&'static str> {
+    match operation {
+        BinaryOperator::Add => Some("add"),
+        BinaryOperator::Sub => Some("sub"),
+        _ => None,
+    }
+}
+
+/// The helper that computes `<operator>` on `i<width>` integers with the `nuw`/`nsw` flags of the
+/// instruction in the given mode, `None` if the cairo operator does. Only `add` and `sub` depend on
+/// the mode, the booleans (`i1`) use the cairo operators.
+pub fn arithmetic_helper(
+    mode: ArithmeticMode,
+    operator: BinaryOperator,
+    width: u32,
+    (nuw, nsw): (bool, bool),
+) -> Option<ArithmeticHelper> {
+    if operation_name(operator).is_none() || width == 1 {
+        return None;
+    }
+    match mode {
+        ArithmeticMode::Native => None,
+        // The flags only make the overflows poison, wrapping around is a valid result.
+        ArithmeticMode::Wrapping => Some(ArithmeticHelper { operation: operator, width, nuw: false, nsw: false }),
+        // The cairo operators panic on signed overflow, which is exactly the `nsw` check.
+        ArithmeticMode::Checked if nsw && !nuw => None,
+        ArithmeticMode::Checked => Some(ArithmeticHelper { operation: operator, width, nuw, nsw }),
+    }
+}
+
+impl<'ctx> CairoFunctionBuilder<'ctx> {
+    /// Translate `left <operator> right` on `i<width>` integers, see [arithmetic_helper]. The
+    /// helper it calls, if any, is recorded in the function.
+    pub fn int_arithmetic(
+        &mut self,
+        operator: BinaryOperator,
+        left: Expr,
+        right: Expr,
+        width: u32,
+        flags: (bool, bool),
+    ) -> Expr {
+        match arithmetic_helper(self.options.arithmetic_mode, operator, width, flags) {
+            Some(helper) => {
+                if !self.function.arithmetic_helpers.contains(&helper) {
+                    self.function.arithmetic_helpers.push(helper);
+                }
+                Expr::call(helper.name(), vec![left, right])
+            }
+            None => Expr::binary(operator, left, right),
+        }
+    }
+}
+
+/// The helpers the functions call, in the order of their names.
+pub fn arithmetic_helpers(functions: &[CairoFunction]) -> Vec<ArithmeticHelper> {
+    let mut helpers = Vec::new();
+    for helper in functions.iter().flat_map(|function| function.arithmetic_helpers.iter()) {
+        if !helpers.contains(helper) {
+            helpers.push(*helper);
+        }
+    }
+    helpers.sort_by_key(ArithmeticHelper::name);
+    helpers
+}
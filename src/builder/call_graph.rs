@@ -0,0 +1,140 @@
+//! Graph of the calls between the functions of a module, like the graph of the basic blocks of a
+//! function in `preprocess_function`.
+//!
+//! Cairo functions can call each other whatever their order in the file so recursion and mutual
+//! recursion are translated as plain calls. The graph is used to:
+//! * emit the callers before their callees so the file reads from the entry points down to the
+//!   helpers, the functions of a recursive cycle are kept together,
+//! * know which functions are recursive (a strongly connected component with more than one function
+//!   or a function that calls itself), the cost estimation counts a single call of them.
+
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+
+use inkwell::module::Module;
+use inkwell::values::{FunctionValue, InstructionOpcode};
+use petgraph::algo::tarjan_scc;
+use petgraph::graph::{DiGraph, NodeIndex};
+
+use super::function::CairoFunctionBuilder;
+use super::CairoBuilder;
+use crate::ast::Expr;
+
+/// Calls between the functions defined in a module. Calls to declared functions (intrinsics,
+/// other modules...) and indirect calls aren't in the graph.
+#[derive(Default, Debug)]
+pub struct CallGraph<'ctx> {
+    /// Caller => callee, the nodes are in the order of the module.
+    graph: DiGraph<FunctionValue<'ctx>, ()>,
+    nodes: HashMap<FunctionValue<'ctx>, NodeIndex>,
+}
+
+impl<'ctx> CallGraph<'ctx> {
+    pub fn new(module: &Module<'ctx>) -> Self {
+        let mut call_graph = Self::default();
+        for function in module.get_functions().filter(|function| function.count_basic_blocks() > 0) {
+            let node = call_graph.graph.add_node(function);
+            call_graph.nodes.insert(function, node);
+        }
+        for function in module.get_functions().filter(|function| function.count_basic_blocks() > 0) {
+            let caller = call_graph.nodes[&function];
+            let calls = function
+                .get_basic_block_iter()
+                .flat_map(|bb| bb.get_instructions())
+                .filter(|instruction| instruction.get_opcode() == InstructionOpcode::Call);
+            for call in calls {
+                let callee = CairoFunctionBuilder::get_called_function_name(&call);
+                if let Some(&callee) = module.get_function(&callee).and_then(|callee| call_graph.nodes.get(&callee)) {
+                    // Calling the same function twice is still one edge.
+                    call_graph.graph.update_edge(caller, callee, ());
+                }
+            }
+        }
+        call_graph
+    }
+
+    /// Strongly connected components: the functions that are (mutually) recursive are in the same
+    /// component. The components are in the order of the module.
+    fn components(&self) -> Vec<Vec<NodeIndex>> {
+        let mut components = tarjan_scc(&self.graph);
+        components.iter_mut().for_each(|component| component.sort());
+        components.sort();
+        components
+    }
+
+    /// The recursive cycles: the functions that can call themselves, directly or through the other
+    /// functions of their cycle.
+    pub fn recursive_cycles(&self) -> Vec<Vec<FunctionValue<'ctx>>> {
+        self.components()
+            .into_iter()
+            .filter(|component| component.len() > 1 || self.graph.contains_edge(component[0], component[0]))
+            .map(|component| component.into_iter().map(|node| self.graph[node]).collect())
+            .collect()
+    }
+
+    /// Functions that can call themselves, directly or through other functions.
+    pub fn recursive_functions(&self) -> HashSet<FunctionValue<'ctx>> {
+        self.recursive_cycles().into_iter().flatten().collect()
+    }
+
+    /// Order in which to emit the functions: a caller comes before its callees unless they're in
+    /// the same recursive cycle, the functions of a cycle are next to each other. When there is a
+    /// choice the order of the module is kept.
+    pub fn emission_order(&self) -> Vec<FunctionValue<'ctx>> {
+        let components = self.components();
+        let component_of = components
+            .iter()
+            .enumerate()
+            .flat_map(|(index, component)| component.iter().map(move |node| (*node, index)))
+            .collect::<HashMap<_, _>>();
+        // Edges of the condensed graph, which doesn't have cycles.
+        let mut callees = vec![HashSet::new(); components.len()];
+        let mut callers_count = vec![0; components.len()];
+        for edge in self.graph.raw_edges() {
+            let (caller, callee) = (component_of[&edge.source()], component_of[&edge.target()]);
+            if caller != callee && callees[caller].insert(callee) {
+                callers_count[callee] += 1;
+            }
+        }
+        // Kahn's algorithm, the components are numbered in the order of the module so taking the
+        // smallest available one keeps that order when possible.
+        let mut available = (0..components.len())
+            .filter(|component| callers_count[*component] == 0)
+            .map(Reverse)
+            .collect::<BinaryHeap<_>>();
+        let mut order = Vec::with_capacity(self.graph.node_count());
+        while let Some(Reverse(component)) = available.pop() {
+            order.extend(components[component].iter().map(|node| self.graph[*node]));
+            for callee in callees[component].iter() {
+                callers_count[*callee] -= 1;
+                if callers_count[*callee] == 0 {
+                    available.push(Reverse(*callee));
+                }
+            }
+        }
+        order
+    }
+}
+
+impl<'ctx> CairoBuilder<'ctx> {
+    /// Flag the functions of the recursive cycles of `call_graph`. A function that still calls a
+    /// function of its cycle once translated is recursive: the only calls that disappear are the
+    /// calls to itself turned into a loop (see `convert_self_tail_calls`). Must be called after
+    /// all the functions are translated.
+    pub fn flag_recursive_functions(&mut self, call_graph: &CallGraph<'ctx>) {
+        for cycle in call_graph.recursive_cycles() {
+            let names = cycle
+                .iter()
+                .filter_map(|function| self.function_names.get(function.get_name().to_string_lossy().as_ref()))
+                .cloned()
+                .collect::<HashSet<_>>();
+            for function in self.functions.0.iter_mut().filter(|function| names.contains(&function.signature.name)) {
+                let mut calls_cycle = false;
+                function.body.block().visit_exprs(&mut |expr| {
+                    calls_cycle |= matches!(expr, Expr::Call { function, .. } if names.contains(function));
+                });
+                function.recursive = calls_cycle;
+            }
+        }
+    }
+}
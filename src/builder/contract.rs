@@ -0,0 +1,370 @@
+//! Starknet contract output. The translated functions are wrapped in a `#[starknet::contract]`
+//! module and the exported ones are exposed through an interface:
+//! ```cairo
+//! #[starknet::interface]
+//! pub trait IMath<TContractState> {
+//!     fn add(self: @TContractState, a: u32, b: u32) -> u32;
+//! }
+//!
+//! #[starknet::contract]
+//! pub mod math {
+//!     #[storage]
+//!     struct Storage {}
+//!
+//!     #[abi(embed_v0)]
+//!     impl MathImpl of super::IMath<ContractState> {
+//!         fn add(self: @ContractState, a: u32, b: u32) -> u32 {
+//!             return abi_from_i32(add(abi_to_i32(a), abi_to_i32(b)));
+//!         }
+//!     }
+//!
+//!     pub fn add(a: i32, b: i32) -> i32 { ... }
+//! }
+//! ```
+//! LLVM integers don't have a sign and the translation uses the signed cairo integers, at the ABI
+//! boundary they're unsigned integers with the same bit pattern (`-1_i32` <=> `0xffffffff_u32`) as
+//! that's what the callers usually expect.
+
+use std::collections::HashSet;
+use std::fmt::Display;
+
+use inkwell::attributes::AttributeLoc;
+use inkwell::module::Module;
+use inkwell::values::BasicValueEnum;
+
+use super::allocator::MEMORY_PARAMETER;
+use super::function::{CairoFunction, CairoFunctionSignature, CairoParameter};
+use super::naming::sanitize_identifier;
+use super::options::ContractOptions;
+use super::types::CairoStruct;
+use crate::ast::{BinaryOperator, Block, Expr, Item, Pattern, Statement};
+
+/// Annotation of the functions to export:
+/// `__attribute__((annotate("starknet_external")))` in C, it ends up in `@llvm.global.annotations`.
+pub const EXTERNAL_ANNOTATION: &str = "starknet_external";
+/// String attribute of the functions to export: `define i32 @add(i32 %a) "starknet-external" {`
+pub const EXTERNAL_ATTRIBUTE: &str = "starknet-external";
+
+/// Type of a value at the ABI boundary of the contract.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum AbiType {
+    /// `bool` on both sides.
+    Bool,
+    /// `iN` in the translation, `uN` with the same bit pattern in the ABI.
+    Unsigned(u32),
+    /// The integers that don't have an unsigned cairo equivalent (`i24`...) are passed as
+    /// `felt252`.
+    Felt,
+}
+
+impl AbiType {
+    /// ABI type of a value of the cairo type `ty`, `None` if it can't cross the ABI boundary.
+    pub fn of(ty: &str) -> Option<Self> {
+        if ty == "bool" {
+            return Some(AbiType::Bool);
+        }
+        match ty.strip_prefix('i')?.parse::<u32>().ok()? {
+            width @ (8 | 16 | 32 | 64 | 128) => Some(AbiType::Unsigned(width)),
+            _ => Some(AbiType::Felt),
+        }
+    }
+
+    /// Convert `value` from the ABI type to the type of the translation.
+    fn decode(&self, value: Expr) -> Expr {
+        match self {
+            AbiType::Bool => value,
+            AbiType::Unsigned(width) => Expr::call(format!("abi_to_i{width}"), vec![value]),
+            AbiType::Felt => Expr::method_call(Expr::method_call(value, "try_into", Vec::new()), "unwrap", Vec::new()),
+        }
+    }
+
+    /// Convert `value` from the type of the translation to the ABI type.
+    fn encode(&self, value: Expr) -> Expr {
+        match self {
+            AbiType::Bool => value,
+            AbiType::Unsigned(width) => Expr::call(format!("abi_from_i{width}"), vec![value]),
+            AbiType::Felt => Expr::method_call(value, "into", Vec::new()),
+        }
+    }
+}
+
+impl Display for AbiType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AbiType::Bool => f.write_str("bool"),
+            AbiType::Unsigned(width) => f.write_str(&format!("u{width}")),
+            AbiType::Felt => f.write_str("felt252"),
+        }
+    }
+}
+
+/// A translated function exposed by the contract.
+#[derive(Clone, PartialEq, Debug)]
+pub struct EntryPoint {
+    pub(crate) name: String,
+    pub(crate) parameters: Vec<(String, AbiType)>,
+    /// `None` if the function doesn't return anything.
+    pub(crate) return_type: Option<AbiType>,
+    /// The function allocates so the entry point creates the memory it runs in, see
+    /// [crate::builder::allocator].
+    pub(crate) memory: bool,
+}
+
+impl EntryPoint {
+    /// Expose the function with this signature. Fails if one of its types can't cross the ABI
+    /// boundary.
+    pub fn new(signature: &CairoFunctionSignature) -> Result<Self, String> {
+        let abi_type = |ty: &str| {
+            AbiType::of(ty)
+                .ok_or_else(|| format!("{} can't be exported: {ty} isn't supported in the ABI", signature.name))
+        };
+        let parameters = signature
+            .parameters
+            .0
+            .iter()
+            .map(|parameter| Ok((parameter.name.clone(), abi_type(&parameter.ty)?)))
+            .collect::<Result<Vec<_>, String>>()?;
+        let return_type = match signature.return_type.as_str() {
+            "()" => None,
+            ty => Some(abi_type(ty)?),
+        };
+        Ok(Self { name: signature.name.clone(), parameters, return_type, memory: false })
+    }
+
+    /// `fn add(self: @ContractState, a: u32, b: u32) -> u32` with `state` as the type of `self`.
+    pub fn signature(&self, state: &str) -> CairoFunctionSignature {
+        let this = CairoParameter::new("self".to_owned(), format!("@{state}"));
+        let parameters = self.parameters.iter().map(|(name, ty)| CairoParameter::new(name.clone(), ty.to_string()));
+        let return_type = self.return_type.map_or_else(|| "()".to_owned(), |ty| ty.to_string());
+        let mut signature = CairoFunctionSignature::new(
+            self.name.clone(),
+            std::iter::once(this).chain(parameters).collect(),
+            return_type,
+        );
+        signature.public = false;
+        signature
+    }
+
+    /// The implementation of the entry point in the contract: it calls the translated function, see
+    /// [EntryPoint::call].
+    pub fn function(&self) -> CairoFunction {
+        // Each call of the entry point runs in a new memory.
+        let memory =
+            self.memory.then(|| Statement::declare_mut(MEMORY_PARAMETER, Expr::call("MemoryTrait::new", Vec::new())));
+        let call = match self.return_type {
+            Some(_) => Statement::Return(Some(self.call())),
+            None => Statement::Expr(self.call()),
+        };
+        CairoFunction::new(self.signature("ContractState"), Block::new(memory.into_iter().chain([call]).collect()))
+    }
+
+    /// Call of the translated function with the arguments converted from the ABI types and the
+    /// result converted to the ABI type.
+    pub fn call(&self) -> Expr {
+        let arguments = self.parameters.iter().map(|(name, ty)| ty.decode(Expr::variable(name)));
+        let memory = self.memory.then(|| Expr::reference(MEMORY_PARAMETER));
+        let arguments = memory.into_iter().chain(arguments).collect();
+        let call = Expr::call(self.name.clone(), arguments);
+        match self.return_type {
+            Some(ty) => ty.encode(call),
+            None => call,
+        }
+    }
+}
+
+/// The starknet contract a module is wrapped in.
+#[derive(Clone, PartialEq, Debug)]
+pub struct CairoContract {
+    /// Name of the contract module.
+    pub(crate) name: String,
+    pub(crate) entry_points: Vec<EntryPoint>,
+}
+
+impl CairoContract {
+    pub fn new(name: &str) -> Self {
+        Self { name: sanitize_identifier(name), entry_points: Vec::new() }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn entry_points(&self) -> &[EntryPoint] {
+        &self.entry_points
+    }
+
+    pub fn push_entry_point(&mut self, entry_point: EntryPoint) {
+        self.entry_points.push(entry_point)
+    }
+
+    /// `my_counter` => `MyCounter`, the base of the names of the interface and its impl.
+    pub fn camel_case_name(&self) -> String {
+        self.name
+            .split('_')
+            .filter(|word| !word.is_empty())
+            .map(|word| {
+                let mut chars = word.chars();
+                chars.next().map(|first| first.to_ascii_uppercase().to_string() + chars.as_str()).unwrap_or_default()
+            })
+            .collect()
+    }
+
+    /// Wrap the items of the translated module in the contract, see the layout at the top of this
+    /// module. The ABI conversions are added after the items.
+    pub fn items(&self, items: Vec<Item>) -> Vec<Item> {
+        let name = self.camel_case_name();
+        let interface = Item::Trait {
+            name: format!("I{name}<TContractState>"),
+            signatures: self.entry_points.iter().map(|entry_point| entry_point.signature("TContractState")).collect(),
+        };
+        let storage = Item::attribute("storage", Item::Struct(CairoStruct::new("Storage".to_owned(), Vec::new())));
+        let implementation = Item::attribute(
+            "abi(embed_v0)",
+            Item::Impl {
+                name: format!("{name}Impl"),
+                of_trait: format!("super::I{name}<ContractState>"),
+                functions: self.entry_points.iter().map(EntryPoint::function).collect(),
+            },
+        );
+        let conversions = self.unsigned_widths().into_iter().flat_map(abi_conversions).map(Item::Function);
+        let items = [storage, implementation].into_iter().chain(items).chain(conversions).collect();
+        vec![
+            Item::attribute("starknet::interface", interface),
+            Item::attribute("starknet::contract", Item::Module { name: self.name.clone(), items }),
+        ]
+    }
+
+    /// Widths of the integers that are converted at the ABI boundary, each needs its conversion
+    /// functions.
+    pub fn unsigned_widths(&self) -> Vec<u32> {
+        let mut widths = self
+            .entry_points
+            .iter()
+            .flat_map(|entry_point| entry_point.parameters.iter().map(|(_, ty)| *ty).chain(entry_point.return_type))
+            .filter_map(|ty| match ty {
+                AbiType::Unsigned(width) => Some(width),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        widths.sort();
+        widths.dedup();
+        widths
+    }
+}
+
+/// Conversions between `i<width>` and `u<width>` with the same bit pattern, through `felt252` as
+/// there is no direct conversion between signed and unsigned integers:
+/// ```cairo
+/// fn abi_to_i32(value: u32) -> i32 {
+///     let felt: felt252 = value.into();
+///     if value < 0x80000000 {
+///         felt.try_into().unwrap()
+///     } else {
+///         (felt - 0x100000000).try_into().unwrap()
+///     }
+/// }
+/// ```
+fn abi_conversions(width: u32) -> [CairoFunction; 2] {
+    let modulus = Expr::literal(format!("0x1{}", "0".repeat(width as usize / 4)));
+    let sign_bit = Expr::literal(format!("0x8{}", "0".repeat(width as usize / 4 - 1)));
+    let (value, felt) = (Expr::variable("value"), Expr::variable("felt"));
+    [
+        felt_conversion(
+            format!("abi_to_i{width}"),
+            (format!("u{width}"), format!("i{width}")),
+            Expr::binary(BinaryOperator::Lt, value.clone(), sign_bit),
+            felt.clone(),
+            Expr::binary(BinaryOperator::Sub, felt.clone(), modulus.clone()),
+        ),
+        felt_conversion(
+            format!("abi_from_i{width}"),
+            (format!("i{width}"), format!("u{width}")),
+            Expr::binary(BinaryOperator::Lt, value, Expr::literal("0")),
+            Expr::binary(BinaryOperator::Add, felt.clone(), modulus),
+            felt,
+        ),
+    ]
+}
+
+/// `fn <name>(value: <from>) -> <to>` that converts `value` to a `felt` and returns
+/// `then_value` or `else_value` (computed from `felt`) converted to `<to>` depending on
+/// `condition`.
+fn felt_conversion(
+    name: String,
+    (from, to): (String, String),
+    condition: Expr,
+    then_value: Expr,
+    else_value: Expr,
+) -> CairoFunction {
+    let convert = |value: Expr| {
+        let value = Expr::method_call(Expr::method_call(value, "try_into", Vec::new()), "unwrap", Vec::new());
+        Block::new(vec![Statement::Tail(value)])
+    };
+    let felt = Statement::Let {
+        pattern: Pattern::typed("felt", "felt252"),
+        value: Expr::method_call(Expr::variable("value"), "into", Vec::new()),
+    };
+    let result = Statement::If { condition, then_block: convert(then_value), else_block: Some(convert(else_value)) };
+    let mut signature = CairoFunctionSignature::new(name, vec![CairoParameter::new("value".to_owned(), from)], to);
+    signature.public = false;
+    CairoFunction::new(signature, Block::new(vec![felt, result]))
+}
+
+/// Get the symbols of the functions that are exported from the contract: the ones listed in the
+/// options (by LLVM symbol or cairo name) and the ones with the export annotation or attribute.
+///
+/// # Arguments
+///
+/// * `module` - The LLVM module that is translated.
+/// * `options` - The contract options.
+/// * `cairo_name` - Get the cairo name of a function from its LLVM symbol.
+pub fn exported_symbols(
+    module: &Module,
+    options: &ContractOptions,
+    cairo_name: impl Fn(&str) -> Option<String>,
+) -> HashSet<String> {
+    let annotated = annotated_functions(module);
+    module
+        .get_functions()
+        .filter(|function| function.count_basic_blocks() > 0)
+        .map(|function| (function, function.get_name().to_string_lossy().into_owned()))
+        .filter(|(function, symbol)| {
+            options.exports.contains(symbol)
+                || cairo_name(symbol).is_some_and(|name| options.exports.contains(&name))
+                || annotated.contains(symbol)
+                || function.get_string_attribute(AttributeLoc::Function, EXTERNAL_ATTRIBUTE).is_some()
+        })
+        .map(|(_, symbol)| symbol)
+        .collect()
+}
+
+/// Find the functions annotated with [EXTERNAL_ANNOTATION]. The annotations are in a global that
+/// LLVM prints like that:
+/// ```llvm
+/// @llvm.global.annotations = appending global [1 x { ptr, ptr, ptr, i32, ptr }] [{ ptr, ptr, ptr, i32, ptr } { ptr @add, ptr @.str, ptr @.str.1, i32 3, ptr null }], section "llvm.metadata"
+/// ```
+/// where `@.str` is the annotation.
+fn annotated_functions(module: &Module) -> HashSet<String> {
+    let Some(annotations) = module.get_global("llvm.global.annotations") else {
+        return HashSet::new();
+    };
+    let printed = annotations.print_to_string().to_string();
+    let global_name = |name: &str| {
+        let name = name.split([',', ' ', '}']).next().unwrap_or_default();
+        name.trim_matches('"').to_owned()
+    };
+    printed
+        .split("{ ptr @")
+        .skip(1)
+        .filter_map(|entry| {
+            let function = global_name(entry);
+            let (_, annotation) = entry.split_once("ptr @")?;
+            let annotation = module.get_global(&global_name(annotation))?.get_initializer()?;
+            let BasicValueEnum::ArrayValue(annotation) = annotation else {
+                return None;
+            };
+            let annotation = annotation.get_string_constant()?;
+            (annotation.to_bytes() == EXTERNAL_ANNOTATION.as_bytes()).then_some(function)
+        })
+        .collect()
+}
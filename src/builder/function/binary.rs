@@ -1,10 +1,17 @@
 use inkwell::basic_block::BasicBlock;
-use inkwell::values::{AnyValue, BasicValueEnum, InstructionValue, IntValue};
+use inkwell::values::{AnyValue, AsValueRef, BasicValueEnum, InstructionOpcode, InstructionValue, IntValue};
+use llvm_sys::core::{LLVMGetNSW, LLVMGetNUW};
 
-use super::CairoFunctionBuilder;
+use super::{CairoFunctionBuilder, ResultVariable};
+use crate::ast::{BinaryOperator, Expr};
+use crate::builder::types::{cairo_default_value, POINTER_WIDTH};
 
 impl<'ctx> CairoFunctionBuilder<'ctx> {
-    fn extract_const_int_value(val: IntValue) -> String {
+    pub(crate) fn extract_const_int_value(val: IntValue) -> String {
+        // `i1` are booleans.
+        if val.get_type().get_bit_width() == 1 {
+            return (val.get_zero_extended_constant() == Some(1)).to_string();
+        }
         // An llvm int constant is represented like this <type> <value> ex: i128 1234
         // First we get the value by getting the last chunk of its string representation
         let const_val = val.print_to_string()
@@ -21,14 +28,57 @@ impl<'ctx> CairoFunctionBuilder<'ctx> {
         // We add the type to have more type safety and detect bugs.
         format!("{const_val}_{ty}")
     }
+    /// Get the name of the variable we want to store the result of the instruction in. If the
+    /// variable was declared before a new scope we only assign it else we declare it with `let`.
+    pub fn get_result_variable(&mut self, instruction: &InstructionValue<'ctx>) -> ResultVariable {
+        let basic_val: BasicValueEnum = instruction.as_any_value_enum().try_into().unwrap();
+        // Try to get the variable from our variables mapping. If not found create it and insert it in the
+        // mmaping.
+        match self.variables.get(&basic_val) {
+            Some(name) => ResultVariable { name: name.clone(), declared: true },
+            None => {
+                let instr_name = self.get_value_name(&basic_val);
+                // Save the result variable in our mapping to be able to use later.
+                self.variables.insert(basic_val, instr_name.clone());
+                ResultVariable { name: instr_name, declared: false }
+            }
+        }
+    }
+
+    /// Get the cairo representation of an integer operand. It's either a const literal or a
+    /// variable that might be in our annoying phi mapping.
+    pub fn get_int_operand_expr(&self, operand: &BasicValueEnum<'ctx>, bb: &BasicBlock<'ctx>) -> Expr {
+        if Self::is_undef_or_poison(operand) {
+            // `undef` and `poison` are constants too but they don't have a value.
+            cairo_default_value(operand.get_type(), &self.struct_names)
+        } else if operand.into_int_value().is_const() {
+            Expr::literal(Self::extract_const_int_value(operand.into_int_value()))
+        } else {
+            // If it's not a const might be in our annoying phi mapping.
+            Expr::Variable(
+                self.bblock_variables
+                    .get(bb)
+                    .and_then(|annoying_phis| annoying_phis.get(operand))
+                    .map(|variable| &variable.temp)
+                    .or_else(|| self.variables.get(operand))
+                    .cloned()
+                    .unwrap_or_else(|| self.get_value_name(operand)),
+            )
+        }
+    }
+
     /// Translates an LLVM binary operation to cairo. This can be anything that expects exactly 1
     /// operator with a left and right operand.
     pub fn process_binary_int_op(
         &mut self,
         instruction: &InstructionValue<'ctx>,
-        operator: &str,
+        operator: BinaryOperator,
         bb: &BasicBlock<'ctx>,
-    ) -> String {
+    ) {
+        // Vectors don't exist in cairo so we apply the operation on each element.
+        if instruction.get_type().is_vector_type() {
+            return self.process_vector_binary_op(instruction, operator, bb);
+        }
         // Get th left operand.
         let left = unsafe {
             instruction
@@ -46,38 +96,34 @@ impl<'ctx> CairoFunctionBuilder<'ctx> {
                 .expect("right operand of add should be a basic value")
         };
         // Get the name of the variable we want to store the result of the operantion in.
+        let result_variable = self.get_result_variable(instruction);
 
-        let instr_name = {
-            let basic_val: BasicValueEnum = instruction.as_any_value_enum().try_into().unwrap();
-            // Try to get the variable from our variables mapping. If not found create it and insert it in the
-            // mmaping.
-            self.variables.get(&basic_val).cloned().unwrap_or_else(|| {
-                let instr_name = self.get_name(instruction.get_name().unwrap_or_default());
-                if let Ok(basic_value_enum) = instruction.as_any_value_enum().try_into() {
-                    // Save the result variable in our mapping to be able to use later.
-                    self.variables.insert(basic_value_enum, instr_name.clone());
-                }
-                format!("let {}", instr_name)
-            })
-        };
-
-        let annoying_phis = self.bblock_variables.get(bb).cloned().unwrap_or_default();
         // The operand is either a variable or a constant so either we get it from our mapping or it's
-        // unnamed and it's a const literal.
-        // TODO(Lucas): a variable can surely be in the variables mapping, try to get it from there as well.
-        let left_name = if left.into_int_value().is_const() {
-            Self::extract_const_int_value(left.into_int_value())
-        } else {
-            // If it's not a const might be in our annoying phi mapping.
-            annoying_phis.get(&left).cloned().unwrap_or_else(|| self.get_name(left.get_name()))
-        };
-        let right_name = if right.into_int_value().is_const() {
-            Self::extract_const_int_value(right.into_int_value())
-        } else {
-            // If it's not a const might be in our annoying phi mapping.
-            annoying_phis.get(&right).cloned().unwrap_or_else(|| self.get_name(right.get_name()))
+        // unnamed and it's a const literal. `icmp` also compares pointers.
+        let left_name = self.get_operand_expr(&left, bb);
+        let right_name = self.get_operand_expr(&right, bb);
+
+        let width = match left {
+            BasicValueEnum::IntValue(value) => value.get_type().get_bit_width(),
+            _ => POINTER_WIDTH,
         };
+        let value = self.int_arithmetic(operator, left_name, right_name, width, Self::wrap_flags(instruction));
+        self.push_statement(result_variable.set(value));
+    }
 
-        format!("{} = {} {} {};", instr_name, left_name, operator, right_name)
+    /// Get the `nuw` and `nsw` flags of an instruction: `%sum = add nuw nsw i32 %a, %b`. Only
+    /// `add`, `sub`, `mul` and `shl` can have them, the other instructions (`icmp`...) have
+    /// neither.
+    pub fn wrap_flags(instruction: &InstructionValue<'ctx>) -> (bool, bool) {
+        if !matches!(
+            instruction.get_opcode(),
+            InstructionOpcode::Add | InstructionOpcode::Sub | InstructionOpcode::Mul | InstructionOpcode::Shl
+        ) {
+            return (false, false);
+        }
+        // inkwell doesn't expose the flags. LLVM asserts that the instruction can have them, which
+        // is checked above.
+        let value = instruction.as_value_ref();
+        unsafe { (LLVMGetNUW(value) != 0, LLVMGetNSW(value) != 0) }
     }
 }
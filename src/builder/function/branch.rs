@@ -2,6 +2,7 @@ use inkwell::basic_block::BasicBlock;
 use inkwell::values::InstructionValue;
 
 use super::CairoFunctionBuilder;
+use crate::ast::{Block, Expr, Statement};
 
 impl<'ctx> CairoFunctionBuilder<'ctx> {
     /// Process a branch instruction. If there is only 1 operand without condition it'll translate
@@ -13,25 +14,30 @@ impl<'ctx> CairoFunctionBuilder<'ctx> {
         bb: &BasicBlock<'ctx>,
         is_loop: &bool,
         is_else: &bool,
-    ) -> String {
+    ) {
         // Get all the annoying variables that require to be declared in a bigger scope and will update
         // their value.
-        self.bblock_variables.get(bb).cloned().unwrap_or_default().into_values().for_each(|val| {
-            self.push_body_line(format!("{} = {};", val.trim_end_matches("_temp"), val));
+        self.bblock_variables.get(bb).cloned().unwrap_or_default().into_values().for_each(|variable| {
+            self.push_statement(Statement::assign(variable.outer, Expr::variable(variable.temp)));
         });
         self.set_basic_block_booleans(bb);
         // Case were there is an inconditionnal jump.
         if instruction.get_num_operands() == 1 {
             self.process_basic_block(&instruction.get_operand(0).unwrap().right().unwrap());
-            "".to_owned()
         } else {
             // There is a condition could either be a loop break or if/else
             let cond = instruction.get_operand(0).unwrap().left().unwrap();
+            if let Some(check) = self.poison_check(&cond, "branch condition") {
+                self.push_statement(check);
+            }
             // If we're in a loop this is the exit condition so we break.
             if *is_loop {
-                let res = format!("if {}\n{{break;}}", self.variables.get(&cond).unwrap());
-
-                res
+                // `undef`/`poison` conditions don't have a variable, they're the default value.
+                self.push_statement(Statement::If {
+                    condition: self.get_operand_expr(&cond, bb),
+                    then_block: Block::new(vec![Statement::Break]),
+                    else_block: None,
+                });
             } else {
                 self.close_scopes(bb, is_else, is_loop);
                 // else it means that we're in a if/else case and the first block is the if the 2nd is the else.
@@ -39,8 +45,6 @@ impl<'ctx> CairoFunctionBuilder<'ctx> {
                 self.process_basic_block(&instruction.get_operand(1).unwrap().right().unwrap());
                 self.else_blocks.insert(instruction.get_operand(2).unwrap().right().unwrap());
                 self.process_basic_block(&instruction.get_operand(2).unwrap().right().unwrap());
-
-                "".to_owned()
             }
         }
     }
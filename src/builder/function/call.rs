@@ -0,0 +1,109 @@
+use inkwell::basic_block::BasicBlock;
+use inkwell::values::InstructionValue;
+
+use super::float::{float_intrinsic, is_transcendental_intrinsic};
+use super::CairoFunctionBuilder;
+use crate::ast::{Expr, Statement};
+use crate::builder::allocator::allocator_function;
+
+/// LLVM intrinsics that only carry information for the optimizer and don't have any effect on
+/// the program.
+const IGNORED_INTRINSICS: [&str; 5] =
+    ["llvm.dbg.", "llvm.lifetime.", "llvm.assume", "llvm.experimental.noalias.scope.decl", "llvm.invariant."];
+
+impl<'ctx> CairoFunctionBuilder<'ctx> {
+    /// Get the name of the function called by a call instruction. The called function is the last
+    /// operand of the instruction.
+    pub fn get_called_function_name(instruction: &InstructionValue<'ctx>) -> String {
+        instruction
+            .get_operand(instruction.get_num_operands() - 1)
+            .and_then(|operand| operand.left())
+            .expect("Call instruction should have a called function")
+            .get_name()
+            .to_str()
+            .expect("Function name should be utf-8")
+            .to_owned()
+    }
+
+    /// Translate a call instruction. Calls to LLVM intrinsics and to the functions of the rust
+    /// runtime are lowered to the equivalent cairo code, other calls are translated as is, see
+    /// [Self::process_direct_call].
+    ///
+    /// # Panics
+    ///
+    /// If the called function is an LLVM intrinsic we don't support (`llvm.ctpop.i32`,
+    /// `llvm.sin.f64`...). There is no cairo function with its name so the call can't be
+    /// translated as is.
+    pub fn process_call(&mut self, instruction: &InstructionValue<'ctx>, bb: &BasicBlock<'ctx>) {
+        let called_function = Self::get_called_function_name(instruction);
+        if IGNORED_INTRINSICS.iter().any(|intrinsic| called_function.starts_with(intrinsic)) {
+            return;
+        }
+        // llvm.vector.reduce.<operation>.<vector type>
+        if let Some(reduction) = called_function.strip_prefix("llvm.vector.reduce.") {
+            let operation = reduction.split('.').next().expect("Reduction should have an operation");
+            return self.process_vector_reduce(instruction, operation, bb);
+        }
+        if let Some(operation) = float_intrinsic(&called_function) {
+            return self.process_float_op(instruction, operation, bb);
+        }
+        // Saturating float to integer conversions, `fptosi`/`fptoui` already saturate in cairo.
+        if called_function.starts_with("llvm.fptosi.sat.") || called_function.starts_with("llvm.fptoui.sat.") {
+            return self.process_float_to_int(instruction, called_function.starts_with("llvm.fptosi"), bb);
+        }
+        if is_transcendental_intrinsic(&called_function) {
+            panic!(
+                "Unsupported LLVM intrinsic {called_function}: the float library only has the basic operations (add, \
+                 sub, mul, div, rem, sqrt, fma and the roundings), not pow, exp, log and the trigonometric functions"
+            );
+        }
+        if called_function.starts_with("llvm.") {
+            panic!("Unsupported LLVM intrinsic {called_function}");
+        }
+        if let Some(panic) = self.process_rust_panic(instruction, &called_function, bb) {
+            return self.push_statement(Statement::Expr(panic));
+        }
+        // The rust allocator functions are lowered to the cairo allocator, see [crate::builder::allocator].
+        let function = match allocator_function(&called_function) {
+            Some(function) => function.to_owned(),
+            None => self.function_name(&called_function),
+        };
+        self.process_direct_call(instruction, function, bb)
+    }
+
+    /// Translate a call to a function of the module (or of another module of the package) as a
+    /// cairo call with the same arguments.
+    /// %res = call i32 @foo(i32 %a, i32 1)
+    /// becomes
+    /// let res = foo(a, 1_i32);
+    ///
+    /// # Arguments
+    ///
+    /// * `instruction` - The call instruction.
+    /// * `function` - The cairo name of the called function.
+    pub fn process_direct_call(
+        &mut self,
+        instruction: &InstructionValue<'ctx>,
+        function: String,
+        bb: &BasicBlock<'ctx>,
+    ) {
+        // The last operand is the called function.
+        let mut arguments = Vec::with_capacity(instruction.get_num_operands() as usize - 1);
+        for index in 0..instruction.get_num_operands() - 1 {
+            let argument =
+                instruction.get_operand(index).and_then(|operand| operand.left()).expect("Argument should be a value");
+            if let Some(check) = self.poison_check(&argument, "function argument") {
+                self.push_statement(check);
+            }
+            arguments.push(self.get_operand_expr(&argument, bb));
+        }
+        let call = Expr::call(function, arguments);
+        // Functions that return void don't have a result.
+        if instruction.get_type().is_void_type() {
+            self.push_statement(Statement::Expr(call));
+        } else {
+            let result_variable = self.get_result_variable(instruction);
+            self.push_statement(result_variable.set(call));
+        }
+    }
+}
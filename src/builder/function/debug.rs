@@ -0,0 +1,78 @@
+use std::collections::HashMap;
+
+use inkwell::values::{AnyValue, BasicValueEnum, FunctionValue, InstructionOpcode};
+
+use super::CairoFunctionBuilder;
+use crate::source_map::metadata_field;
+
+/// Get the printed name of a value (`%4`, `%x`, `%"x.y"`) from its definition. Instructions are
+/// printed `%4 = add i32 %a, %b` and parameters `i32 %a`.
+fn printed_value_name(value: &BasicValueEnum) -> Option<String> {
+    let printed = value.print_to_string().to_string();
+    let printed = printed.trim();
+    let name = match printed.split_once(" = ") {
+        Some((name, _)) if printed.starts_with('%') => name,
+        _ => printed.rsplit(' ').next()?,
+    };
+    name.starts_with('%').then(|| name.to_owned())
+}
+
+impl<'ctx> CairoFunctionBuilder<'ctx> {
+    /// Name the values after the variables of the original source code when the IR has debug info.
+    /// The debug intrinsics bind a value to a `DILocalVariable`:
+    /// ```llvm
+    /// call void @llvm.dbg.value(metadata i128 %4, metadata !12, metadata !DIExpression()), !dbg !15
+    /// !12 = !DILocalVariable(name: "sum", scope: !5, file: !1, line: 3, type: !9)
+    /// ```
+    /// A source variable can be bound to several values (each assignment creates a new SSA value),
+    /// [CairoFunctionBuilder::assign_names] makes the names unique.
+    pub fn collect_debug_names(&mut self, function: &FunctionValue<'ctx>) {
+        // Printed name => value. That's how the values are referenced in the debug intrinsics.
+        let mut values = function
+            .get_param_iter()
+            .filter_map(|param| printed_value_name(&param).map(|name| (name, param)))
+            .collect::<HashMap<_, _>>();
+        let mut bindings = Vec::new();
+        for instruction in function.get_basic_block_iter().flat_map(|bb| bb.get_instructions()) {
+            if let Ok(value) = BasicValueEnum::try_from(instruction.as_any_value_enum()) {
+                if let Some(name) = printed_value_name(&value) {
+                    values.insert(name, value);
+                }
+            }
+            let printed = instruction.print_to_string().to_string();
+            if instruction.get_opcode() != InstructionOpcode::Call
+                || !(printed.contains("@llvm.dbg.value(") || printed.contains("@llvm.dbg.declare("))
+            {
+                continue;
+            }
+            // (metadata <ty> <value>, metadata !<variable>, metadata !DIExpression())
+            let mut arguments = printed.split("metadata ").skip(1);
+            let (Some(value), Some(variable)) = (arguments.next(), arguments.next()) else {
+                continue;
+            };
+            // Constants and argument lists don't name anything.
+            let Some(value) =
+                value.trim_end_matches([',', ' ']).rsplit(' ').next().filter(|value| value.starts_with('%'))
+            else {
+                continue;
+            };
+            let variable = variable.trim_end_matches([',', ' ']);
+            // The variable is usually a reference `!12` but can be printed inline.
+            let node = if variable.starts_with("!DILocalVariable(") {
+                Some(variable)
+            } else {
+                self.debug_metadata.node(variable)
+            };
+            if let Some(name) = node.and_then(|node| metadata_field(node, "name")) {
+                bindings.push((value.to_owned(), name.to_owned()));
+            }
+        }
+
+        for (value, name) in bindings {
+            // A value can be bound to several variables, keep the first one.
+            if let Some(value) = values.get(&value) {
+                self.debug_names.entry(*value).or_insert(name);
+            }
+        }
+    }
+}
@@ -0,0 +1,29 @@
+use inkwell::basic_block::BasicBlock;
+use inkwell::values::InstructionValue;
+
+use super::CairoFunctionBuilder;
+use crate::ast::Expr;
+use crate::builder::types::cairo_type;
+
+impl<'ctx> CairoFunctionBuilder<'ctx> {
+    /// Translate a `zext` instruction. Cairo doesn't have implicit casts so:
+    /// * `zext i1 %x to i32` becomes `if x { 1_i32 } else { 0_i32 }` as booleans can't be converted
+    ///   to integers.
+    /// * `zext i8 %x to i32` becomes `x.into()` as the conversion from a smaller unsigned integer
+    ///   can't fail.
+    pub fn process_zext(&mut self, instruction: &InstructionValue<'ctx>, bb: &BasicBlock<'ctx>) {
+        let operand = instruction.get_operand(0).unwrap().left().expect("zext should have an operand");
+        let result_variable = self.get_result_variable(instruction);
+        let value = self.get_operand_expr(&operand, bb);
+        let value = if operand.into_int_value().get_type().get_bit_width() == 1 {
+            let ty = cairo_type(
+                instruction.get_type().try_into().expect("zext should return an integer"),
+                &self.struct_names,
+            );
+            Expr::if_else(value, Expr::literal(format!("1_{ty}")), Expr::literal(format!("0_{ty}")))
+        } else {
+            Expr::method_call(value, "into", Vec::new())
+        };
+        self.push_statement(result_variable.set(value));
+    }
+}
@@ -0,0 +1,250 @@
+use inkwell::basic_block::BasicBlock;
+use inkwell::types::BasicTypeEnum;
+use inkwell::values::{BasicValueEnum, FloatValue, InstructionOpcode, InstructionValue};
+use inkwell::FloatPredicate;
+
+use super::CairoFunctionBuilder;
+use crate::ast::{BinaryOperator, Expr};
+use crate::builder::options::FloatMode;
+use crate::builder::soft_float::float_struct;
+use crate::builder::types::float_width;
+
+/// `1.0` in [FloatMode::FixedPoint], the numbers have 32 fractional bits.
+const FIXED_POINT_ONE: f64 = 4294967296.0;
+
+/// LLVM float intrinsics and the function of the float library they're lowered to:
+/// `llvm.sqrt.f32` => `f32_sqrt`. We only use the default rounding mode so `rint` and `nearbyint`
+/// round to even.
+const FLOAT_INTRINSICS: [(&str, &str); 16] = [
+    ("llvm.sqrt.", "sqrt"),
+    ("llvm.fabs.", "abs"),
+    ("llvm.floor.", "floor"),
+    ("llvm.ceil.", "ceil"),
+    ("llvm.trunc.", "trunc"),
+    ("llvm.round.", "round"),
+    ("llvm.roundeven.", "roundeven"),
+    ("llvm.rint.", "roundeven"),
+    ("llvm.nearbyint.", "roundeven"),
+    ("llvm.minnum.", "minnum"),
+    ("llvm.maxnum.", "maxnum"),
+    ("llvm.minimum.", "minimum"),
+    ("llvm.maximum.", "maximum"),
+    ("llvm.copysign.", "copysign"),
+    ("llvm.fma.", "fma"),
+    ("llvm.fmuladd.", "fma"),
+];
+
+/// LLVM float intrinsics the float library doesn't implement: it only has the operations IEEE-754
+/// requires to be correctly rounded, the transcendental functions would need their own
+/// approximations.
+const TRANSCENDENTAL_INTRINSICS: [&str; 12] = [
+    "llvm.pow.",
+    "llvm.powi.",
+    "llvm.exp.",
+    "llvm.exp2.",
+    "llvm.exp10.",
+    "llvm.log.",
+    "llvm.log2.",
+    "llvm.log10.",
+    "llvm.sin.",
+    "llvm.cos.",
+    "llvm.tan.",
+    "llvm.ldexp.",
+];
+
+/// Is `called_function` a float intrinsic the float library doesn't implement, see
+/// [TRANSCENDENTAL_INTRINSICS].
+pub fn is_transcendental_intrinsic(called_function: &str) -> bool {
+    TRANSCENDENTAL_INTRINSICS.iter().any(|intrinsic| called_function.starts_with(intrinsic))
+}
+
+/// Get the function of the float library an LLVM intrinsic is lowered to, see [FLOAT_INTRINSICS].
+pub fn float_intrinsic(called_function: &str) -> Option<&'static str> {
+    FLOAT_INTRINSICS
+        .iter()
+        .find(|(intrinsic, _)| called_function.starts_with(intrinsic))
+        .map(|(_, operation)| *operation)
+}
+
+/// Prefix of the functions of the float library for values of type `ty`: `f32` or `f64`.
+fn float_prefix(ty: BasicTypeEnum) -> String {
+    match ty {
+        BasicTypeEnum::FloatType(float_ty) => {
+            let width = float_width(float_ty)
+                .unwrap_or_else(|| panic!("{} isn't supported, only float and double are", float_ty.print_to_string()));
+            format!("f{width}")
+        }
+        BasicTypeEnum::VectorType(_) => panic!("Vector float operations aren't supported"),
+        _ => panic!("Expected a float type, got {}", ty.print_to_string()),
+    }
+}
+
+impl<'ctx> CairoFunctionBuilder<'ctx> {
+    /// Translate a float constant to a value of the float library. In [FloatMode::Ieee] it's the
+    /// bit pattern of the float (`F32 { bits: 0x3fc00000_u32 }` for `1.5`), in
+    /// [FloatMode::FixedPoint] it's the value times 2^32 (`F32 { raw: 6442450944_i128 }`).
+    pub fn float_literal(&self, value: FloatValue<'ctx>) -> Expr {
+        let (constant, _) = value.get_constant().expect("Float constant should have a value");
+        let width = float_width(value.get_type()).expect("Only float and double are supported");
+        let (field, literal) = match self.options.float_mode {
+            FloatMode::Ieee => {
+                // An f32 constant is exactly representable as an f64 so the conversion is lossless.
+                let bits = if width == 32 { u64::from((constant as f32).to_bits()) } else { constant.to_bits() };
+                ("bits", format!("{bits:#x}_u{width}"))
+            }
+            FloatMode::FixedPoint => ("raw", format!("{}_i128", (constant * FIXED_POINT_ONE).round() as i128)),
+        };
+        Expr::Struct { name: float_struct(width), fields: vec![(field.to_owned(), Expr::literal(literal))] }
+    }
+
+    /// Get the float operand at `index` of an instruction.
+    fn float_operand(instruction: &InstructionValue<'ctx>, index: u32) -> BasicValueEnum<'ctx> {
+        instruction
+            .get_operand(index)
+            .and_then(|operand| operand.left())
+            .expect("Float instruction should have a value operand")
+    }
+
+    /// Translate an arithmetic float instruction or a float intrinsic to a call to the float
+    /// library:
+    /// %res = fadd float %a, %b
+    /// becomes
+    /// let res = f32_add(a, b);
+    pub fn process_float_op(&mut self, instruction: &InstructionValue<'ctx>, operation: &str, bb: &BasicBlock<'ctx>) {
+        let prefix = float_prefix(instruction.get_type().try_into().expect("Float operation should return a value"));
+        // The last operand of a call is the called function.
+        let operands = match instruction.get_opcode() {
+            InstructionOpcode::Call => instruction.get_num_operands() - 1,
+            _ => instruction.get_num_operands(),
+        };
+        let arguments =
+            (0..operands).map(|index| self.get_operand_expr(&Self::float_operand(instruction, index), bb)).collect();
+        let result_variable = self.get_result_variable(instruction);
+        self.push_statement(result_variable.set(Expr::call(format!("{prefix}_{operation}"), arguments)));
+    }
+
+    /// Translate a float comparison. The float library only has the ordered comparisons (false if
+    /// one of the operands is NaN) and `unordered` so the other predicates are built from them:
+    /// %res = fcmp ult double %a, %b
+    /// becomes
+    /// let res = !f64_le(b, a);
+    pub fn process_fcmp(&mut self, instruction: &InstructionValue<'ctx>, bb: &BasicBlock<'ctx>) {
+        let predicate = instruction.get_fcmp_predicate().expect("fcmp should have a predicate");
+        let left = Self::float_operand(instruction, 0);
+        let right = Self::float_operand(instruction, 1);
+        let prefix = float_prefix(left.get_type());
+        let (a, b) = (self.get_operand_expr(&left, bb), self.get_operand_expr(&right, bb));
+        let call = |operation: &str, left: &Expr, right: &Expr| {
+            Expr::call(format!("{prefix}_{operation}"), vec![left.clone(), right.clone()])
+        };
+        let value = match predicate {
+            FloatPredicate::OEQ => call("eq", &a, &b),
+            FloatPredicate::OGT => call("lt", &b, &a),
+            FloatPredicate::OGE => call("le", &b, &a),
+            FloatPredicate::OLT => call("lt", &a, &b),
+            FloatPredicate::OLE => call("le", &a, &b),
+            FloatPredicate::ONE => {
+                Expr::binary(BinaryOperator::And, Expr::not(call("unordered", &a, &b)), Expr::not(call("eq", &a, &b)))
+            }
+            FloatPredicate::ORD => Expr::not(call("unordered", &a, &b)),
+            FloatPredicate::UEQ => Expr::binary(BinaryOperator::Or, call("unordered", &a, &b), call("eq", &a, &b)),
+            // Unordered or greater is the opposite of ordered and lower or equal.
+            FloatPredicate::UGT => Expr::not(call("le", &a, &b)),
+            FloatPredicate::UGE => Expr::not(call("lt", &a, &b)),
+            FloatPredicate::ULT => Expr::not(call("le", &b, &a)),
+            FloatPredicate::ULE => Expr::not(call("lt", &b, &a)),
+            FloatPredicate::UNE => Expr::not(call("eq", &a, &b)),
+            FloatPredicate::UNO => call("unordered", &a, &b),
+            FloatPredicate::PredicateTrue => Expr::literal("true"),
+            FloatPredicate::PredicateFalse => Expr::literal("false"),
+        };
+        let result_variable = self.get_result_variable(instruction);
+        self.push_statement(result_variable.set(value));
+    }
+
+    /// Translate `fptosi`, `fptoui` and their saturating intrinsics (`llvm.fptosi.sat.*`). The
+    /// library saturates and returns an `i128` with the bit pattern of the result:
+    /// %res = fptoui float %a to i32
+    /// becomes
+    /// let res = f32_fptoui(a, 32_u32).try_into().unwrap();
+    pub fn process_float_to_int(&mut self, instruction: &InstructionValue<'ctx>, signed: bool, bb: &BasicBlock<'ctx>) {
+        let operand = Self::float_operand(instruction, 0);
+        let prefix = float_prefix(operand.get_type());
+        let ty: BasicTypeEnum = instruction.get_type().try_into().expect("Float conversion should return a value");
+        let width = ty.into_int_type().get_bit_width();
+        let function = format!("{prefix}_fpto{}i", if signed { "s" } else { "u" });
+        let call =
+            Expr::call(function, vec![self.get_operand_expr(&operand, bb), Expr::literal(format!("{width}_u32"))]);
+        let value = match width {
+            // All the bits of an `i1` are set or none.
+            1 => Expr::binary(BinaryOperator::Ne, call, Expr::literal("0_i128")),
+            128 => call,
+            _ => Expr::method_call(Expr::method_call(call, "try_into", Vec::new()), "unwrap", Vec::new()),
+        };
+        let result_variable = self.get_result_variable(instruction);
+        self.push_statement(result_variable.set(value));
+    }
+
+    /// Translate `sitofp` and `uitofp`. The integer is passed as an `i128`, for `uitofp` the
+    /// library gets its width to know its unsigned value:
+    /// %res = uitofp i32 %a to double
+    /// becomes
+    /// let res = f64_uitofp(a.into(), 32_u32);
+    pub fn process_int_to_float(&mut self, instruction: &InstructionValue<'ctx>, signed: bool, bb: &BasicBlock<'ctx>) {
+        let operand = Self::float_operand(instruction, 0);
+        let width = operand.into_int_value().get_type().get_bit_width();
+        let value = self.get_operand_expr(&operand, bb);
+        let value = match width {
+            // `true` is -1 as a signed `i1`.
+            1 => {
+                Expr::if_else(value, Expr::literal(if signed { "-1_i128" } else { "1_i128" }), Expr::literal("0_i128"))
+            }
+            128 => value,
+            _ => Expr::method_call(value, "into", Vec::new()),
+        };
+        let prefix = float_prefix(instruction.get_type().try_into().expect("Float conversion should return a value"));
+        let call = if signed {
+            Expr::call(format!("{prefix}_sitofp"), vec![value])
+        } else {
+            Expr::call(format!("{prefix}_uitofp"), vec![value, Expr::literal(format!("{width}_u32"))])
+        };
+        let result_variable = self.get_result_variable(instruction);
+        self.push_statement(result_variable.set(call));
+    }
+
+    /// Translate `fpext` and `fptrunc`:
+    /// %res = fpext float %a to double
+    /// becomes
+    /// let res = f32_to_f64(a);
+    pub fn process_float_cast(&mut self, instruction: &InstructionValue<'ctx>, bb: &BasicBlock<'ctx>) {
+        let operand = Self::float_operand(instruction, 0);
+        let from = float_prefix(operand.get_type());
+        let to = float_prefix(instruction.get_type().try_into().expect("Float conversion should return a value"));
+        let value = self.get_operand_expr(&operand, bb);
+        let result_variable = self.get_result_variable(instruction);
+        self.push_statement(result_variable.set(Expr::call(format!("{from}_to_{to}"), vec![value])));
+    }
+
+    /// Translate the `bitcast`s between a float and an integer of the same size
+    /// (`f32::to_bits`/`f32::from_bits`).
+    pub fn process_float_bitcast(&mut self, instruction: &InstructionValue<'ctx>, bb: &BasicBlock<'ctx>) {
+        let operand = Self::float_operand(instruction, 0);
+        let ty: BasicTypeEnum = instruction.get_type().try_into().expect("bitcast should return a value");
+        let value = self.get_operand_expr(&operand, bb);
+        let value = match (operand.get_type(), ty) {
+            (BasicTypeEnum::IntType(_), BasicTypeEnum::FloatType(_)) => Expr::call(
+                format!("{}_from_bits", float_prefix(ty)),
+                vec![Expr::method_call(value, "into", Vec::new())],
+            ),
+            (BasicTypeEnum::FloatType(_), BasicTypeEnum::IntType(_)) => {
+                let bits = Expr::call(format!("{}_to_bits", float_prefix(operand.get_type())), vec![value]);
+                Expr::method_call(Expr::method_call(bits, "try_into", Vec::new()), "unwrap", Vec::new())
+            }
+            (from, to) => {
+                panic!("bitcast from {} to {} isn't supported", from.print_to_string(), to.print_to_string())
+            }
+        };
+        let result_variable = self.get_result_variable(instruction);
+        self.push_statement(result_variable.set(value));
+    }
+}
@@ -0,0 +1,180 @@
+//! Memory instructions. The pointers are addresses in the emulated memory of the allocator (see
+//! [crate::builder::allocator]) so the instructions are lowered to its functions:
+//! ```cairo
+//! let slot = rust_alloc(4_i64, 4_i64); // %slot = alloca i32, align 4
+//! store_i32(slot, x); // store i32 %x, ptr %slot
+//! let v = load_i32(slot); // %v = load i32, ptr %slot
+//! let second = ptr_add(pair, 8, 1); // %second = getelementptr { i64, i64 }, ptr %pair, i64 0, i32 1
+//! ```
+//! The functions that access the memory get it as a `ref memory` parameter once all the functions
+//! are translated, see [crate::builder::CairoBuilder::thread_memory]. The integers, the pointers
+//! and the floats can be loaded and stored, the floats as their bit patterns:
+//! ```cairo
+//! let x = f64_from_bits(load_i64(ref memory, p).into()); // %x = load double, ptr %p
+//! store_i64(ref memory, p, f64_to_bits(x).try_into().unwrap()); // store double %x, ptr %p
+//! ```
+//! The translation fails on the other types (structs, arrays...).
+
+use inkwell::basic_block::BasicBlock;
+use inkwell::targets::TargetData;
+use inkwell::types::BasicTypeEnum;
+use inkwell::values::{AsValueRef, BasicValueEnum, InstructionValue};
+use llvm_sys::core::LLVMGetGEPSourceElementType;
+
+use super::CairoFunctionBuilder;
+use crate::ast::{Expr, Statement};
+use crate::builder::allocator::{allocator_function, POINTER_ADD};
+use crate::builder::options::FloatMode;
+use crate::builder::types::float_width;
+
+impl<'ctx> CairoFunctionBuilder<'ctx> {
+    /// Get an operand of a memory instruction.
+    fn memory_operand(instruction: &InstructionValue<'ctx>, index: u32) -> BasicValueEnum<'ctx> {
+        instruction
+            .get_operand(index)
+            .and_then(|operand| operand.left())
+            .unwrap_or_else(|| panic!("{:?} should have an operand {index}", instruction.get_opcode()))
+    }
+
+    /// The sizes and offsets of the types in the data layout of the module.
+    fn target_data(&self) -> TargetData {
+        TargetData::create(&self.data_layout)
+    }
+
+    /// Suffix of the functions of the allocator that load and store the values of type `ty`, see
+    /// [crate::builder::allocator::MEMORY_ACCESS_TYPES]. The floats are accessed as the integers of
+    /// the same size, with their width.
+    fn memory_access_type(&self, ty: BasicTypeEnum<'ctx>) -> Result<(String, Option<u32>), String> {
+        match ty {
+            BasicTypeEnum::IntType(int) if int.get_bit_width() == 1 => Ok(("bool".to_owned(), None)),
+            BasicTypeEnum::IntType(int) if [8, 16, 32, 64, 128].contains(&int.get_bit_width()) => {
+                Ok((format!("i{}", int.get_bit_width()), None))
+            }
+            BasicTypeEnum::PointerType(_) => Ok(("ptr".to_owned(), None)),
+            // The fixed-point numbers don't have a bit pattern to store.
+            BasicTypeEnum::FloatType(float) if self.options.float_mode == FloatMode::Ieee => match float_width(float) {
+                Some(width) => Ok((format!("i{width}"), Some(width))),
+                None => Err(format!("Loading and storing {} isn't supported", ty.print_to_string())),
+            },
+            BasicTypeEnum::FloatType(_) => {
+                Err(format!("Loading and storing {} needs the IEEE floats (--float ieee)", ty.print_to_string()))
+            }
+            _ => Err(format!(
+                "Loading and storing {} isn't supported, only the integers, the floats and the pointers are",
+                ty.print_to_string()
+            )),
+        }
+    }
+
+    /// Translate an `alloca`. The stack slot is allocated in the memory and never freed:
+    /// %slot = alloca [4 x i32], align 4
+    /// becomes
+    /// let slot = rust_alloc(16_i64, 4_i64);
+    pub fn process_alloca(&mut self, instruction: &InstructionValue<'ctx>) {
+        let ty = instruction.get_allocated_type().expect("alloca should have an allocated type");
+        let count = Self::memory_operand(instruction, 0)
+            .into_int_value()
+            .get_zero_extended_constant()
+            .expect("Only the allocas of a constant number of elements are supported");
+        let target_data = self.target_data();
+        let size = target_data.get_abi_size(&ty) * count;
+        let align = instruction.get_alignment().unwrap_or_else(|_| target_data.get_abi_alignment(&ty));
+        let alloc = allocator_function("__rust_alloc").expect("The allocator has an alloc function");
+        let arguments = vec![Expr::literal(format!("{size}_i64")), Expr::literal(format!("{align}_i64"))];
+        let result_variable = self.get_result_variable(instruction);
+        self.push_statement(result_variable.set(Expr::call(alloc, arguments)));
+    }
+
+    /// Translate a `load`:
+    /// %v = load i32, ptr %p, align 4
+    /// becomes
+    /// let v = load_i32(p);
+    pub fn process_load(&mut self, instruction: &InstructionValue<'ctx>, bb: &BasicBlock<'ctx>) {
+        let pointer = Self::memory_operand(instruction, 0);
+        let ty: BasicTypeEnum = instruction.get_type().try_into().expect("load should return a value");
+        let (access_type, float) = match self.memory_access_type(ty) {
+            Ok(access_type) => access_type,
+            Err(error) => return self.errors.push(error),
+        };
+        let address = self.get_operand_expr(&pointer, bb);
+        let mut value = Expr::call(format!("load_{access_type}"), vec![address]);
+        if let Some(width) = float {
+            value = Expr::call(format!("f{width}_from_bits"), vec![Expr::method_call(value, "into", Vec::new())]);
+        }
+        let result_variable = self.get_result_variable(instruction);
+        self.push_statement(result_variable.set(value));
+    }
+
+    /// Translate a `store`:
+    /// store i32 %x, ptr %p, align 4
+    /// becomes
+    /// store_i32(p, x);
+    pub fn process_store(&mut self, instruction: &InstructionValue<'ctx>, bb: &BasicBlock<'ctx>) {
+        let value = Self::memory_operand(instruction, 0);
+        let pointer = Self::memory_operand(instruction, 1);
+        let (access_type, float) = match self.memory_access_type(value.get_type()) {
+            Ok(access_type) => access_type,
+            Err(error) => return self.errors.push(error),
+        };
+        let mut value = self.get_operand_expr(&value, bb);
+        if let Some(width) = float {
+            let bits = Expr::call(format!("f{width}_to_bits"), vec![value]);
+            value = Expr::method_call(Expr::method_call(bits, "try_into", Vec::new()), "unwrap", Vec::new());
+        }
+        let arguments = vec![self.get_operand_expr(&pointer, bb), value];
+        self.push_statement(Statement::Expr(Expr::call(format!("store_{access_type}"), arguments)));
+    }
+
+    /// Translate a `getelementptr`. The constant indexes are summed in one offset and each other
+    /// index adds `index * <size of the element>` to the address:
+    /// %elem = getelementptr inbounds { i64, i64 }, ptr %pairs, i64 %i, i32 1
+    /// becomes
+    /// let elem = ptr_add(ptr_add(pairs, i.into(), 16), 8, 1);
+    pub fn process_gep(&mut self, instruction: &InstructionValue<'ctx>, bb: &BasicBlock<'ctx>) {
+        let pointer = Self::memory_operand(instruction, 0);
+        if !pointer.is_pointer_value() {
+            panic!("getelementptr on vectors of pointers isn't supported");
+        }
+        // inkwell doesn't expose the type the indexes go into.
+        let mut ty = unsafe { BasicTypeEnum::new(LLVMGetGEPSourceElementType(instruction.as_value_ref())) };
+        let target_data = self.target_data();
+        let mut address = self.get_operand_expr(&pointer, bb);
+        let mut offset = 0_i128;
+        for position in 1..instruction.get_num_operands() {
+            let operand = Self::memory_operand(instruction, position);
+            let index = operand.into_int_value();
+            // The first index steps over whole elements of the source type, the next ones go into them.
+            let size = if position == 1 {
+                target_data.get_abi_size(&ty)
+            } else {
+                match ty {
+                    BasicTypeEnum::StructType(struct_type) => {
+                        let field =
+                            index.get_zero_extended_constant().expect("Struct field indexes are constant") as u32;
+                        let field_offset = target_data.offset_of_element(&struct_type, field);
+                        offset += i128::from(field_offset.expect("The struct should have the field"));
+                        ty = struct_type.get_field_type_at_index(field).expect("The struct should have the field");
+                        continue;
+                    }
+                    BasicTypeEnum::ArrayType(array) => {
+                        ty = array.get_element_type();
+                        target_data.get_abi_size(&ty)
+                    }
+                    _ => panic!("getelementptr into {} isn't supported", ty.print_to_string()),
+                }
+            };
+            match index.get_sign_extended_constant() {
+                Some(index) => offset += i128::from(index) * i128::from(size),
+                None => {
+                    let index = Expr::method_call(self.get_operand_expr(&operand, bb), "into", Vec::new());
+                    address = Expr::call(POINTER_ADD, vec![address, index, Expr::literal(size.to_string())]);
+                }
+            }
+        }
+        if offset != 0 {
+            address = Expr::call(POINTER_ADD, vec![address, Expr::literal(offset.to_string()), Expr::literal("1")]);
+        }
+        let result_variable = self.get_result_variable(instruction);
+        self.push_statement(result_variable.set(address));
+    }
+}
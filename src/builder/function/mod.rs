@@ -1,18 +1,37 @@
 use std::collections::{HashMap, HashSet};
-use std::ffi::CStr;
 use std::fmt::Display;
+use std::rc::Rc;
 
 use inkwell::basic_block::BasicBlock;
-use inkwell::values::{BasicValueEnum, InstructionOpcode};
+use inkwell::values::{AnyValue, BasicValueEnum, InstructionOpcode, InstructionValue};
 use inkwell::IntPredicate;
 use petgraph::graph::{DiGraph, NodeIndex};
+use pointer::PointerConversion;
+
+use super::arithmetic::ArithmeticHelper;
+use super::naming::NameScope;
+use super::options::TranslationOptions;
+use super::types::{cairo_default_value, null_pointer, StructNames};
+use crate::ast::printer::Printer;
+use crate::ast::{BinaryOperator, Block, Expr, Statement};
+use crate::source_map::{DebugMetadata, SourceOrigin};
 
 pub mod binary;
 pub mod branch;
+pub mod call;
+pub mod debug;
 pub mod extend;
+pub mod float;
+pub mod memory;
+pub mod names;
+pub mod panic;
 pub mod phi;
+pub mod pointer;
+pub mod poison;
 pub mod preprocessing;
+pub mod recursion;
 pub mod types;
+pub mod vector;
 
 #[derive(Default, Clone, Debug)]
 pub struct CairoFunctionBuilder<'ctx> {
@@ -21,14 +40,88 @@ pub struct CairoFunctionBuilder<'ctx> {
     pub(crate) bb_graph: DiGraph<BasicBlock<'ctx>, ()>,
     pub(crate) node_id_from_name: HashMap<BasicBlock<'ctx>, NodeIndex<u32>>,
     pub(crate) function: CairoFunction,
-    pub(crate) phis_bblock: HashSet<BasicBlock<'ctx>>,
-    pub(crate) bblock_variables: HashMap<BasicBlock<'ctx>, HashMap<BasicValueEnum<'ctx>, String>>,
+    /// Incoming blocks of the phis in the order of the function so the output is deterministic.
+    pub(crate) phis_bblock: Vec<BasicBlock<'ctx>>,
+    pub(crate) bblock_variables: HashMap<BasicBlock<'ctx>, HashMap<BasicValueEnum<'ctx>, PhiVariable>>,
     pub(crate) if_blocks: HashMap<BasicBlock<'ctx>, BasicValueEnum<'ctx>>,
     pub(crate) else_blocks: HashSet<BasicBlock<'ctx>>,
     pub(crate) return_block: Option<BasicBlock<'ctx>>,
+    /// Values that are known to be `poison` at compile time.
+    pub(crate) poison_values: HashSet<BasicValueEnum<'ctx>>,
+    pub(crate) options: TranslationOptions,
+    /// Scopes (if/else/loop) that are opened but not closed yet. The innermost is the last one.
+    pub(crate) scopes: Vec<Scope>,
+    /// Debug info of the module, shared by all the functions.
+    pub(crate) debug_metadata: Rc<DebugMetadata>,
+    /// Names of the source variables the values are bound to.
+    pub(crate) debug_names: HashMap<BasicValueEnum<'ctx>, String>,
+    /// Identifiers used in the function.
+    pub(crate) names: NameScope,
+    /// Cairo name of each value of the function.
+    pub(crate) value_names: HashMap<BasicValueEnum<'ctx>, String>,
+    /// `is_from_<block>` boolean of each basic block.
+    pub(crate) block_flags: HashMap<BasicBlock<'ctx>, String>,
+    /// LLVM symbol => cairo name of the functions of the module, shared by all the functions.
+    pub(crate) function_names: Rc<HashMap<String, String>>,
+    /// LLVM struct name => cairo name of the structs of the module, shared by all the functions.
+    pub(crate) struct_names: Rc<StructNames>,
+    /// Data layout of the module, shared by all the functions.
+    pub(crate) data_layout: Rc<String>,
+    /// Why some instructions can't be translated, the translation of the function fails with them.
+    pub(crate) errors: Vec<String>,
+}
+
+/// A scope that is being built. Once it's closed it becomes a statement of its parent scope.
+#[derive(Clone, Debug)]
+pub(crate) struct Scope {
+    kind: ScopeKind,
+    block: Block,
+}
+
+#[derive(Clone, Debug)]
+enum ScopeKind {
+    If { condition: Expr },
+    Else { condition: Expr, then_block: Block },
+    Loop,
+}
+
+/// A value that is an incoming value of a phi of its own basic block. It's computed in `temp` and
+/// copied to `outer`, which is declared before the scope of the block, when the block ends.
+#[derive(Clone, Debug)]
+pub(crate) struct PhiVariable {
+    pub(crate) outer: String,
+    pub(crate) temp: String,
+}
+
+/// Variable an instruction stores its result in.
+pub struct ResultVariable {
+    pub(crate) name: String,
+    /// Was the variable declared before a new scope. If so we only need to assign it.
+    pub(crate) declared: bool,
+}
+
+impl ResultVariable {
+    /// Get the statement that stores `value` in the variable.
+    pub fn set(self, value: Expr) -> Statement {
+        if self.declared {
+            Statement::assign(self.name, value)
+        } else {
+            Statement::declare(self.name, value)
+        }
+    }
 }
 
 impl<'ctx> CairoFunctionBuilder<'ctx> {
+    pub fn new(
+        options: TranslationOptions,
+        debug_metadata: Rc<DebugMetadata>,
+        function_names: Rc<HashMap<String, String>>,
+        struct_names: Rc<StructNames>,
+        data_layout: Rc<String>,
+    ) -> Self {
+        Self { options, debug_metadata, function_names, struct_names, data_layout, ..Default::default() }
+    }
+
     pub fn name(&self) -> &str {
         &self.function.signature.name
     }
@@ -41,13 +134,81 @@ impl<'ctx> CairoFunctionBuilder<'ctx> {
         &self.function.signature.return_type
     }
 
-    pub fn push_body_line(&mut self, line: String) {
-        self.function.body.push_line(line)
+    /// Add a statement to the innermost open scope.
+    pub fn push_statement(&mut self, statement: Statement) {
+        match self.scopes.last_mut() {
+            Some(scope) => scope.block.push(statement),
+            None => self.function.body.push_statement(statement),
+        }
+    }
+
+    /// Open an `if <condition> {` scope.
+    pub fn open_if(&mut self, condition: Expr) {
+        self.scopes.push(Scope { kind: ScopeKind::If { condition }, block: Block::default() });
+    }
+
+    /// Close the current `if` scope and open its `else` scope.
+    pub fn open_else(&mut self) {
+        let scope = self.scopes.pop().expect("An else clause should follow an if clause");
+        let ScopeKind::If { condition } = scope.kind else {
+            panic!("An else clause should follow an if clause");
+        };
+        self.scopes
+            .push(Scope { kind: ScopeKind::Else { condition, then_block: scope.block }, block: Block::default() });
+    }
+
+    /// Open a `loop {` scope.
+    pub fn open_loop(&mut self) {
+        self.scopes.push(Scope { kind: ScopeKind::Loop, block: Block::default() });
+    }
+
+    /// Close the innermost scope and add it to its parent scope. Does nothing if there is no open
+    /// scope.
+    pub fn close_scope(&mut self) {
+        let Some(scope) = self.scopes.pop() else {
+            return;
+        };
+        let statement = match scope.kind {
+            ScopeKind::If { condition } => Statement::If { condition, then_block: scope.block, else_block: None },
+            ScopeKind::Else { condition, then_block } => {
+                Statement::If { condition, then_block, else_block: Some(scope.block) }
+            }
+            ScopeKind::Loop => Statement::Loop(scope.block),
+        };
+        self.push_statement(statement);
+    }
+
+    /// Close all the scopes that are still open. This is called once the whole function is
+    /// translated.
+    pub fn close_all_scopes(&mut self) {
+        while !self.scopes.is_empty() {
+            self.close_scope();
+        }
     }
-    pub fn get_name(&self, name: &CStr) -> String {
-        (!name.is_empty())
-            .then(|| name.to_str().expect("Variable name for binary op should be uft-8").replace('.', "_"))
-            .unwrap_or_else(|| format!("var{}", self.variables.keys().count()))
+
+    /// Get the cairo representation of an operand. It's either a constant or a variable that might
+    /// be in our annoying phi mapping.
+    pub fn get_operand_expr(&self, operand: &BasicValueEnum<'ctx>, bb: &BasicBlock<'ctx>) -> Expr {
+        if Self::is_undef_or_poison(operand) {
+            return cairo_default_value(operand.get_type(), &self.struct_names);
+        }
+        match operand {
+            BasicValueEnum::IntValue(_) => self.get_int_operand_expr(operand, bb),
+            BasicValueEnum::FloatValue(value) if value.is_const() => self.float_literal(*value),
+            BasicValueEnum::PointerValue(pointer) if pointer.is_null() => null_pointer(),
+            BasicValueEnum::VectorValue(vector) if vector.is_const() => {
+                Expr::FixedArray(self.extract_const_vector_elements(*vector))
+            }
+            _ => Expr::Variable(
+                self.bblock_variables
+                    .get(bb)
+                    .and_then(|annoying_phis| annoying_phis.get(operand))
+                    .map(|variable| &variable.temp)
+                    .or_else(|| self.variables.get(operand))
+                    .cloned()
+                    .unwrap_or_else(|| self.get_value_name(operand)),
+            ),
+        }
     }
 
     /// Set all the basic block booleans to the correct value. This should be used at the end of a
@@ -55,20 +216,29 @@ impl<'ctx> CairoFunctionBuilder<'ctx> {
     pub fn set_basic_block_booleans(&mut self, bb: &BasicBlock<'ctx>) {
         // If we're not in the last basic block set all the booleans to the right value to know what basic
         // block we were in so we can process the phi instruction can work correctly
+        let mut statements = Vec::new();
         if self.return_block.is_some_and(|bblock| bblock != *bb) || self.return_block.is_none() {
             for bblock in self.phis_bblock.iter() {
-                // If we were in this basic block
-                if self.get_name(bblock.get_name()) == self.get_name(bb.get_name()) {
-                    let code_line = format!("is_from_{} = true;", self.get_name(bblock.get_name()),);
-                    self.function.body.push_line(code_line);
-                } else {
-                    // if we were not in this basic block
-                    let code_line = format!("is_from_{} = false;", self.get_name(bblock.get_name()),);
-                    self.function.body.push_line(code_line);
-                }
+                // true if we were in this basic block, false otherwise.
+                let is_from = bblock == bb;
+                statements.push(Statement::assign(self.block_flag(bblock), Expr::literal(is_from.to_string())));
             }
         }
+        statements.into_iter().for_each(|statement| self.push_statement(statement));
+    }
+    /// Get the LLVM instruction (and its source location if there is debug info) the statements
+    /// we're about to emit come from.
+    pub fn instruction_origin(&self, instruction: &InstructionValue<'ctx>, bb: &BasicBlock<'ctx>) -> SourceOrigin {
+        let printed = instruction.print_to_string().to_string();
+        SourceOrigin {
+            function: self.name().to_owned(),
+            block: bb.get_name().to_string_lossy().into_owned(),
+            // Remove the metadata `, !dbg !12`.
+            instruction: printed.split(", !").next().unwrap_or_default().trim().to_owned(),
+            location: self.debug_metadata.instruction_location(&printed),
+        }
     }
+
     /// Process a basic block and convert it to cairo. It will call itself recursively through the
     /// [CairoFunctionBuilder::process_branch] function.
     pub fn process_basic_block(&mut self, bb: &BasicBlock<'ctx>) {
@@ -85,64 +255,126 @@ impl<'ctx> CairoFunctionBuilder<'ctx> {
 
         // Iterate over each instruction of the basic block. 1 instruction == 1 LLVM code line
         for instruction in bb.get_instructions() {
-            // Get the opcode of the instruction
-            let code_line = match instruction.get_opcode() {
-                InstructionOpcode::Add => self.process_binary_int_op(&instruction, "+", bb),
-                InstructionOpcode::Sub => self.process_binary_int_op(&instruction, "-", bb),
-                InstructionOpcode::Return => self.process_return(&instruction),
+            // Mark where the following statements come from for the source map.
+            self.push_statement(Statement::Origin(self.instruction_origin(&instruction, bb)));
+            // Get the opcode of the instruction and add its translation to the function body.
+            match instruction.get_opcode() {
+                InstructionOpcode::Add => self.process_binary_int_op(&instruction, BinaryOperator::Add, bb),
+                InstructionOpcode::Sub => self.process_binary_int_op(&instruction, BinaryOperator::Sub, bb),
+                InstructionOpcode::Return => self.process_return(&instruction, bb),
                 InstructionOpcode::ICmp => {
                     // we just matched on ICmp so it will never fail
                     match instruction.get_icmp_predicate().unwrap() {
-                        IntPredicate::EQ => self.process_binary_int_op(&instruction, "==", bb),
-                        IntPredicate::NE => self.process_binary_int_op(&instruction, "!=", bb),
-                        IntPredicate::ULT => self.process_binary_int_op(&instruction, "<", bb),
-                        _ => "".to_owned(),
+                        IntPredicate::EQ => self.process_binary_int_op(&instruction, BinaryOperator::Eq, bb),
+                        IntPredicate::NE => self.process_binary_int_op(&instruction, BinaryOperator::Ne, bb),
+                        IntPredicate::ULT => self.process_binary_int_op(&instruction, BinaryOperator::Lt, bb),
+                        _ => (),
                     }
                 }
                 InstructionOpcode::Br => self.process_branch(&instruction, bb, &is_loop, &is_else),
-                InstructionOpcode::ZExt => self.process_zext(&instruction, &is_loop),
+                InstructionOpcode::ZExt => self.process_zext(&instruction, bb),
                 InstructionOpcode::Phi => self.process_phi(&instruction, bb),
-                _ => "".to_owned(),
+                InstructionOpcode::ExtractElement => self.process_extract_element(&instruction, bb),
+                InstructionOpcode::InsertElement => self.process_insert_element(&instruction, bb),
+                InstructionOpcode::ShuffleVector => self.process_shuffle_vector(&instruction, bb),
+                InstructionOpcode::Call => self.process_call(&instruction, bb),
+                InstructionOpcode::Freeze => self.process_freeze(&instruction, bb),
+                InstructionOpcode::Unreachable => self.process_unreachable(),
+                InstructionOpcode::FAdd => self.process_float_op(&instruction, "add", bb),
+                InstructionOpcode::FSub => self.process_float_op(&instruction, "sub", bb),
+                InstructionOpcode::FMul => self.process_float_op(&instruction, "mul", bb),
+                InstructionOpcode::FDiv => self.process_float_op(&instruction, "div", bb),
+                InstructionOpcode::FRem => self.process_float_op(&instruction, "rem", bb),
+                InstructionOpcode::FNeg => self.process_float_op(&instruction, "neg", bb),
+                InstructionOpcode::FCmp => self.process_fcmp(&instruction, bb),
+                InstructionOpcode::FPToSI => self.process_float_to_int(&instruction, true, bb),
+                InstructionOpcode::FPToUI => self.process_float_to_int(&instruction, false, bb),
+                InstructionOpcode::SIToFP => self.process_int_to_float(&instruction, true, bb),
+                InstructionOpcode::UIToFP => self.process_int_to_float(&instruction, false, bb),
+                InstructionOpcode::FPExt | InstructionOpcode::FPTrunc => self.process_float_cast(&instruction, bb),
+                InstructionOpcode::BitCast => self.process_bitcast(&instruction, bb),
+                InstructionOpcode::PtrToInt => self.process_ptr_to_int(&instruction, bb),
+                InstructionOpcode::IntToPtr => self.process_int_to_ptr(&instruction, bb),
+                InstructionOpcode::AddrSpaceCast => self.process_pointer_cast(&instruction, bb),
+                InstructionOpcode::Alloca => self.process_alloca(&instruction),
+                InstructionOpcode::Load => self.process_load(&instruction, bb),
+                InstructionOpcode::Store => self.process_store(&instruction, bb),
+                InstructionOpcode::GetElementPtr => self.process_gep(&instruction, bb),
+                _ => (),
             };
-            self.push_body_line(code_line);
+            self.track_poison(&instruction);
             if is_loop && instruction.get_opcode() == InstructionOpcode::Br {
                 self.close_scopes(bb, &is_else, &is_loop);
             }
-            // Add the line to the function body
         }
     }
 }
 
 #[derive(Default, Clone, PartialEq, Debug)]
 pub struct CairoFunction {
+    /// LLVM symbol of the function it was translated from.
+    pub(crate) symbol: String,
     pub(crate) signature: CairoFunctionSignature,
     pub(crate) body: CairoFunctionBody,
+    /// The function can call itself, directly or through other functions. Set once all the
+    /// functions are translated, see [crate::builder::CairoBuilder::flag_recursive_functions].
+    pub(crate) recursive: bool,
+    /// Arithmetic helpers the function calls, see [crate::builder::arithmetic].
+    pub(crate) arithmetic_helpers: Vec<ArithmeticHelper>,
+    /// Conversions between pointers and integers the function calls, see [PointerConversion].
+    pub(crate) pointer_conversions: Vec<PointerConversion>,
+}
+
+impl CairoFunction {
+    /// A function that isn't translated from an LLVM function (helpers, entry points...).
+    pub fn new(signature: CairoFunctionSignature, body: Block) -> Self {
+        Self {
+            symbol: String::new(),
+            signature,
+            body: CairoFunctionBody::new(body),
+            recursive: false,
+            arithmetic_helpers: Vec::new(),
+            pointer_conversions: Vec::new(),
+        }
+    }
+
+    pub fn recursive(&self) -> bool {
+        self.recursive
+    }
 }
 
 impl Display for CairoFunction {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.write_str(&format!("{} {{\n{}\n}}", self.signature, self.body))
+        f.write_str(&Printer::default().function(self, 0))
     }
 }
 
 #[derive(Default, Clone, PartialEq, Debug)]
-pub struct CairoFunctionBody(Vec<String>);
+pub struct CairoFunctionBody(Block);
 
 impl CairoFunctionBody {
-    pub fn new(body: Vec<String>) -> Self {
+    pub fn new(body: Block) -> Self {
         Self(body)
     }
+
+    pub fn block(&self) -> &Block {
+        &self.0
+    }
+
+    pub fn block_mut(&mut self) -> &mut Block {
+        &mut self.0
+    }
 }
 
 impl CairoFunctionBody {
-    pub fn push_line(&mut self, line: String) {
-        self.0.push(line)
+    pub fn push_statement(&mut self, statement: Statement) {
+        self.0.push(statement)
     }
 }
 
 impl Display for CairoFunctionBody {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.write_str(&self.0.join("\n"))
+        f.write_str(&self.0.to_string())
     }
 }
 
@@ -151,26 +383,38 @@ pub struct CairoFunctionSignature {
     pub(crate) name: String,
     pub(crate) parameters: CairoParameters,
     pub(crate) return_type: String,
+    /// `pub fn` or `fn`, see [crate::builder::visibility].
+    pub(crate) public: bool,
 }
 
 impl CairoFunctionSignature {
+    /// Signature of a public function.
     pub fn new(name: String, parameters: Vec<CairoParameter>, return_type: String) -> Self {
-        Self { name, parameters: CairoParameters(parameters), return_type }
+        Self { name, parameters: CairoParameters(parameters), return_type, public: true }
+    }
+
+    /// `pub ` or nothing.
+    pub fn visibility(&self) -> &'static str {
+        if self.public {
+            "pub "
+        } else {
+            ""
+        }
     }
 }
 
 #[derive(Default, Clone, PartialEq, Debug)]
-pub struct CairoParameters(Vec<CairoParameter>);
+pub struct CairoParameters(pub(crate) Vec<CairoParameter>);
 
 impl Display for CairoParameters {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.write_str(&self.0.iter().map(ToString::to_string).collect::<Vec<_>>().join(","))
+        f.write_str(&self.0.iter().map(ToString::to_string).collect::<Vec<_>>().join(", "))
     }
 }
 
 impl Display for CairoFunctionSignature {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.write_str(&format!("pub fn {}({}) -> {}", self.name, self.parameters, self.return_type))
+        f.write_str(&format!("{}fn {}({}) -> {}", self.visibility(), self.name, self.parameters, self.return_type))
     }
 }
 
@@ -178,15 +422,23 @@ impl Display for CairoFunctionSignature {
 pub struct CairoParameter {
     pub(crate) name: String,
     pub(crate) ty: String,
+    /// `ref <name>: <ty>`, the changes the function makes to the parameter are seen by the caller.
+    pub(crate) by_ref: bool,
 }
 impl CairoParameter {
     pub fn new(name: String, ty: String) -> Self {
-        Self { name, ty }
+        Self { name, ty, by_ref: false }
+    }
+
+    /// A parameter passed by reference, see [CairoParameter::by_ref].
+    pub fn by_ref(name: String, ty: String) -> Self {
+        Self { name, ty, by_ref: true }
     }
 }
 
 impl Display for CairoParameter {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.write_str(&format!("{}: {}", self.name, self.ty))
+        let by_ref = if self.by_ref { "ref " } else { "" };
+        f.write_str(&format!("{by_ref}{}: {}", self.name, self.ty))
     }
 }
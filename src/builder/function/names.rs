@@ -0,0 +1,75 @@
+use inkwell::basic_block::BasicBlock;
+use inkwell::values::{AnyValue, BasicValueEnum, FunctionValue};
+
+use super::CairoFunctionBuilder;
+use crate::builder::allocator::MEMORY_PARAMETER;
+use crate::builder::naming::{function_identifier, sanitize_identifier};
+
+impl<'ctx> CairoFunctionBuilder<'ctx> {
+    /// Give a unique cairo name to every value and basic block of the function before translating
+    /// it. Names are picked in that order so the most meaningful ones win when they collide:
+    /// 1. Values bound to a source variable get its name (see
+    ///    [CairoFunctionBuilder::collect_debug_names]).
+    /// 2. Named values get their LLVM name (`%x.y` => `x_y`).
+    /// 3. Unnamed values (`%4`) get `var0`, `var1`...
+    /// 4. Each basic block gets the `is_from_<block>` boolean used to translate the phis.
+    ///
+    /// `memory` is reserved first, it's the parameter the functions that access the memory get
+    /// once they're all translated (see [crate::builder::CairoBuilder::thread_memory]).
+    pub fn assign_names(&mut self, function: &FunctionValue<'ctx>) {
+        self.names.fresh(MEMORY_PARAMETER);
+        let values = function
+            .get_param_iter()
+            .chain(
+                function
+                    .get_basic_block_iter()
+                    .flat_map(|bb| bb.get_instructions())
+                    .filter_map(|instruction| BasicValueEnum::try_from(instruction.as_any_value_enum()).ok()),
+            )
+            .collect::<Vec<_>>();
+        for value in values.iter() {
+            if let Some(name) = self.debug_names.get(value) {
+                let name = self.names.fresh(name);
+                self.value_names.insert(*value, name);
+            }
+        }
+        for value in values.iter() {
+            let llvm_name = value.get_name().to_string_lossy();
+            if !llvm_name.is_empty() && !self.value_names.contains_key(value) {
+                let name = self.names.fresh(&llvm_name);
+                self.value_names.insert(*value, name);
+            }
+        }
+        let mut unnamed = 0;
+        for value in values.iter() {
+            if self.value_names.contains_key(value) {
+                continue;
+            }
+            let name = self.names.fresh(&format!("var{unnamed}"));
+            self.value_names.insert(*value, name);
+            unnamed += 1;
+        }
+        for (index, bb) in function.get_basic_block_iter().enumerate() {
+            let block_name = bb.get_name().to_string_lossy();
+            let block_name = if block_name.is_empty() { format!("bb{index}") } else { block_name.into_owned() };
+            let flag = self.names.fresh(&format!("is_from_{block_name}"));
+            self.block_flags.insert(bb, flag);
+        }
+    }
+
+    /// Get the cairo name of a value. Values that aren't defined in the function (globals...) get
+    /// their sanitized LLVM name.
+    pub fn get_value_name(&self, value: &BasicValueEnum<'ctx>) -> String {
+        self.value_names.get(value).cloned().unwrap_or_else(|| sanitize_identifier(&value.get_name().to_string_lossy()))
+    }
+
+    /// Get the boolean that is true when the last executed basic block is `bb`.
+    pub fn block_flag(&self, bb: &BasicBlock<'ctx>) -> String {
+        self.block_flags.get(bb).cloned().expect("All the basic blocks should be named before the translation")
+    }
+
+    /// Get the cairo name of a function from its LLVM symbol.
+    pub fn function_name(&self, symbol: &str) -> String {
+        self.function_names.get(symbol).cloned().unwrap_or_else(|| function_identifier(symbol))
+    }
+}
@@ -0,0 +1,214 @@
+use inkwell::basic_block::BasicBlock;
+use inkwell::values::{AnyValue, AsValueRef, BasicValueEnum, GlobalValue, InstructionValue};
+
+use super::CairoFunctionBuilder;
+use crate::ast::Expr;
+
+/// Paths of the functions of the rust runtime that always panic. A path that ends with `::` is a
+/// module, all its functions panic.
+const PANIC_FUNCTIONS: [&str; 5] = [
+    "core::panicking::",
+    "std::panicking::",
+    "core::option::unwrap_failed",
+    "core::option::expect_failed",
+    "core::result::unwrap_failed",
+];
+
+/// Demangled path of a rust symbol, without the hash. Both manglings are supported, the legacy one
+/// `_ZN4core9panicking5panic17h0123456789abcdefE` and v0 `_RNvNtCs1234_4core9panicking5panic`
+/// give `core::panicking::panic`. `None` if the symbol isn't a mangled rust symbol.
+fn rust_path(symbol: &str) -> Option<String> {
+    // The alternate format doesn't print the hash.
+    rustc_demangle::try_demangle(symbol).ok().map(|demangled| format!("{demangled:#}"))
+}
+
+/// Is the function at `path` (demangled) a function of the rust runtime that panics.
+fn is_panic_path(path: &str) -> bool {
+    PANIC_FUNCTIONS.iter().any(|panic_path| match panic_path.strip_suffix("::") {
+        Some(_) => path.starts_with(panic_path),
+        None => path == *panic_path,
+    })
+}
+
+/// Is `symbol` a function of the rust runtime that panics.
+pub fn is_panic_function(symbol: &str) -> bool {
+    rust_path(symbol).is_some_and(|path| is_panic_path(&path))
+}
+
+/// Extract the content of all the LLVM c strings (`c"hello\0A"`) of a printed value.
+fn extract_c_strings(printed: &str) -> Vec<Vec<u8>> {
+    let mut strings = Vec::new();
+    let mut rest = printed;
+    while let Some(start) = rest.find("c\"") {
+        rest = &rest[start + 2..];
+        // Quotes are escaped in c strings so the next one is the end of the string.
+        let Some(end) = rest.find('"') else {
+            break;
+        };
+        strings.push(unescape_c_string(&rest[..end]));
+        rest = &rest[end + 1..];
+    }
+    strings
+}
+
+/// Non printable characters of LLVM c strings are escaped with their hex value: `\0A`.
+fn unescape_c_string(escaped: &str) -> Vec<u8> {
+    let bytes = escaped.as_bytes();
+    let mut unescaped = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        if bytes[index] == b'\\' {
+            unescaped.push(u8::from_str_radix(&escaped[index + 1..index + 3], 16).expect("Escaped byte should be hex"));
+            index += 3;
+        } else {
+            unescaped.push(bytes[index]);
+            index += 1;
+        }
+    }
+    unescaped
+}
+
+/// Format bytes as a cairo string literal that can be used in `panic!`.
+fn cairo_panic_message(bytes: &[u8]) -> String {
+    let mut message = String::with_capacity(bytes.len());
+    for byte in bytes {
+        match byte {
+            b'"' => message.push_str("\\\""),
+            b'\\' => message.push_str("\\\\"),
+            // `panic!` formats its message so braces need to be escaped.
+            b'{' => message.push_str("{{"),
+            b'}' => message.push_str("}}"),
+            b'\n' => message.push_str("\\n"),
+            0x20..=0x7e => message.push(*byte as char),
+            _ => message.push_str(&format!("\\x{byte:02x}")),
+        }
+    }
+    message
+}
+
+/// Message of the `core::panicking::panic_const::panic_const_*` functions that rustc emits for
+/// the arithmetic checks. The message isn't passed as an argument but is part of the path:
+/// `core::panicking::panic_const::panic_const_add_overflow`
+fn panic_const_message(path: &str) -> String {
+    let (_, function) = path.rsplit_once("::").expect("Path should be in a module");
+    let check = function.strip_prefix("panic_const_").expect("Path should be a panic_const function");
+    match check {
+        "add_overflow" => "attempt to add with overflow".to_owned(),
+        "sub_overflow" => "attempt to subtract with overflow".to_owned(),
+        "mul_overflow" => "attempt to multiply with overflow".to_owned(),
+        "div_overflow" => "attempt to divide with overflow".to_owned(),
+        "rem_overflow" => "attempt to calculate the remainder with overflow".to_owned(),
+        "neg_overflow" => "attempt to negate with overflow".to_owned(),
+        "shl_overflow" => "attempt to shift left with overflow".to_owned(),
+        "shr_overflow" => "attempt to shift right with overflow".to_owned(),
+        "div_by_zero" => "attempt to divide by zero".to_owned(),
+        "rem_by_zero" => "attempt to calculate the remainder with a divisor of zero".to_owned(),
+        _ => check.replace('_', " "),
+    }
+}
+
+impl<'ctx> CairoFunctionBuilder<'ctx> {
+    /// If the value is a global, get it. Printing a global gives its definition
+    /// `@alloc_123 = private unnamed_addr constant ...` so that's how we recognize them.
+    fn as_global(value: &BasicValueEnum<'ctx>) -> Option<GlobalValue<'ctx>> {
+        (value.is_pointer_value() && value.print_to_string().to_string().starts_with('@'))
+            .then(|| unsafe { GlobalValue::new(value.as_value_ref()) })
+    }
+
+    /// Get the string stored in a global constant.
+    /// @alloc_msg = private unnamed_addr constant <{ [28 x i8] }> <{ [28 x i8] c"attempt to add
+    /// with overflow" }>
+    fn global_string(value: &BasicValueEnum<'ctx>) -> Option<Vec<u8>> {
+        let global = Self::as_global(value)?;
+        extract_c_strings(&global.get_initializer()?.print_to_string().to_string()).into_iter().next()
+    }
+
+    /// Get the `file:line:col` of a `core::panic::Location` global. It's a `&str` (pointer to the
+    /// file name + length) followed by the line and column. The length, line and column are
+    /// stored as bytes:
+    /// @alloc_loc = private unnamed_addr constant <{ ptr, [16 x i8] }> <{ ptr @alloc_file, [16 x
+    /// i8] c"\0A\00\00\00\00\00\00\00\03\00\00\00\05\00\00\00" }>
+    fn panic_location(value: &BasicValueEnum<'ctx>) -> Option<String> {
+        let BasicValueEnum::StructValue(location) = Self::as_global(value)?.get_initializer()? else {
+            return None;
+        };
+        let file = Self::global_string(&location.get_field_at_index(0)?)?;
+        let data = extract_c_strings(&location.get_field_at_index(1)?.print_to_string().to_string()).pop()?;
+        let file_len = u64::from_le_bytes(data.get(0..8)?.try_into().ok()?) as usize;
+        let line = u32::from_le_bytes(data.get(8..12)?.try_into().ok()?);
+        let col = u32::from_le_bytes(data.get(12..16)?.try_into().ok()?);
+        Some(format!("{}:{line}:{col}", String::from_utf8_lossy(file.get(..file_len).unwrap_or(&file))))
+    }
+
+    /// Get the panic message passed as a `&str` (pointer + length) in the arguments of a panicking
+    /// function.
+    fn panic_message_argument(arguments: &[BasicValueEnum<'ctx>]) -> Option<Vec<u8>> {
+        // The last argument is the location which is a global as well.
+        let (index, mut message) = arguments[..arguments.len().saturating_sub(1)]
+            .iter()
+            .enumerate()
+            .find_map(|(index, arg)| Self::global_string(arg).map(|msg| (index, msg)))?;
+        // The length of the string follows the pointer.
+        if let Some(len) = arguments
+            .get(index + 1)
+            .filter(|len| len.is_int_value())
+            .and_then(|len| len.into_int_value().get_zero_extended_constant())
+        {
+            message.truncate(len as usize);
+        }
+        Some(message)
+    }
+
+    /// Translate the calls to the well known functions of the rust runtime that panic. Those are
+    /// the `core::panicking::*` functions, the bounds checks and the `unwrap`/`expect` failures.
+    /// They're lowered to a cairo `panic!` with the message and the location recovered from the
+    /// globals they take as arguments. Returns the `panic!` expression or `None` if the called
+    /// function doesn't panic.
+    ///
+    /// # Arguments
+    ///
+    /// * `instruction` - The call instruction.
+    /// * `called_function` - The symbol of the called function. It's demangled to recognize the
+    ///   functions whatever the mangling: `_ZN4core9panicking5panic17h0123456789abcdefE` or
+    ///   `_RNvNtCs1234_4core9panicking5panic` are `core::panicking::panic`.
+    pub fn process_rust_panic(
+        &mut self,
+        instruction: &InstructionValue<'ctx>,
+        called_function: &str,
+        bb: &BasicBlock<'ctx>,
+    ) -> Option<Expr> {
+        let path = rust_path(called_function).filter(|path| is_panic_path(path))?;
+        let arguments = (0..instruction.get_num_operands() - 1)
+            .filter_map(|index| instruction.get_operand(index).and_then(|operand| operand.left()))
+            .collect::<Vec<_>>();
+        // The location is always the last argument.
+        let location = arguments.last().and_then(Self::panic_location);
+
+        let message = if path == "core::panicking::panic_bounds_check" {
+            // panic_bounds_check(index, len, location) the message depends on runtime values.
+            let index = self.get_operand_expr(&arguments[0], bb);
+            let len = self.get_operand_expr(&arguments[1], bb);
+            let location =
+                location.map(|location| format!("{}: ", cairo_panic_message(location.as_bytes()))).unwrap_or_default();
+            return Some(Expr::Panic {
+                message: format!("{location}index out of bounds: the len is {{}} but the index is {{}}"),
+                arguments: vec![len, index],
+            });
+        } else if path.starts_with("core::panicking::panic_const::") {
+            panic_const_message(&path)
+        } else if path == "core::option::unwrap_failed" {
+            "called `Option::unwrap()` on a `None` value".to_owned()
+        } else {
+            // panic(message, len, location), expect_failed(message, len, location)...
+            // panic_fmt takes a `fmt::Arguments` that is built on the stack so we can't recover its message.
+            Self::panic_message_argument(&arguments)
+                .map(|message| String::from_utf8_lossy(&message).into_owned())
+                .unwrap_or_else(|| "explicit panic".to_owned())
+        };
+        let message = cairo_panic_message(message.as_bytes());
+        Some(match location {
+            Some(location) => Expr::panic(format!("{}: {}", cairo_panic_message(location.as_bytes()), message)),
+            None => Expr::panic(message),
+        })
+    }
+}
@@ -0,0 +1,209 @@
+use inkwell::basic_block::BasicBlock;
+use inkwell::types::BasicTypeEnum;
+use inkwell::values::{BasicValueEnum, InstructionValue};
+
+use super::{CairoFunction, CairoFunctionBuilder, CairoFunctionSignature, CairoParameter};
+use crate::ast::{BinaryOperator, Block, Expr, Pattern, Statement};
+use crate::builder::types::POINTER_WIDTH;
+
+/// Conversion between an address and an `i<width>` with the same bit pattern, for `ptrtoint` and
+/// `inttoptr`. The cairo integers are signed and there is no direct conversion between them and
+/// the `u64` addresses so it goes through `felt252`, like the ABI conversions of the contracts:
+/// `inttoptr i64 -1` is the address `0xffffffffffffffff`. The functions record the conversions they
+/// call and they're emitted after the functions of the module, like the arithmetic helpers.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct PointerConversion {
+    /// `inttoptr` if set, `ptrtoint` otherwise.
+    pub(crate) to_pointer: bool,
+    pub(crate) width: u32,
+}
+
+impl PointerConversion {
+    /// `ptr_from_i32` for `inttoptr`, `ptr_to_i32` for `ptrtoint`.
+    pub fn name(&self) -> String {
+        let direction = if self.to_pointer { "from" } else { "to" };
+        format!("ptr_{direction}_i{}", self.width)
+    }
+
+    /// The cairo function of the conversion. `ptrtoint` keeps the low bits of the address:
+    /// ```cairo
+    /// fn ptr_to_i32(value: u64) -> i32 {
+    ///     let bits = value % 0x100000000;
+    ///     let felt: felt252 = bits.into();
+    ///     if bits < 0x80000000 {
+    ///         felt.try_into().unwrap()
+    ///     } else {
+    ///         (felt - 0x100000000).try_into().unwrap()
+    ///     }
+    /// }
+    /// ```
+    /// `inttoptr` zero-extends the bit pattern of the integer, or truncates it to 64 bits:
+    /// ```cairo
+    /// fn ptr_from_i128(value: i128) -> u64 {
+    ///     let felt: felt252 = value.into();
+    ///     let bits: u256 = if value < 0 {
+    ///         (felt + 0x100000000000000000000000000000000).into()
+    ///     } else {
+    ///         felt.into()
+    ///     };
+    ///     (bits % 0x10000000000000000).try_into().unwrap()
+    /// }
+    /// ```
+    pub fn function(&self) -> CairoFunction {
+        let int_type = format!("i{}", self.width);
+        let modulus = power_of_two(self.width);
+        let into = |value: Expr| Expr::method_call(value, "into", Vec::new());
+        let try_into =
+            |value: Expr| Expr::method_call(Expr::method_call(value, "try_into", Vec::new()), "unwrap", Vec::new());
+        let felt = |value: &str| Statement::Let {
+            pattern: Pattern::typed("felt", "felt252"),
+            value: into(Expr::variable(value)),
+        };
+        let mut body = Vec::new();
+        let (from, to) = if self.to_pointer {
+            let is_negative = Expr::binary(BinaryOperator::Lt, Expr::variable("value"), Expr::literal("0"));
+            let unsigned = |convert: &dyn Fn(Expr) -> Expr| {
+                Expr::if_else(
+                    is_negative.clone(),
+                    convert(Expr::binary(BinaryOperator::Add, Expr::variable("felt"), modulus.clone())),
+                    convert(Expr::variable("felt")),
+                )
+            };
+            body.push(felt("value"));
+            if self.width <= POINTER_WIDTH {
+                body.push(Statement::Tail(unsigned(&try_into)));
+            } else {
+                body.push(Statement::Let { pattern: Pattern::typed("bits", "u256"), value: unsigned(&into) });
+                let address = Expr::binary(BinaryOperator::Rem, Expr::variable("bits"), power_of_two(POINTER_WIDTH));
+                body.push(Statement::Tail(try_into(address)));
+            }
+            (int_type, "u64".to_owned())
+        } else {
+            assert!(self.width <= POINTER_WIDTH, "The wider integers hold the addresses as is");
+            let bits = match self.width {
+                POINTER_WIDTH => Expr::variable("value"),
+                _ => Expr::binary(BinaryOperator::Rem, Expr::variable("value"), modulus.clone()),
+            };
+            body.push(Statement::declare("bits", bits));
+            body.push(felt("bits"));
+            body.push(Statement::Tail(Expr::if_else(
+                Expr::binary(BinaryOperator::Lt, Expr::variable("bits"), power_of_two(self.width - 1)),
+                try_into(Expr::variable("felt")),
+                try_into(Expr::binary(BinaryOperator::Sub, Expr::variable("felt"), modulus)),
+            )));
+            ("u64".to_owned(), int_type)
+        };
+        let mut signature =
+            CairoFunctionSignature::new(self.name(), vec![CairoParameter::new("value".to_owned(), from)], to);
+        signature.public = false;
+        CairoFunction::new(signature, Block::new(body))
+    }
+}
+
+/// `2^exponent` as a hex literal.
+fn power_of_two(exponent: u32) -> Expr {
+    Expr::literal(format!("{:#x}{}", 1 << (exponent % 4), "0".repeat(exponent as usize / 4)))
+}
+
+/// Names of all the conversions that can be emitted in a module, the translated functions can't
+/// take them.
+pub fn pointer_conversion_names() -> impl Iterator<Item = String> {
+    (2..=128).flat_map(|width| [true, false].map(|to_pointer| PointerConversion { to_pointer, width }.name()))
+}
+
+/// The conversions the functions call, in the order of their names.
+pub fn pointer_conversions(functions: &[CairoFunction]) -> Vec<PointerConversion> {
+    let mut conversions = Vec::new();
+    for conversion in functions.iter().flat_map(|function| function.pointer_conversions.iter()) {
+        if !conversions.contains(conversion) {
+            conversions.push(*conversion);
+        }
+    }
+    conversions.sort_by_key(PointerConversion::name);
+    conversions
+}
+
+impl<'ctx> CairoFunctionBuilder<'ctx> {
+    /// Get the operand of a cast instruction.
+    fn cast_operand(instruction: &InstructionValue<'ctx>) -> BasicValueEnum<'ctx> {
+        instruction.get_operand(0).and_then(|operand| operand.left()).expect("Cast should have an operand")
+    }
+
+    /// Call a pointer conversion and record it in the function so it's emitted with the module.
+    fn convert_pointer(&mut self, conversion: PointerConversion, value: Expr) -> Expr {
+        if !self.function.pointer_conversions.contains(&conversion) {
+            self.function.pointer_conversions.push(conversion);
+        }
+        Expr::call(conversion.name(), vec![value])
+    }
+
+    /// Get the width of the integer operand or result of a pointer cast.
+    fn int_width(ty: BasicTypeEnum<'ctx>) -> u32 {
+        match ty {
+            BasicTypeEnum::IntType(ty) if ty.get_bit_width() > 1 => ty.get_bit_width(),
+            _ => panic!("Casts between pointers and {} aren't supported", ty.print_to_string()),
+        }
+    }
+
+    /// Translate a `ptrtoint` instruction, the address is converted to the integer with the same
+    /// bit pattern, see [PointerConversion]:
+    /// %addr = ptrtoint ptr %p to i64
+    /// becomes
+    /// let addr = ptr_to_i64(p);
+    pub fn process_ptr_to_int(&mut self, instruction: &InstructionValue<'ctx>, bb: &BasicBlock<'ctx>) {
+        let operand = Self::cast_operand(instruction);
+        let ty: BasicTypeEnum = instruction.get_type().try_into().expect("ptrtoint should return an integer");
+        let width = Self::int_width(ty);
+        let value = self.get_operand_expr(&operand, bb);
+        // The wider integers can hold any address.
+        let value = if width > POINTER_WIDTH {
+            Expr::method_call(value, "into", Vec::new())
+        } else {
+            self.convert_pointer(PointerConversion { to_pointer: false, width }, value)
+        };
+        let result_variable = self.get_result_variable(instruction);
+        self.push_statement(result_variable.set(value));
+    }
+
+    /// Translate an `inttoptr` instruction. The address is the bit pattern of the integer,
+    /// truncated to 64 bits, see [PointerConversion]:
+    /// %p = inttoptr i64 %addr to ptr
+    /// becomes
+    /// let p = ptr_from_i64(addr);
+    pub fn process_int_to_ptr(&mut self, instruction: &InstructionValue<'ctx>, bb: &BasicBlock<'ctx>) {
+        let operand = Self::cast_operand(instruction);
+        let width = Self::int_width(operand.get_type());
+        let value = self.get_operand_expr(&operand, bb);
+        let value = self.convert_pointer(PointerConversion { to_pointer: true, width }, value);
+        let result_variable = self.get_result_variable(instruction);
+        self.push_statement(result_variable.set(value));
+    }
+
+    /// Translate an `addrspacecast` or a `bitcast` between pointers. The address doesn't change so
+    /// it's a copy:
+    /// %q = addrspacecast ptr %p to ptr addrspace(1)
+    /// becomes
+    /// let q = p;
+    pub fn process_pointer_cast(&mut self, instruction: &InstructionValue<'ctx>, bb: &BasicBlock<'ctx>) {
+        let operand = Self::cast_operand(instruction);
+        let value = self.get_operand_expr(&operand, bb);
+        let result_variable = self.get_result_variable(instruction);
+        self.push_statement(result_variable.set(value));
+    }
+
+    /// Translate a `bitcast`. The casts between floats and integers change the representation (see
+    /// [CairoFunctionBuilder::process_float_bitcast]), the casts between pointers don't.
+    pub fn process_bitcast(&mut self, instruction: &InstructionValue<'ctx>, bb: &BasicBlock<'ctx>) {
+        let from = Self::cast_operand(instruction).get_type();
+        let to: BasicTypeEnum = instruction.get_type().try_into().expect("bitcast should return a value");
+        match (from, to) {
+            (BasicTypeEnum::PointerType(_), BasicTypeEnum::PointerType(_)) => {
+                self.process_pointer_cast(instruction, bb)
+            }
+            (BasicTypeEnum::FloatType(_), _) | (_, BasicTypeEnum::FloatType(_)) => {
+                self.process_float_bitcast(instruction, bb)
+            }
+            _ => panic!("bitcast from {} to {} isn't supported", from.print_to_string(), to.print_to_string()),
+        }
+    }
+}
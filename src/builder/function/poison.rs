@@ -0,0 +1,82 @@
+use inkwell::basic_block::BasicBlock;
+use inkwell::values::{AnyValue, BasicValueEnum, InstructionOpcode, InstructionValue};
+
+use super::CairoFunctionBuilder;
+use crate::ast::{Expr, Statement};
+
+impl<'ctx> CairoFunctionBuilder<'ctx> {
+    /// Is the value an `undef` or a `poison` constant. Those don't have an actual value so we
+    /// materialize them with the default value of their type. `poison` is a kind of `undef` for
+    /// LLVM.
+    pub fn is_undef_or_poison(value: &BasicValueEnum<'ctx>) -> bool {
+        match value {
+            BasicValueEnum::ArrayValue(value) => value.is_undef(),
+            BasicValueEnum::IntValue(value) => value.is_undef(),
+            BasicValueEnum::FloatValue(value) => value.is_undef(),
+            BasicValueEnum::PointerValue(value) => value.is_undef(),
+            BasicValueEnum::StructValue(value) => value.is_undef(),
+            BasicValueEnum::VectorValue(value) => value.is_undef(),
+            // The scalable vectors of the newer LLVM versions aren't supported.
+            #[allow(unreachable_patterns)]
+            _ => false,
+        }
+    }
+
+    /// Is the value `poison`, either because it's the `poison` constant or because it was computed
+    /// from a poison value.
+    pub fn is_poison(&self, value: &BasicValueEnum<'ctx>) -> bool {
+        self.poison_values.contains(value) || value.is_poison()
+    }
+
+    /// Poison propagates through instructions: if an operand is poison the result is poison as
+    /// well. `freeze` stops the propagation and `phi`/`select` only forward one of their operands
+    /// depending on the runtime so we don't track them.
+    pub fn track_poison(&mut self, instruction: &InstructionValue<'ctx>) {
+        if matches!(
+            instruction.get_opcode(),
+            InstructionOpcode::Freeze | InstructionOpcode::Phi | InstructionOpcode::Select
+        ) {
+            return;
+        }
+        // Instructions that don't produce a value (store, br, call void...) can't propagate anything.
+        let Ok(result) = BasicValueEnum::try_from(instruction.as_any_value_enum()) else {
+            return;
+        };
+        if instruction
+            .get_operands()
+            .flatten()
+            .filter_map(|operand| operand.left())
+            .any(|operand| self.is_poison(&operand))
+        {
+            self.poison_values.insert(result);
+        }
+    }
+
+    /// If the poison checks are enabled and the value is poison, get the assertion that makes the
+    /// program fail when it reaches this observable use.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - The value that is used.
+    /// * `usage` - How the value is used, will be in the panic message.
+    pub fn poison_check(&self, value: &BasicValueEnum<'ctx>, usage: &str) -> Option<Statement> {
+        (self.options.poison_checks && self.is_poison(value))
+            .then(|| Statement::Expr(Expr::panic(format!("Poison value reached an observable use: {usage}"))))
+    }
+
+    /// Translate a `freeze` instruction. If its operand is `undef` or `poison` it picks an
+    /// arbitrary value, we use the default one. Else it's a simple copy.
+    /// %y = freeze i32 %x
+    pub fn process_freeze(&mut self, instruction: &InstructionValue<'ctx>, bb: &BasicBlock<'ctx>) {
+        let operand = instruction.get_operand(0).unwrap().left().expect("freeze should have an operand");
+        let result_variable = self.get_result_variable(instruction);
+        let value = self.get_operand_expr(&operand, bb);
+        self.push_statement(result_variable.set(value));
+    }
+
+    /// Translate an `unreachable` instruction. If the program reaches it, the behavior is
+    /// undefined so we stop the execution.
+    pub fn process_unreachable(&mut self) {
+        self.push_statement(Statement::Expr(Expr::panic("Entered unreachable code")));
+    }
+}
@@ -1,10 +1,12 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::HashSet;
 
 use inkwell::basic_block::BasicBlock;
 use inkwell::values::{AnyValue, AsValueRef, BasicValueEnum, FunctionValue, InstructionOpcode, PhiValue};
 use petgraph::algo::{has_path_connecting, tarjan_scc};
 
-use super::CairoFunctionBuilder;
+use super::{CairoFunctionBuilder, PhiVariable};
+use crate::ast::{Expr, Statement};
+use crate::builder::types::cairo_default_value;
 
 impl<'ctx> CairoFunctionBuilder<'ctx> {
     /// Construct a graph of basic blocks and detects loops.  It will detect if bb1 jumps to bb2
@@ -57,7 +59,11 @@ impl<'ctx> CairoFunctionBuilder<'ctx> {
                         bblock_phis_inc.extend([inc1.0, inc2.0]);
                         bblock_phis.insert(unsafe { BasicValueEnum::new(instr.as_value_ref()) });
 
-                        self.phis_bblock.extend([inc1.1, inc2.1]);
+                        for incoming in [inc1.1, inc2.1] {
+                            if !self.phis_bblock.contains(&incoming) {
+                                self.phis_bblock.push(incoming);
+                            }
+                        }
                     }
                     _ => (),
                 };
@@ -66,19 +72,13 @@ impl<'ctx> CairoFunctionBuilder<'ctx> {
             // annoying phi hashmap with a suffix so we can create the correct variable in the previous scope
             // and update it.
             bblock_phis.intersection(&bblock_phis_inc).for_each(|annoying_phi| {
-                let phi_name = format!("{}_temp", self.get_name(annoying_phi.get_name()));
-                self.bblock_variables
-                    .entry(bblock)
-                    .and_modify(|hm| {
-                        hm.insert(*annoying_phi, phi_name.clone());
-                    })
-                    .or_insert_with(|| {
-                        let mut nhm = HashMap::new();
-                        nhm.insert(*annoying_phi, phi_name);
-                        nhm
-                    });
+                let outer = self.get_value_name(annoying_phi);
+                let temp = self.names.fresh(&format!("{outer}_temp"));
+                self.bblock_variables.entry(bblock).or_default().insert(*annoying_phi, PhiVariable { outer, temp });
             })
         }
+        // Keep the blocks in the order of the function, the booleans are declared in that order.
+        self.phis_bblock.sort_by_key(|bblock| self.node_id_from_name[bblock]);
         // Detect the strongly connected components (strongly connected basic blocks == loops)
         self.bb_loop = tarjan_scc(&self.bb_graph)
             .into_iter()
@@ -125,11 +125,11 @@ impl<'ctx> CairoFunctionBuilder<'ctx> {
         if self.return_block.is_some_and(|bblock| &bblock != bb) && *is_else
             || self.return_block.is_some_and(|bblock| &bblock == bb)
         {
-            self.push_body_line("}".to_string());
+            self.close_scope();
         }
         // If we were in a loop, close it
         if *is_loop {
-            self.push_body_line("};".to_string());
+            self.close_scope();
         }
     }
 
@@ -148,37 +148,42 @@ impl<'ctx> CairoFunctionBuilder<'ctx> {
                 if instruction.get_opcode() != InstructionOpcode::Br
                     && instruction.get_opcode() != InstructionOpcode::Return
                 {
+                    // Instructions that don't produce a value (store, call void, debug intrinsics...) don't need
+                    // a variable.
+                    let Ok(basic_val) = BasicValueEnum::try_from(instruction.as_any_value_enum()) else {
+                        continue;
+                    };
                     // Get the variable name, if it's unnamed generate a var{index} string.
-                    let res_name = self.get_name(instruction.get_name().unwrap_or_default());
+                    let res_name = self.get_value_name(&basic_val);
                     // Get the type of the variable because we'll add it to the definition to get more safety.
-                    let ty = instruction.get_type().print_to_string().to_string();
+                    let ty = basic_val.get_type();
                     // i1 are 1 bit integers meaning that they can only be {0, 1} they represent booleans. LLVM can
-                    // work with arbitrary sized integers but not cairo so convert it to bool.
-                    let val = if ty == "i1" { "false".to_owned() } else { format!("0_{ty}") };
-                    self.push_body_line(format!("let mut {} = {};", res_name, val));
+                    // work with arbitrary sized integers but not cairo so convert it to bool. Structs are
+                    // initialized field by field.
+                    let val = cairo_default_value(ty, &self.struct_names);
+                    self.push_statement(Statement::declare_mut(res_name.clone(), val));
                     // Save the variable for later use.
-                    let basic_val: BasicValueEnum = instruction.as_any_value_enum().try_into().unwrap();
                     self.variables.insert(basic_val, res_name);
                 }
             }
         }
         // If it's an if condition open it with the condition variable.
-        if let Some(cond) = self.if_blocks.get(bb) {
+        if let Some(cond) = self.if_blocks.get(bb).copied() {
             // Here we negate the condition because the return basic block is the first once which is annoying
             // for us as we want it to be the last piece of code in our function.
             // TODO(Lucas): Verify that the return block is always the first operand.
-            self.push_body_line(format!("if !{} {{", self.variables.get(cond).unwrap()));
+            self.open_if(Expr::not(self.get_operand_expr(&cond, bb)));
             // If we're at the return block close the previous scope and do nothing as all paths
             // lead here.
         } else if &self.return_block.unwrap() == bb && *is_else {
-            self.push_body_line("}".to_string())
+            self.close_scope()
             // If it's an else condition clause the previous if and open the else.
         } else if *is_else {
-            self.push_body_line("} else {".to_string());
+            self.open_else();
         }
         // If it's a loop open it.
         if *is_loop {
-            self.push_body_line("loop {".to_string());
+            self.open_loop();
         }
     }
 }
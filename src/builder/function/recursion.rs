@@ -0,0 +1,117 @@
+use super::CairoFunctionBuilder;
+use crate::ast::{Block, Expr, Pattern, Statement};
+
+impl<'ctx> CairoFunctionBuilder<'ctx> {
+    /// Turn the calls of the function to itself whose result is returned right away (tail calls
+    /// that LLVM didn't turn into a loop) into iterations of a loop around the body:
+    /// ```cairo
+    /// pub fn sum(n: i32, acc: i32) -> i32 {     pub fn sum(n: i32, acc: i32) -> i32 {
+    ///     ...                                       let mut n = n;
+    ///     let res = sum(n_1, acc_1);        =>      let mut acc = acc;
+    ///     return res;                               loop {
+    /// }                                                 ...
+    ///                                                   let n_next = n_1;
+    ///                                                   let acc_next = acc_1;
+    ///                                                   n = n_next;
+    ///                                                   acc = acc_next;
+    ///                                                   continue;
+    ///                                               };
+    ///                                           }
+    /// ```
+    /// The calls that are already in a loop of the translation are left as is as `continue` would
+    /// restart the inner loop. The new values go through temporaries because an argument can use
+    /// a parameter that was already updated.
+    pub fn convert_self_tail_calls(&mut self) {
+        let name = self.function.signature.name.clone();
+        let parameters =
+            self.function.signature.parameters.0.iter().map(|parameter| parameter.name.clone()).collect::<Vec<_>>();
+        let temporaries =
+            parameters.iter().map(|parameter| self.names.fresh(&format!("{parameter}_next"))).collect::<Vec<_>>();
+        let body = self.function.body.block_mut();
+        if !replace_tail_calls(body, &name, &parameters, &temporaries) {
+            return;
+        }
+        let mut statements = parameters
+            .iter()
+            .map(|parameter| Statement::declare_mut(parameter.clone(), Expr::variable(parameter.clone())))
+            .collect::<Vec<_>>();
+        statements.push(Statement::Loop(std::mem::take(body)));
+        *body = Block::new(statements);
+    }
+}
+
+/// If `statement` is a call to `function`, get its arguments and the variable its
+/// result is stored in (`None` for `f(a);` and `return f(a);`).
+fn self_call<'a>(statement: &'a Statement, function: &str) -> Option<(&'a [Expr], Option<&'a str>)> {
+    let (call, result) = match statement {
+        Statement::Let { pattern: Pattern::Variable { name, .. }, value } => (value, Some(name.as_str())),
+        Statement::Expr(value) | Statement::Return(Some(value)) => (value, None),
+        _ => return None,
+    };
+    match call {
+        Expr::Call { function: called, arguments } if called == function => Some((arguments, result)),
+        _ => None,
+    }
+}
+
+/// Replace the tail calls of `block` and its branches, returns whether there was one.
+fn replace_tail_calls(block: &mut Block, function: &str, parameters: &[String], temporaries: &[String]) -> bool {
+    let mut replaced = false;
+    let mut index = 0;
+    while index < block.statements.len() {
+        if let Some(end) = tail_call_end(block, index, function) {
+            let (arguments, _) =
+                self_call(&block.statements[index], function).expect("There is a tail call at this index");
+            let mut iteration = temporaries
+                .iter()
+                .zip(arguments.iter())
+                .map(|(temporary, argument)| Statement::declare(temporary.clone(), argument.clone()))
+                .collect::<Vec<_>>();
+            iteration.extend(
+                parameters.iter().zip(temporaries.iter()).map(|(parameter, temporary)| {
+                    Statement::assign(parameter.clone(), Expr::variable(temporary.clone()))
+                }),
+            );
+            iteration.push(Statement::Continue);
+            // Keep the origins between the call and the return for the source map.
+            let mut statements = block.statements[index + 1..end]
+                .iter()
+                .filter(|statement| matches!(statement, Statement::Origin(_)))
+                .cloned()
+                .collect::<Vec<_>>();
+            statements.extend(iteration);
+            index += statements.len();
+            block.statements.splice(index - statements.len()..=end, statements);
+            replaced = true;
+            continue;
+        }
+        if let Statement::If { then_block, else_block, .. } = &mut block.statements[index] {
+            replaced |= replace_tail_calls(then_block, function, parameters, temporaries);
+            if let Some(else_block) = else_block {
+                replaced |= replace_tail_calls(else_block, function, parameters, temporaries);
+            }
+        }
+        index += 1;
+    }
+    replaced
+}
+
+/// If the statement at `index` is a tail call to `function`, get the index of the statement that
+/// returns its result (the call itself for `return f(a);`).
+fn tail_call_end(block: &Block, index: usize, function: &str) -> Option<usize> {
+    let statement = &block.statements[index];
+    let (_, result) = self_call(statement, function)?;
+    if matches!(statement, Statement::Return(_)) {
+        return Some(index);
+    }
+    let (offset, next) = block.statements[index + 1..]
+        .iter()
+        .enumerate()
+        .find(|(_, statement)| !matches!(statement, Statement::Origin(_)))?;
+    let returns_result = match (next, result) {
+        (Statement::Return(Some(Expr::Variable(returned))), Some(result)) => returned == result,
+        (Statement::Return(None), None) => true,
+        _ => false,
+    };
+    returns_result.then_some(index + 1 + offset)
+}
@@ -1,6 +1,9 @@
+use inkwell::basic_block::BasicBlock;
 use inkwell::values::{FunctionValue, InstructionValue};
 
 use super::{CairoFunctionBuilder, CairoFunctionSignature, CairoParameter};
+use crate::ast::Statement;
+use crate::builder::types::cairo_type;
 
 impl<'ctx> CairoFunctionBuilder<'ctx> {
     /// Translate the LLVM function signature into a Cairo function signature.
@@ -8,52 +11,41 @@ impl<'ctx> CairoFunctionBuilder<'ctx> {
     /// # Arguments
     ///
     /// * `function` - The function we want to translate the signature of.
-    /// * `fn_id` - Is the index of the function in our file but it can be any number it's just in
-    ///   case the llvm function name is empty.
     ///
     /// # Returns
     ///
     /// * `String` - The cairo function signature in the form
     /// `pub fn <name>(<param1>: <type1>,<param2>: <type2>,) -> <return_type>`
-    pub fn process_function_signature(
-        &mut self,
-        function: &FunctionValue<'ctx>,
-        fn_id: usize,
-    ) -> CairoFunctionSignature {
-        // Get the function name and if it's empty call it "function{fn_id}"
-        let name = function.get_name().to_str().map(|val| val.to_string()).unwrap_or(format!("function{fn_id}"));
+    pub fn process_function_signature(&mut self, function: &FunctionValue<'ctx>) -> CairoFunctionSignature {
+        // Mangled rust symbols are demangled, see [CairoBuilder::translate_function_names].
+        let name = self.function_name(&function.get_name().to_string_lossy());
         let mut parameters = Vec::<CairoParameter>::with_capacity(function.count_params() as usize);
         // Extract each parameter and its type.
         function.get_param_iter().for_each(|param| {
-            let param_name = self.get_name(param.get_name());
-            let param_type = param.get_type().print_to_string().to_string();
+            let param_name = self.get_value_name(&param);
+            let param_type = cairo_type(param.get_type(), &self.struct_names);
             self.variables.insert(param, param_name.clone());
-            parameters.push(CairoParameter { name: param_name, ty: param_type });
+            parameters.push(CairoParameter::new(param_name, param_type));
         });
         // Get the return type of the function. If it's Some it means that the function returns a value else
         // it returns void.
-        let return_type = if let Some(ty) = function.get_type().get_return_type() {
-            ty.print_to_string().to_string()
-        } else {
-            "()".to_string()
+        let return_type = match function.get_type().get_return_type() {
+            Some(ty) => cairo_type(ty, &self.struct_names),
+            None => "()".to_string(),
         };
         CairoFunctionSignature::new(name, parameters, return_type)
     }
 
     /// Translate an LLVM Return instruction in cairo.
-    pub fn process_return(&mut self, instruction: &InstructionValue) -> String {
-        format!(
-            "return {};",
-            self.variables
-                .get(
-                &instruction
-                        .get_operand(0)
-                        .expect("Return opcode should have exactly 1 operand")
-                        .left()
-                        .expect("Return can only return a value hence left")
-                )
-                // TODO handle const
-                .expect("Return a variable")
-        )
+    pub fn process_return(&mut self, instruction: &InstructionValue<'ctx>, bb: &BasicBlock<'ctx>) {
+        // `ret void` doesn't have any operand.
+        let Some(value) = instruction.get_operand(0).and_then(|operand| operand.left()) else {
+            return self.push_statement(Statement::Return(None));
+        };
+        if let Some(check) = self.poison_check(&value, "return value") {
+            self.push_statement(check);
+        }
+        let value = self.get_operand_expr(&value, bb);
+        self.push_statement(Statement::Return(Some(value)));
     }
 }
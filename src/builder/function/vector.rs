@@ -0,0 +1,252 @@
+use inkwell::basic_block::BasicBlock;
+use inkwell::values::{AnyValue, BasicValueEnum, InstructionValue, VectorValue};
+
+use super::CairoFunctionBuilder;
+use crate::ast::{BinaryOperator, Expr, Pattern, Statement};
+use crate::builder::types::{cairo_default_value, cairo_type, null_pointer};
+
+impl<'ctx> CairoFunctionBuilder<'ctx> {
+    /// Extract the elements of a constant vector (`<i32 1, i32 2>`, `splat (i32 1)`,
+    /// `zeroinitializer`...).
+    pub(crate) fn extract_const_vector_elements(&self, val: VectorValue<'ctx>) -> Vec<Expr> {
+        (0..val.get_type().get_size())
+            .map(|index| {
+                let element = val.get_element_as_constant(index);
+                if Self::is_undef_or_poison(&element) {
+                    // We can't know what value the program would have used so use the default one.
+                    return cairo_default_value(element.get_type(), &self.struct_names);
+                }
+                match element {
+                    BasicValueEnum::IntValue(value) => Expr::literal(Self::extract_const_int_value(value)),
+                    BasicValueEnum::FloatValue(value) => self.float_literal(value),
+                    BasicValueEnum::PointerValue(pointer) if pointer.is_null() => null_pointer(),
+                    _ => panic!("Unsupported vector constant element {}", element.print_to_string()),
+                }
+            })
+            .collect()
+    }
+
+    /// Get the cairo representation of each element of a vector operand. Cairo doesn't have SIMD
+    /// so vectors are fixed-size arrays that we destructure to work on each element:
+    /// let [v_0, v_1, v_2, v_3] = v;
+    /// The destructuring statement is added to the function body.
+    fn get_vector_elements(&mut self, operand: &BasicValueEnum<'ctx>, bb: &BasicBlock<'ctx>) -> Vec<Expr> {
+        let vector = operand.into_vector_value();
+        if vector.is_const() {
+            return self.extract_const_vector_elements(vector);
+        }
+        let name = self
+            .bblock_variables
+            .get(bb)
+            .and_then(|annoying_phis| annoying_phis.get(operand))
+            .map(|variable| variable.temp.clone())
+            .unwrap_or_else(|| self.get_value_name(operand));
+        let elements = (0..vector.get_type().get_size())
+            .map(|index| self.names.fresh(&format!("{name}_{index}")))
+            .collect::<Vec<_>>();
+        self.push_statement(Statement::Let {
+            pattern: Pattern::FixedArray(elements.iter().cloned().map(Pattern::variable).collect()),
+            value: Expr::variable(name),
+        });
+        elements.into_iter().map(Expr::variable).collect()
+    }
+
+    /// Translate an element-wise binary operation on vectors.
+    /// %res = add <4 x i32> %a, %b
+    /// becomes
+    /// let [a_0, a_1, a_2, a_3] = a;
+    /// let [b_0, b_1, b_2, b_3] = b;
+    /// let res = [a_0 + b_0, a_1 + b_1, a_2 + b_2, a_3 + b_3];
+    pub fn process_vector_binary_op(
+        &mut self,
+        instruction: &InstructionValue<'ctx>,
+        operator: BinaryOperator,
+        bb: &BasicBlock<'ctx>,
+    ) {
+        let left = instruction.get_operand(0).unwrap().left().expect("Left operand should be a vector");
+        let right = instruction.get_operand(1).unwrap().left().expect("Right operand should be a vector");
+        let result_variable = self.get_result_variable(instruction);
+
+        let width = left.into_vector_value().get_type().get_element_type().into_int_type().get_bit_width();
+        let flags = Self::wrap_flags(instruction);
+        let left_elements = self.get_vector_elements(&left, bb);
+        let right_elements = self.get_vector_elements(&right, bb);
+        let result = left_elements
+            .into_iter()
+            .zip(right_elements)
+            .map(|(left, right)| self.int_arithmetic(operator, left, right, width, flags))
+            .collect::<Vec<_>>();
+        self.push_statement(result_variable.set(Expr::FixedArray(result)));
+    }
+
+    /// Translate an `extractelement` instruction.
+    /// %elem = extractelement <4 x i32> %v, i64 2
+    pub fn process_extract_element(&mut self, instruction: &InstructionValue<'ctx>, bb: &BasicBlock<'ctx>) {
+        let vector = instruction.get_operand(0).unwrap().left().expect("extractelement should have a vector");
+        let index = instruction.get_operand(1).unwrap().left().expect("extractelement should have an index");
+        let result_variable = self.get_result_variable(instruction);
+
+        let mut elements = self.get_vector_elements(&vector, bb);
+        let value = match index.into_int_value().get_zero_extended_constant() {
+            Some(index) => elements.swap_remove(index as usize),
+            // The index is only known at runtime so index the array.
+            // *[v_0, v_1].span()[index.try_into().unwrap()]
+            None => {
+                let index = Expr::method_call(
+                    Expr::method_call(self.get_int_operand_expr(&index, bb), "try_into", Vec::new()),
+                    "unwrap",
+                    Vec::new(),
+                );
+                let span = Expr::method_call(Expr::FixedArray(elements), "span", Vec::new());
+                Expr::Desnap(Box::new(Expr::Index { array: Box::new(span), index: Box::new(index) }))
+            }
+        };
+        self.push_statement(result_variable.set(value));
+    }
+
+    /// Translate an `insertelement` instruction. The element can be of any type.
+    /// %res = insertelement <4 x i32> %v, i32 %elem, i64 1
+    pub fn process_insert_element(&mut self, instruction: &InstructionValue<'ctx>, bb: &BasicBlock<'ctx>) {
+        let vector = instruction.get_operand(0).unwrap().left().expect("insertelement should have a vector");
+        let element = instruction.get_operand(1).unwrap().left().expect("insertelement should have an element");
+        let index = instruction.get_operand(2).unwrap().left().expect("insertelement should have an index");
+        let result_variable = self.get_result_variable(instruction);
+
+        let mut elements = self.get_vector_elements(&vector, bb);
+        let element = self.get_operand_expr(&element, bb);
+        match index.into_int_value().get_zero_extended_constant() {
+            Some(index) => elements[index as usize] = element,
+            // The index is only known at runtime so each element checks if it's the one to replace.
+            None => {
+                let index_ty = cairo_type(index.get_type(), &self.struct_names);
+                let index = self.get_int_operand_expr(&index, bb);
+                elements = elements
+                    .into_iter()
+                    .enumerate()
+                    .map(|(position, current)| {
+                        let is_index = Expr::binary(
+                            BinaryOperator::Eq,
+                            index.clone(),
+                            Expr::literal(format!("{position}_{index_ty}")),
+                        );
+                        Expr::if_else(is_index, element.clone(), current)
+                    })
+                    .collect();
+            }
+        }
+        self.push_statement(result_variable.set(Expr::FixedArray(elements)));
+    }
+
+    /// Translate a `shufflevector` instruction. The mask isn't an operand of the instruction, the
+    /// undefined elements of the mask (`poison`) are negative.
+    /// %res = shufflevector <4 x i32> %a, <4 x i32> %b, <4 x i32> <i32 0, i32 4, i32 1, i32 5>
+    pub fn process_shuffle_vector(&mut self, instruction: &InstructionValue<'ctx>, bb: &BasicBlock<'ctx>) {
+        let first = instruction.get_operand(0).unwrap().left().expect("shufflevector should have a first vector");
+        let second = instruction.get_operand(1).unwrap().left().expect("shufflevector should have a second vector");
+        let element_ty = instruction.get_type().into_vector_type().get_element_type();
+        let mask = instruction
+            .get_shuffle_vector_mask()
+            .expect("shufflevector should have a mask")
+            .into_iter()
+            .map(|index| usize::try_from(index).ok())
+            .collect::<Vec<_>>();
+
+        let result_variable = self.get_result_variable(instruction);
+        let mut elements = self.get_vector_elements(&first, bb);
+        elements.extend(self.get_vector_elements(&second, bb));
+        let result = mask
+            .into_iter()
+            .map(|index| {
+                index.map_or_else(
+                    || cairo_default_value(element_ty, &self.struct_names),
+                    |index| elements[index].clone(),
+                )
+            })
+            .collect::<Vec<_>>();
+        self.push_statement(result_variable.set(Expr::FixedArray(result)));
+    }
+
+    /// Translate a vector reduction intrinsic such as `llvm.vector.reduce.add.v4i32`.
+    ///
+    /// # Arguments
+    ///
+    /// * `instruction` - The call instruction.
+    /// * `reduction` - The reduction operation (`add`, `mul`, `and`, `or`, `xor`, `smax`,
+    ///   `umin`...).
+    pub fn process_vector_reduce(
+        &mut self,
+        instruction: &InstructionValue<'ctx>,
+        reduction: &str,
+        bb: &BasicBlock<'ctx>,
+    ) {
+        let vector = instruction.get_operand(0).unwrap().left().expect("Reduction should have a vector operand");
+        let element_ty = vector.into_vector_value().get_type().get_element_type();
+        let result_variable = self.get_result_variable(instruction);
+
+        let elements = self.get_vector_elements(&vector, bb);
+        let width = element_ty.into_int_type().get_bit_width();
+        // `i1` are booleans, `true` is the biggest unsigned value and the smallest signed one (-1).
+        let is_bool = width == 1;
+        let result = match reduction {
+            "umax" | "umin" if !is_bool => {
+                let zero = cairo_default_value(element_ty, &self.struct_names);
+                self.reduce_unsigned(elements, reduction == "umax", zero, &result_variable.name)
+            }
+            _ => elements
+                .into_iter()
+                .reduce(|acc, element| match reduction {
+                    // The reductions don't have `nuw`/`nsw` flags, they wrap around like LLVM.
+                    "add" => self.int_arithmetic(BinaryOperator::Add, acc, element, width, (false, false)),
+                    "mul" => self.int_arithmetic(BinaryOperator::Mul, acc, element, width, (false, false)),
+                    "and" => Expr::binary(BinaryOperator::BitAnd, acc, element),
+                    "or" => Expr::binary(BinaryOperator::BitOr, acc, element),
+                    "xor" => Expr::binary(BinaryOperator::BitXor, acc, element),
+                    "umax" | "smin" if is_bool => Expr::binary(BinaryOperator::Or, acc, element),
+                    "umin" | "smax" if is_bool => Expr::binary(BinaryOperator::And, acc, element),
+                    "smax" => Expr::call("core::cmp::max", vec![acc, element]),
+                    "smin" => Expr::call("core::cmp::min", vec![acc, element]),
+                    _ => panic!("Unsupported vector reduction {reduction}"),
+                })
+                .expect("Vectors have at least 1 element"),
+        };
+        self.push_statement(result_variable.set(result));
+    }
+
+    /// Reduce the elements with an unsigned max or min. The integers are signed in cairo so the
+    /// comparison of each step is stored in a variable, see [unsigned_less_than]:
+    /// let res_less = if v_0 < 0_i8 { v_1 < 0_i8 && v_0 < v_1 } else { v_1 < 0_i8 || v_0 < v_1 };
+    /// let res_acc = if res_less { v_1 } else { v_0 };
+    /// ...
+    fn reduce_unsigned(&mut self, elements: Vec<Expr>, max: bool, zero: Expr, result_name: &str) -> Expr {
+        let mut elements = elements.into_iter().peekable();
+        let mut acc = elements.next().expect("Vectors have at least 1 element");
+        while let Some(element) = elements.next() {
+            let less = self.names.fresh(&format!("{result_name}_less"));
+            let comparison = unsigned_less_than(acc.clone(), element.clone(), zero.clone());
+            self.push_statement(Statement::declare(less.clone(), comparison));
+            let (if_less, otherwise) = if max { (element, acc) } else { (acc, element) };
+            let value = Expr::if_else(Expr::variable(less), if_less, otherwise);
+            // The last step is the result.
+            if elements.peek().is_none() {
+                return value;
+            }
+            let partial = self.names.fresh(&format!("{result_name}_acc"));
+            self.push_statement(Statement::declare(partial.clone(), value));
+            acc = Expr::variable(partial);
+        }
+        acc
+    }
+}
+
+/// Compare two integers as the unsigned integers with the same bit pattern. The negative ones are
+/// the biggest, so when the signs differ the smallest is the non negative one:
+/// if a < 0 { b < 0 && a < b } else { b < 0 || a < b }
+fn unsigned_less_than(a: Expr, b: Expr, zero: Expr) -> Expr {
+    let is_negative = |value: &Expr| Expr::binary(BinaryOperator::Lt, value.clone(), zero.clone());
+    let signed_less = Expr::binary(BinaryOperator::Lt, a.clone(), b.clone());
+    Expr::if_else(
+        is_negative(&a),
+        Expr::binary(BinaryOperator::And, is_negative(&b), signed_less.clone()),
+        Expr::binary(BinaryOperator::Or, is_negative(&b), signed_less),
+    )
+}
@@ -1,17 +1,222 @@
 use std::collections::HashMap;
 use std::fmt::Display;
+use std::rc::Rc;
 
+use arithmetic::{arithmetic_helpers, helper_names};
+use contract::{exported_symbols, CairoContract, EntryPoint};
+use function::pointer::{pointer_conversion_names, pointer_conversions};
 use function::{CairoFunction, CairoFunctionBuilder};
+use inkwell::module::Module;
 use inkwell::values::FunctionValue;
+use naming::{function_identifier, NameScope};
+use options::{FloatMode, TranslationOptions};
+use types::{CairoStruct, CairoStructs, StructNames};
 
+use crate::ast::printer::{FormatOptions, Printer, ORIGIN_MARKER};
+use crate::ast::{Expr, Item, Statement};
+use crate::optimizer::optimize_function;
+use crate::source_map::{DebugMetadata, SourceMap};
+
+pub mod allocator;
+pub mod arithmetic;
+pub mod call_graph;
+pub mod contract;
 pub mod function;
+pub mod naming;
+pub mod options;
+pub mod passes;
+pub mod soft_float;
+pub mod types;
+pub mod visibility;
 
 /// Struct containing helpers to translate LLVM IR to cairo
 #[derive(Default)]
 pub struct CairoBuilder<'ctx> {
-    /// Cairo functions
-    pub(crate) cairo_fn_from_llvm: HashMap<FunctionValue<'ctx>, String>,
+    /// LLVM symbol => cairo name of the functions of the module (defined and declared).
+    pub(crate) function_names: Rc<HashMap<String, String>>,
     pub(crate) functions: CairoFunctions,
+    /// Cairo structs translated from the LLVM named struct types.
+    pub(crate) structs: CairoStructs,
+    /// LLVM struct name => cairo name of the structs, shared by all the functions.
+    pub(crate) struct_names: Rc<StructNames>,
+    pub(crate) options: TranslationOptions,
+    /// Debug info of the module we're translating.
+    pub(crate) debug_metadata: Rc<DebugMetadata>,
+    /// Data layout of the module, the sizes and offsets of the types in memory.
+    pub(crate) data_layout: Rc<String>,
+}
+
+/// Cairo code translated from an LLVM module.
+#[derive(Default, Clone, PartialEq, Debug)]
+pub struct CairoModule {
+    /// Paths imported with `use` at the top of the module.
+    pub(crate) uses: Vec<String>,
+    pub(crate) structs: CairoStructs,
+    pub(crate) functions: CairoFunctions,
+    /// The starknet contract the module is wrapped in, if any.
+    pub(crate) contract: Option<CairoContract>,
+    /// The float library emitted after the functions, if the module uses floats.
+    pub(crate) float_library: Option<FloatMode>,
+    /// Whether the allocator and the memory are emitted after the functions.
+    pub(crate) allocator: bool,
+}
+
+impl CairoModule {
+    pub fn new(structs: CairoStructs, functions: CairoFunctions) -> Self {
+        Self { uses: Vec::new(), structs, functions, contract: None, float_library: None, allocator: false }
+    }
+
+    pub fn contract(&self) -> Option<&CairoContract> {
+        self.contract.as_ref()
+    }
+
+    pub fn set_contract(&mut self, contract: CairoContract) {
+        self.contract = Some(contract)
+    }
+
+    pub fn float_library(&self) -> Option<FloatMode> {
+        self.float_library
+    }
+
+    pub fn set_float_library(&mut self, mode: FloatMode) {
+        self.float_library = Some(mode)
+    }
+
+    pub fn allocator(&self) -> bool {
+        self.allocator
+    }
+
+    pub fn set_allocator(&mut self) {
+        self.allocator = true
+    }
+
+    pub fn uses(&self) -> &[String] {
+        &self.uses
+    }
+
+    /// Import `path` in the module. Importing the same path twice does nothing.
+    pub fn push_use(&mut self, path: String) {
+        if !self.uses.contains(&path) {
+            self.uses.push(path)
+        }
+    }
+
+    pub fn structs(&self) -> &CairoStructs {
+        &self.structs
+    }
+
+    pub fn functions(&self) -> &[CairoFunction] {
+        self.functions.functions()
+    }
+
+    pub fn count_functions(&self) -> usize {
+        self.functions.count_functions()
+    }
+
+    /// Render the module with a custom layout. Items are separated by an empty line.
+    pub fn format(&self, options: FormatOptions) -> String {
+        self.render(&Printer::new(options))
+    }
+
+    /// Render the module and map each line of the generated code to the LLVM instruction it comes
+    /// from.
+    ///
+    /// # Arguments
+    ///
+    /// * `options` - The layout of the code. The origins are printed as comments if
+    ///   [FormatOptions::source_comments] is set.
+    /// * `file` - Name of the cairo file the code will be written to.
+    pub fn format_with_source_map(&self, options: FormatOptions, file: &str) -> (String, SourceMap) {
+        let marked = self.render(&Printer::with_origin_markers(options));
+        let mut origins = self.functions().iter().flat_map(|function| function.body.block().origins());
+        let mut source_map = SourceMap::new(file.to_owned());
+        let mut lines = Vec::new();
+        let mut current_origin = None;
+        // The functions of a contract are in the contract module.
+        let item_indentation = if self.contract.is_some() { options.indent_width } else { 0 };
+        for line in marked.lines() {
+            if line.trim_start() == ORIGIN_MARKER {
+                current_origin = origins.next();
+                if let Some(origin) = current_origin.filter(|_| options.source_comments) {
+                    lines.push(line.replace(ORIGIN_MARKER, &format!("// {origin}")));
+                }
+                continue;
+            }
+            // Lines that aren't indented more than the items are not in a function body (signatures,
+            // closing braces, structs...).
+            if line.len() - line.trim_start_matches(' ').len() <= item_indentation {
+                current_origin = None;
+            }
+            lines.push(line.to_owned());
+            if let Some(origin) = current_origin {
+                source_map.add_line(lines.len(), origin);
+            }
+        }
+        (lines.join("\n"), source_map)
+    }
+
+    /// The items of the module in the order they're printed. The helpers, the allocator and the
+    /// float library come after the translated functions.
+    pub fn items(&self) -> Vec<Item> {
+        let items = self
+            .uses
+            .iter()
+            .cloned()
+            .map(Item::Use)
+            .chain(self.structs.structs().iter().map(CairoStruct::item))
+            .chain(self.functions.functions().iter().cloned().map(Item::Function))
+            .chain(arithmetic_helpers(self.functions()).iter().map(|helper| Item::Function(helper.function())))
+            .chain(pointer_conversions(self.functions()).iter().map(|conversion| Item::Function(conversion.function())))
+            .chain(self.allocator.then(|| Item::Verbatim(allocator::library())))
+            .chain(self.float_library.map(|mode| Item::Verbatim(soft_float::library(mode))))
+            .collect();
+        match &self.contract {
+            Some(contract) => contract.items(items),
+            None => items,
+        }
+    }
+
+    fn render(&self, printer: &Printer) -> String {
+        printer.items(&self.items(), 0)
+    }
+}
+
+impl Display for CairoModule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.format(FormatOptions::default()))
+    }
+}
+
+/// Names of the items that can be emitted in a module next to the translated functions: the
+/// arithmetic helpers (`wrapping_add_i32`), the pointer conversions (`ptr_to_i64`), the ABI
+/// conversions of the contracts (`abi_to_i32`)
+/// and the items of the allocator (`rust_alloc`, `Memory`) and of the float library (`f32_add`).
+fn generated_item_names() -> Vec<String> {
+    let abi_conversions =
+        [8, 16, 32, 64, 128].into_iter().flat_map(|width| [format!("abi_to_i{width}"), format!("abi_from_i{width}")]);
+    let libraries =
+        [allocator::library(), soft_float::library(FloatMode::Ieee), soft_float::library(FloatMode::FixedPoint)];
+    // The items of the libraries are the lines that aren't indented: `pub fn f32_add(a: F32, b: F32) ->
+    // F32 {`, the traits generated for the impls are items too: `pub impl MemoryImpl of MemoryTrait
+    // {`.
+    let identifier = |code: &str| {
+        let name = code.split(|c: char| !c.is_ascii_alphanumeric() && c != '_').next().unwrap_or_default();
+        (!name.is_empty()).then(|| name.to_owned())
+    };
+    let library_items = libraries
+        .iter()
+        .flat_map(|code| code.lines())
+        .flat_map(|line| {
+            let line = line.strip_prefix("pub ").unwrap_or(line);
+            let item = ["fn ", "struct ", "trait ", "impl ", "const ", "mod "]
+                .iter()
+                .find_map(|keyword| line.strip_prefix(keyword))
+                .unwrap_or_default();
+            let generated_trait = line.strip_prefix("impl ").and_then(|item| item.split_once(" of ")).map(|(_, of)| of);
+            identifier(item).into_iter().chain(generated_trait.and_then(identifier))
+        })
+        .collect::<Vec<_>>();
+    helper_names().chain(pointer_conversion_names()).chain(abi_conversions).chain(library_items).collect()
 }
 
 #[derive(Default, Clone, PartialEq, Debug)]
@@ -34,22 +239,81 @@ impl CairoFunctions {
 
 impl Display for CairoFunctions {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.write_str(&self.0.iter().map(ToString::to_string).collect::<Vec<_>>().join("\n"))
+        f.write_str(&self.0.iter().map(ToString::to_string).collect::<Vec<_>>().join("\n\n"))
     }
 }
 
 impl<'ctx> CairoBuilder<'ctx> {
-    /// Translates an LLVM function to a cairo function and return it as a string.
-    pub fn translate_function(&mut self, func: &FunctionValue) -> CairoFunction {
+    pub fn new(options: TranslationOptions) -> Self {
+        Self { options, ..Default::default() }
+    }
+
+    /// Parse the debug info of the module so the source map can point to the original source code.
+    pub fn translate_debug_metadata(&mut self, module: &Module<'ctx>) {
+        self.debug_metadata = Rc::new(DebugMetadata::parse(&module.print_to_string().to_string()));
+    }
+
+    /// Save the data layout of the module, `getelementptr` and `alloca` need the sizes of the
+    /// types.
+    pub fn translate_data_layout(&mut self, module: &Module<'ctx>) {
+        self.data_layout = Rc::new(module.get_data_layout().as_str().to_string_lossy().into_owned());
+    }
+
+    /// Give a unique cairo name to all the functions of the module. Rust symbols are demangled:
+    /// `_ZN3fib3fib17h0123456789abcdefE` => `fib_fib`. Unnamed functions (`@0`) get
+    /// `function<index>`. The names of the items generated with the functions are reserved, see
+    /// [generated_item_names].
+    pub fn translate_function_names(&mut self, module: &Module<'ctx>) {
+        let mut scope = NameScope::default();
+        for name in generated_item_names() {
+            scope.fresh(&name);
+        }
+        let mut names = HashMap::new();
+        for (index, function) in module.get_functions().enumerate() {
+            let symbol = function.get_name().to_string_lossy().into_owned();
+            let name = if symbol.is_empty() { format!("function{index}") } else { function_identifier(&symbol) };
+            names.insert(symbol, scope.fresh(&name));
+        }
+        self.function_names = Rc::new(names);
+    }
+
+    /// Build the starknet contract the translated functions are wrapped in, if the options ask for
+    /// one. Must be called after all the functions are translated. Fails if an exported function
+    /// can't be an entry point.
+    pub fn translate_contract(&self, module: &Module<'ctx>) -> Result<Option<CairoContract>, String> {
+        let Some(options) = self.options.contract.as_ref() else {
+            return Ok(None);
+        };
+        let exported = exported_symbols(module, options, |symbol| self.function_names.get(symbol).cloned());
+        let mut contract = CairoContract::new(&options.name);
+        for cairo_function in self.functions.functions().iter().filter(|function| exported.contains(&function.symbol)) {
+            contract.push_entry_point(EntryPoint::new(&cairo_function.signature)?);
+        }
+        Ok(Some(contract))
+    }
+
+    /// Translates an LLVM function to a cairo function and return it as a string. Fails if some of
+    /// its instructions can't be translated.
+    pub fn translate_function(&mut self, func: &FunctionValue) -> Result<CairoFunction, String> {
         // Create a cairo function builder that will help us to build the function.
-        let mut function_builder = CairoFunctionBuilder::default();
+        let mut function_builder = CairoFunctionBuilder::new(
+            self.options.clone(),
+            self.debug_metadata.clone(),
+            self.function_names.clone(),
+            self.struct_names.clone(),
+            self.data_layout.clone(),
+        );
 
+        // Name the values after the source variables if there is debug info, then make all the names
+        // valid and unique.
+        function_builder.collect_debug_names(func);
+        function_builder.assign_names(func);
         function_builder.preprocess_function(func);
 
-        // Start by extracting the signature and translating it to cairo. (All functions will be public the
-        // original compiler already checked that there is no illegal call)
-        function_builder.function.signature =
-            function_builder.process_function_signature(func, self.cairo_fn_from_llvm.keys().count());
+        // Start by extracting the signature and translating it to cairo. (The function is public for now,
+        // the visibility is decided once we know which functions are entry points, see `apply_visibility`)
+        function_builder.function.symbol = func.get_name().to_string_lossy().into_owned();
+        function_builder.function.signature = function_builder.process_function_signature(func);
 
         // To understand that we need to know what the phi instruction does. It approximately does the
         // following:
@@ -60,9 +324,9 @@ impl<'ctx> CairoBuilder<'ctx> {
         // I recommend to read the official doc though https://llvm.org/docs/LangRef.html#phi-instruction
         // We'll create a mutable boolean that we'll initiate to false to know from which basic block we're
         // coming from as once again basic blocks don't exist in cairo.
-        for bb in function_builder.phis_bblock.iter() {
-            let code_line = format!("let mut is_from_{} = false;", function_builder.get_name(bb.get_name()),);
-            function_builder.function.body.push_line(code_line);
+        for bb in function_builder.phis_bblock.clone().iter() {
+            let is_from = function_builder.block_flag(bb);
+            function_builder.push_statement(Statement::declare_mut(is_from, Expr::literal("false")));
         }
         // get the first basic block and process it. As they are all linked together we'll process all of
         // them recursively by calling `process_basic_block` before jumping to another block. Each function
@@ -70,6 +334,21 @@ impl<'ctx> CairoBuilder<'ctx> {
         // there was no return instruction in the bb you'll need to jump to another bb at the end.
         // For more information read this https://llvm.org/doxygen/group__LLVMCCoreValueBasicBlock.html#details
         function_builder.process_basic_block(&func.get_first_basic_block().unwrap());
-        function_builder.function
+        // The scopes are closed when we reach specific blocks so make sure nothing is left open.
+        function_builder.close_all_scopes();
+        if !function_builder.errors.is_empty() {
+            return Err(format!(
+                "Failed to translate {}: {}",
+                func.get_name().to_string_lossy(),
+                function_builder.errors.join(", ")
+            ));
+        }
+        if self.options.tail_calls_to_loops {
+            function_builder.convert_self_tail_calls();
+        }
+        if self.options.optimize {
+            optimize_function(&mut function_builder.function);
+        }
+        Ok(function_builder.function)
     }
 }
@@ -0,0 +1,111 @@
+//! Turn LLVM names into valid and unique cairo identifiers.
+//!
+//! LLVM names can contain pretty much anything (`%"x.y$z"`, `%0`, `@_ZN4core3fmt5write17h..E`)
+//! while cairo identifiers are `[a-zA-Z_][a-zA-Z0-9_]*` and can't be keywords. Each function has
+//! its own [NameScope] so two values never end up with the same name, and the module has one for
+//! the function names.
+
+use std::collections::HashSet;
+
+/// Cairo keywords (strict and reserved), they can't be used as identifiers.
+const CAIRO_KEYWORDS: [&str; 47] = [
+    "as",
+    "break",
+    "const",
+    "continue",
+    "else",
+    "enum",
+    "extern",
+    "false",
+    "fn",
+    "for",
+    "if",
+    "impl",
+    "implicits",
+    "let",
+    "loop",
+    "match",
+    "mod",
+    "mut",
+    "nopanic",
+    "of",
+    "pub",
+    "ref",
+    "return",
+    "self",
+    "Self",
+    "struct",
+    "super",
+    "trait",
+    "true",
+    "type",
+    "use",
+    "while",
+    "abstract",
+    "crate",
+    "do",
+    "dyn",
+    "final",
+    "in",
+    "macro",
+    "move",
+    "static",
+    "try",
+    "typeof",
+    "unsafe",
+    "where",
+    "with",
+    "yield",
+];
+
+/// Turn any string into a valid cairo identifier. Invalid characters become `_`, identifiers
+/// can't start with a digit and keywords get a trailing `_`.
+/// `x.y` => `x_y`, `0` => `_0`, `match` => `match_`, `résumé` => `r_sum_`
+pub fn sanitize_identifier(name: &str) -> String {
+    let mut sanitized =
+        name.chars().map(|c| if c.is_ascii_alphanumeric() || c == '_' { c } else { '_' }).collect::<String>();
+    if sanitized.is_empty() {
+        sanitized.push_str("var");
+    } else if sanitized.starts_with(|c: char| c.is_ascii_digit()) {
+        sanitized.insert(0, '_');
+    }
+    if CAIRO_KEYWORDS.contains(&sanitized.as_str()) {
+        sanitized.push('_');
+    }
+    sanitized
+}
+
+/// Cairo name of a function symbol. Mangled rust symbols are demangled to their path without the
+/// hash: `_ZN4core3fmt5write17h0123456789abcdefE` => `core_fmt_write`.
+pub fn function_identifier(symbol: &str) -> String {
+    let path = match rustc_demangle::try_demangle(symbol) {
+        // The alternate format doesn't print the hash.
+        Ok(demangled) => format!("{demangled:#}"),
+        Err(_) => symbol.to_owned(),
+    };
+    // `<impl core::fmt::Debug for u8>::fmt` => `impl_core_fmt_Debug_for_u8_fmt`
+    let words = path.split(|c: char| !c.is_ascii_alphanumeric() && c != '_').filter(|word| !word.is_empty());
+    sanitize_identifier(&words.collect::<Vec<_>>().join("_"))
+}
+
+/// Names that are already used in a cairo scope (function or module).
+#[derive(Default, Clone, PartialEq, Debug)]
+pub struct NameScope {
+    used: HashSet<String>,
+}
+
+impl NameScope {
+    /// Get a valid identifier based on `name` that isn't used in the scope yet and reserve it.
+    /// If the name is taken a suffix is added: `sum`, `sum_1`, `sum_2`...
+    pub fn fresh(&mut self, name: &str) -> String {
+        let name = sanitize_identifier(name);
+        let mut candidate = name.clone();
+        let mut suffix = 0;
+        while self.used.contains(&candidate) {
+            suffix += 1;
+            candidate = format!("{name}_{suffix}");
+        }
+        self.used.insert(candidate.clone());
+        candidate
+    }
+}
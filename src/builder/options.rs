@@ -0,0 +1,182 @@
+use std::str::FromStr;
+
+/// Options that change how the LLVM IR is translated to cairo.
+#[derive(Default, Clone, PartialEq, Debug)]
+pub struct TranslationOptions {
+    /// `poison` values are materialized as a default value as we can't know what the program
+    /// would have done with them. If this is set, a runtime assertion is inserted wherever a
+    /// poison value reaches an observable use so the program fails instead of silently using the
+    /// default value. The uses are the returned values, the branch conditions and the arguments of
+    /// the calls translated as cairo calls.
+    pub poison_checks: bool,
+    /// LLVM passes to run on the module before translating it.
+    pub pre_optimization: PreOptimization,
+    /// Simplify the generated cairo code (constant and copy propagation, constant folding, dead
+    /// stores removal). See [crate::optimizer].
+    pub optimize: bool,
+    /// Wrap the translated functions in a starknet contract instead of emitting free functions.
+    pub contract: Option<ContractOptions>,
+    /// Functions that are the entry points of the module, by LLVM symbol or cairo name. They're
+    /// `pub` and the functions they don't call are removed. If it's empty the entry points are the
+    /// functions LLVM exports, see [crate::builder::visibility].
+    pub entrypoints: Vec<String>,
+    /// Turn the calls of a function to itself that are returned right away (`tail call`s LLVM
+    /// didn't turn into a loop) into a loop so deep recursions don't need a call stack.
+    pub tail_calls_to_loops: bool,
+    /// How `float` and `double` are implemented, see [crate::builder::soft_float].
+    pub float_mode: FloatMode,
+    /// How the integer `add` and `sub` are translated, see [ArithmeticMode].
+    pub arithmetic_mode: ArithmeticMode,
+}
+
+impl TranslationOptions {
+    /// Parse `arg` if it's one of the command line options of the translation that `llvm-to-cairo`
+    /// and `cargo cairo` share, taking its value from `args`. Returns `Ok(false)` when it isn't one
+    /// of them. `--contract` can come after `--export` so the exported functions are pushed to
+    /// `exports` and given to the contract by [TranslationOptions::set_exports] once all the
+    /// arguments are parsed.
+    pub fn parse_argument(
+        &mut self,
+        arg: &str,
+        args: &mut impl Iterator<Item = String>,
+        exports: &mut Vec<String>,
+    ) -> Result<bool, String> {
+        let mut value = |expected: &str| args.next().ok_or_else(|| format!("{arg} expects {expected}"));
+        match arg {
+            "--contract" => self.contract = Some(ContractOptions { name: value("a name")?, exports: Vec::new() }),
+            "--export" => exports.push(value("a function")?),
+            "--entrypoint" => self.entrypoints.push(value("a function")?),
+            "-O" | "--optimize" => self.optimize = true,
+            "--pre-opt" => self.pre_optimization = value("a preset")?.parse()?,
+            _ if arg.starts_with("--pre-opt=") => self.pre_optimization = arg["--pre-opt=".len()..].parse()?,
+            "--arithmetic" => self.arithmetic_mode = value("a mode")?.parse()?,
+            "--float" => self.float_mode = value("a mode")?.parse()?,
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    /// Give the functions of the `--export` options to the contract. Fails when there are some but
+    /// no `--contract`.
+    pub fn set_exports(&mut self, exports: Vec<String>) -> Result<(), String> {
+        match self.contract.as_mut() {
+            Some(contract) => contract.exports = exports,
+            None if !exports.is_empty() => return Err("--export needs a contract, use --contract".to_owned()),
+            None => (),
+        }
+        Ok(())
+    }
+}
+
+/// Cairo doesn't have floats so they're emulated by a library emitted with the translated code.
+#[derive(Default, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum FloatMode {
+    /// IEEE-754 floats on their bit patterns, the results are the same as on a native FPU.
+    #[default]
+    Ieee,
+    /// Signed fixed-point numbers with 32 fractional bits. Faster but not exact, there is no NaN or
+    /// infinity and the values must stay below 2^63.
+    FixedPoint,
+}
+
+impl FromStr for FloatMode {
+    type Err = String;
+
+    fn from_str(mode: &str) -> Result<Self, Self::Err> {
+        match mode {
+            "ieee" => Ok(FloatMode::Ieee),
+            "fixed" => Ok(FloatMode::FixedPoint),
+            _ => Err(format!("unknown float mode {mode}, expected ieee or fixed")),
+        }
+    }
+}
+
+/// LLVM integers wrap around on overflow but the cairo operators panic. The `nuw`/`nsw` flags of an
+/// instruction say that it doesn't wrap in the unsigned/signed sense, its result is poison if it
+/// does.
+#[derive(Default, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ArithmeticMode {
+    /// Use the cairo operators, they panic on signed overflow even when LLVM would wrap.
+    #[default]
+    Native,
+    /// Exact LLVM semantics, the results wrap around whatever the flags.
+    Wrapping,
+    /// Wrap around like LLVM but panic when an instruction overflows in the way its `nuw`/`nsw`
+    /// flags say it can't, like the overflow checks of a rust debug build.
+    Checked,
+}
+
+impl FromStr for ArithmeticMode {
+    type Err = String;
+
+    fn from_str(mode: &str) -> Result<Self, Self::Err> {
+        match mode {
+            "native" => Ok(ArithmeticMode::Native),
+            "wrapping" => Ok(ArithmeticMode::Wrapping),
+            "checked" => Ok(ArithmeticMode::Checked),
+            _ => Err(format!("unknown arithmetic mode {mode}, expected native, wrapping or checked")),
+        }
+    }
+}
+
+/// Starknet contract the translated functions are wrapped in.
+#[derive(Default, Clone, PartialEq, Eq, Debug)]
+pub struct ContractOptions {
+    /// Name of the contract module. The interface is named `I<Name>` in upper camel case.
+    pub name: String,
+    /// Functions exposed as entry points of the contract, by LLVM symbol or cairo name. The
+    /// functions annotated with [crate::builder::contract::EXTERNAL_ANNOTATION] or with the
+    /// [crate::builder::contract::EXTERNAL_ATTRIBUTE] attribute are exposed too.
+    pub exports: Vec<String>,
+}
+
+/// LLVM pass pipeline run before the translation. The translation expects the IR in the shape
+/// rustc produces with optimizations: values in registers instead of stack slots, loops with a
+/// single entry and exit, no `switch`... Running those passes gives the same shapes whatever the
+/// `opt-level` the IR was compiled with.
+#[derive(Default, Clone, PartialEq, Eq, Debug)]
+pub enum PreOptimization {
+    /// Translate the IR as is.
+    #[default]
+    None,
+    /// Only normalize the IR: promote the stack slots to registers, lower the switches and put the
+    /// loops in canonical and LCSSA form.
+    Minimal,
+    /// [PreOptimization::Minimal] plus the simplifications that shrink the IR (instcombine,
+    /// simplifycfg).
+    Aggressive,
+    /// Any pipeline in the syntax of `opt -passes`, e.g. `mem2reg,instcombine`. It's given as
+    /// `custom:<passes>` on the command line.
+    Custom(String),
+}
+
+impl PreOptimization {
+    /// Pipeline to give to the LLVM pass builder. `None` when there is nothing to run.
+    pub fn pipeline(&self) -> Option<&str> {
+        match self {
+            PreOptimization::None => None,
+            PreOptimization::Minimal => Some("mem2reg,lower-switch,loop-simplify,lcssa"),
+            // simplifycfg can turn branches back into switches so lower them after it.
+            PreOptimization::Aggressive => Some("mem2reg,instcombine,simplifycfg,lower-switch,loop-simplify,lcssa"),
+            PreOptimization::Custom(pipeline) => Some(pipeline),
+        }
+    }
+}
+
+impl FromStr for PreOptimization {
+    type Err = String;
+
+    fn from_str(preset: &str) -> Result<Self, Self::Err> {
+        match preset {
+            "none" => Ok(PreOptimization::None),
+            "minimal" => Ok(PreOptimization::Minimal),
+            "aggressive" => Ok(PreOptimization::Aggressive),
+            _ => match preset.strip_prefix("custom:") {
+                Some(pipeline) if !pipeline.is_empty() => Ok(PreOptimization::Custom(pipeline.to_owned())),
+                _ => Err(format!(
+                    "unknown pre-optimization preset {preset}, expected none, minimal, aggressive or custom:<passes>"
+                )),
+            },
+        }
+    }
+}
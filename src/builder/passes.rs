@@ -0,0 +1,23 @@
+//! Run LLVM passes on the module before translating it, see [PreOptimization].
+
+use inkwell::module::Module;
+use inkwell::passes::PassBuilderOptions;
+use inkwell::targets::{CodeModel, InitializationConfig, RelocMode, Target, TargetMachine};
+use inkwell::OptimizationLevel;
+
+use super::options::PreOptimization;
+
+/// Run the pipeline of `pre_optimization` on the module. The new pass manager needs a target
+/// machine, the passes we run don't depend on it so we use the host one.
+pub fn run_pre_optimization(module: &Module, pre_optimization: &PreOptimization) -> Result<(), String> {
+    let Some(pipeline) = pre_optimization.pipeline() else {
+        return Ok(());
+    };
+    Target::initialize_native(&InitializationConfig::default())?;
+    let triple = TargetMachine::get_default_triple();
+    let target = Target::from_triple(&triple).map_err(|error| error.to_string())?;
+    let machine = target
+        .create_target_machine(&triple, "generic", "", OptimizationLevel::None, RelocMode::Default, CodeModel::Default)
+        .ok_or_else(|| format!("Failed to create a target machine for {}", triple.as_str().to_string_lossy()))?;
+    module.run_passes(pipeline, &machine, PassBuilderOptions::create()).map_err(|error| error.to_string())
+}
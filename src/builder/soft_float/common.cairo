@@ -0,0 +1,198 @@
+// Integer helpers of the float library, shared by the IEEE and the fixed-point implementations.
+
+use core::integer::u128_sqrt;
+
+const U128_MAX: u128 = 0xffffffffffffffffffffffffffffffff;
+
+// Rounding modes of `round_to_integral`.
+const TRUNC: u8 = 0;
+const FLOOR: u8 = 1;
+const CEIL: u8 = 2;
+// Halfway cases away from zero, like `llvm.round`.
+const ROUND: u8 = 3;
+// Halfway cases to the even integer, like `llvm.roundeven` and `llvm.rint`.
+const ROUND_EVEN: u8 = 4;
+
+// 2^n for n < 128.
+fn pow2(n: u32) -> u128 {
+    let mut result = 1_u128;
+    let mut base = 2_u128;
+    let mut n = n;
+    while n != 0 {
+        if n % 2 == 1 {
+            result = result * base;
+        }
+        n = n / 2;
+        if n != 0 {
+            base = base * base;
+        }
+    }
+    result
+}
+
+// Number of bits needed to write `value`, 0 for 0.
+fn bit_length(value: u128) -> u32 {
+    if value == 0 {
+        return 0;
+    }
+    // Largest `low` such that 2^low <= value.
+    let mut low = 0_u32;
+    let mut high = 127_u32;
+    while low < high {
+        let middle = (low + high + 1) / 2;
+        if value >= pow2(middle) {
+            low = middle;
+        } else {
+            high = middle - 1;
+        }
+    }
+    low + 1
+}
+
+// `value / 2^shift`, the lowest bit is set if a non zero bit was shifted out (sticky bit) so the
+// result can still be rounded correctly.
+fn shift_right_sticky(value: u128, shift: u32) -> u128 {
+    if shift == 0 {
+        return value;
+    }
+    if shift >= 128 {
+        return if value == 0 {
+            0
+        } else {
+            1
+        };
+    }
+    let divisor = pow2(shift);
+    let quotient = value / divisor;
+    if value % divisor != 0 && quotient % 2 == 0 {
+        quotient + 1
+    } else {
+        quotient
+    }
+}
+
+// Whether the rounding `mode` moves a value away from zero given the discarded fraction
+// (compared to `half`) and the parity of the truncated value.
+fn rounds_up(mode: u8, negative: bool, fraction: u128, half: u128, odd: bool) -> bool {
+    if fraction == 0 {
+        return false;
+    }
+    if mode == FLOOR {
+        negative
+    } else if mode == CEIL {
+        !negative
+    } else if mode == ROUND {
+        fraction >= half
+    } else if mode == ROUND_EVEN {
+        fraction > half || (fraction == half && odd)
+    } else {
+        false
+    }
+}
+
+fn u128_to_i128(value: u128) -> i128 {
+    let felt: felt252 = value.into();
+    felt.try_into().unwrap()
+}
+
+fn i128_to_u128(value: i128) -> u128 {
+    let felt: felt252 = value.into();
+    felt.try_into().unwrap()
+}
+
+fn u128_to_i64(value: u128) -> i64 {
+    let felt: felt252 = value.into();
+    felt.try_into().unwrap()
+}
+
+fn i64_to_u128(value: i64) -> u128 {
+    let felt: felt252 = value.into();
+    felt.try_into().unwrap()
+}
+
+fn u32_to_i64(value: u32) -> i64 {
+    let felt: felt252 = value.into();
+    felt.try_into().unwrap()
+}
+
+fn i64_to_u32(value: i64) -> u32 {
+    let felt: felt252 = value.into();
+    felt.try_into().unwrap()
+}
+
+// |value|, it doesn't overflow for the smallest i128.
+fn magnitude(value: i128) -> u128 {
+    if value < 0 {
+        i128_to_u128(-(value + 1)) + 1
+    } else {
+        i128_to_u128(value)
+    }
+}
+
+// -magnitude for magnitude <= 2^127.
+fn negate(magnitude: u128) -> i128 {
+    if magnitude == 0 {
+        return 0;
+    }
+    -u128_to_i128(magnitude - 1) - 1
+}
+
+// Signed integer of `width` bits with the bit pattern of the unsigned `value`.
+fn unsigned_to_signed(value: u128, width: u32) -> i128 {
+    if value < pow2(width - 1) {
+        return u128_to_i128(value);
+    }
+    // value - 2^width
+    if width == 128 {
+        negate(U128_MAX - value + 1)
+    } else {
+        negate(pow2(width) - value)
+    }
+}
+
+// Unsigned integer with the bit pattern of the `width` bits signed `value`.
+fn signed_to_unsigned(value: i128, width: u32) -> u128 {
+    if value >= 0 {
+        return i128_to_u128(value);
+    }
+    // 2^width - |value|
+    if width == 128 {
+        U128_MAX - magnitude(value) + 1
+    } else {
+        pow2(width) - magnitude(value)
+    }
+}
+
+// Clamp an integer to the range of a `width` bits signed integer.
+fn saturate_signed(negative: bool, magnitude: u128, overflow: bool, width: u32) -> i128 {
+    let limit = pow2(width - 1);
+    if negative {
+        if overflow || magnitude > limit {
+            negate(limit)
+        } else {
+            negate(magnitude)
+        }
+    } else if overflow || magnitude >= limit {
+        u128_to_i128(limit - 1)
+    } else {
+        u128_to_i128(magnitude)
+    }
+}
+
+// Clamp an integer to the range of a `width` bits unsigned integer, the result has the bit
+// pattern of the unsigned value.
+fn saturate_unsigned(negative: bool, magnitude: u128, overflow: bool, width: u32) -> i128 {
+    if negative {
+        return 0;
+    }
+    let max = if width == 128 {
+        U128_MAX
+    } else {
+        pow2(width) - 1
+    };
+    if overflow || magnitude > max {
+        unsigned_to_signed(max, width)
+    } else {
+        unsigned_to_signed(magnitude, width)
+    }
+}
@@ -0,0 +1,82 @@
+// Fixed-point numbers with 32 fractional bits in an i128: `raw = value * 2^32`. The operations are
+// truncated toward zero, there is no NaN or infinity and the values must stay below 2^63 in
+// magnitude or the operations panic.
+
+// 1.0
+const ONE: u128 = 0x100000000;
+
+fn fixed_from_magnitude(negative: bool, magnitude: u128) -> i128 {
+    if negative {
+        negate(magnitude)
+    } else {
+        u128_to_i128(magnitude)
+    }
+}
+
+fn fixed_mul(a: i128, b: i128) -> i128 {
+    fixed_from_magnitude((a < 0) != (b < 0), magnitude(a) * magnitude(b) / ONE)
+}
+
+fn fixed_div(a: i128, b: i128) -> i128 {
+    if b == 0 {
+        panic!("float division by zero in fixed-point mode");
+    }
+    fixed_from_magnitude((a < 0) != (b < 0), magnitude(a) * ONE / magnitude(b))
+}
+
+fn fixed_rem(a: i128, b: i128) -> i128 {
+    if b == 0 {
+        panic!("float remainder by zero in fixed-point mode");
+    }
+    fixed_from_magnitude(a < 0, magnitude(a) % magnitude(b))
+}
+
+fn fixed_sqrt(a: i128) -> i128 {
+    if a < 0 {
+        panic!("square root of a negative float in fixed-point mode");
+    }
+    let root: u128 = u128_sqrt(i128_to_u128(a) * ONE).into();
+    u128_to_i128(root)
+}
+
+fn fixed_copysign(a: i128, sign: i128) -> i128 {
+    fixed_from_magnitude(sign < 0, magnitude(a))
+}
+
+// Round to an integral value in the given rounding mode, see the modes in the integer helpers.
+fn fixed_round(a: i128, mode: u8) -> i128 {
+    let magnitude = magnitude(a);
+    let fraction = magnitude % ONE;
+    let truncated = magnitude - fraction;
+    if rounds_up(mode, a < 0, fraction, ONE / 2, (truncated / ONE) % 2 == 1) {
+        fixed_from_magnitude(a < 0, truncated + ONE)
+    } else {
+        fixed_from_magnitude(a < 0, truncated)
+    }
+}
+
+fn fixed_to_signed(a: i128, width: u32) -> i128 {
+    saturate_signed(a < 0, magnitude(a) / ONE, false, width)
+}
+
+fn fixed_to_unsigned(a: i128, width: u32) -> i128 {
+    saturate_unsigned(a < 0, magnitude(a) / ONE, false, width)
+}
+
+fn fixed_from_signed(value: i128) -> i128 {
+    fixed_from_magnitude(value < 0, magnitude(value) * ONE)
+}
+
+// `value` has the bit pattern of a `width` bits unsigned integer.
+fn fixed_from_unsigned(value: i128, width: u32) -> i128 {
+    u128_to_i128(signed_to_unsigned(value, width) * ONE)
+}
+
+// Both float types have the same representation.
+pub fn f32_to_f64(x: F32) -> F64 {
+    F64 { raw: x.raw }
+}
+
+pub fn f64_to_f32(x: F64) -> F32 {
+    F32 { raw: x.raw }
+}
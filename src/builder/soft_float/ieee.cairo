@@ -0,0 +1,536 @@
+// IEEE-754 binary32 and binary64 arithmetic on the bit patterns. The results are rounded to
+// nearest, ties to even, and the NaNs produced are the canonical quiet NaN.
+
+// Bits kept below the last bit of the mantissa so the results can be rounded correctly.
+const GUARD_BITS: u32 = 3;
+
+// Kinds of values.
+const FINITE: u8 = 0;
+const ZERO: u8 = 1;
+const INFINITE: u8 = 2;
+const NAN: u8 = 3;
+
+#[derive(Copy, Drop)]
+struct FloatFormat {
+    mantissa_bits: u32,
+    exponent_bits: u32,
+}
+
+fn f32_format() -> FloatFormat {
+    FloatFormat { mantissa_bits: 23, exponent_bits: 8 }
+}
+
+fn f64_format() -> FloatFormat {
+    FloatFormat { mantissa_bits: 52, exponent_bits: 11 }
+}
+
+fn sign_bit(format: FloatFormat) -> u128 {
+    pow2(format.mantissa_bits + format.exponent_bits)
+}
+
+fn implicit_bit(format: FloatFormat) -> u128 {
+    pow2(format.mantissa_bits)
+}
+
+fn max_exponent(format: FloatFormat) -> u128 {
+    pow2(format.exponent_bits) - 1
+}
+
+fn bias(format: FloatFormat) -> i64 {
+    u128_to_i64(pow2(format.exponent_bits - 1) - 1)
+}
+
+// A float split in its parts. The value of a finite float is
+// `significand * 2^(exponent - bias - mantissa_bits)` and its significand is normalized: the
+// leading bit is the implicit bit even for the subnormals, whose exponent can go below 1.
+#[derive(Copy, Drop)]
+struct Unpacked {
+    negative: bool,
+    kind: u8,
+    exponent: i64,
+    significand: u128,
+}
+
+fn unpack(bits: u128, format: FloatFormat) -> Unpacked {
+    let negative = bits >= sign_bit(format);
+    let magnitude = bits % sign_bit(format);
+    let exponent = magnitude / implicit_bit(format);
+    let mantissa = magnitude % implicit_bit(format);
+    if exponent == max_exponent(format) {
+        let kind = if mantissa == 0 {
+            INFINITE
+        } else {
+            NAN
+        };
+        return Unpacked { negative, kind, exponent: 0, significand: 0 };
+    }
+    if exponent == 0 {
+        if mantissa == 0 {
+            return Unpacked { negative, kind: ZERO, exponent: 0, significand: 0 };
+        }
+        // Subnormal, move the leading bit to the implicit bit.
+        let shift = format.mantissa_bits + 1 - bit_length(mantissa);
+        return Unpacked {
+            negative, kind: FINITE, exponent: 1 - u32_to_i64(shift), significand: mantissa * pow2(shift),
+        };
+    }
+    Unpacked {
+        negative,
+        kind: FINITE,
+        exponent: u128_to_i64(exponent),
+        significand: mantissa + implicit_bit(format),
+    }
+}
+
+fn nan(format: FloatFormat) -> u128 {
+    max_exponent(format) * implicit_bit(format) + implicit_bit(format) / 2
+}
+
+fn infinity(negative: bool, format: FloatFormat) -> u128 {
+    zero(negative, format) + max_exponent(format) * implicit_bit(format)
+}
+
+fn zero(negative: bool, format: FloatFormat) -> u128 {
+    if negative {
+        sign_bit(format)
+    } else {
+        0
+    }
+}
+
+fn is_nan(bits: u128, format: FloatFormat) -> bool {
+    bits % sign_bit(format) > max_exponent(format) * implicit_bit(format)
+}
+
+// Round and pack the non zero value `significand * 2^(exponent - bias - mantissa_bits - GUARD_BITS)`.
+fn round_pack(negative: bool, exponent: i64, significand: u128, format: FloatFormat) -> u128 {
+    let top = format.mantissa_bits + GUARD_BITS + 1;
+    let length = bit_length(significand);
+    let mut exponent = exponent;
+    let mut significand = significand;
+    // Normalize the significand so it has `top` bits.
+    if length > top {
+        significand = shift_right_sticky(significand, length - top);
+        exponent += u32_to_i64(length - top);
+    } else if length < top {
+        significand = significand * pow2(top - length);
+        exponent -= u32_to_i64(top - length);
+    }
+    // Too small to be normal, the result is subnormal and loses precision.
+    if exponent < 1 {
+        let shift = if 1 - exponent > 200 {
+            200
+        } else {
+            i64_to_u32(1 - exponent)
+        };
+        significand = shift_right_sticky(significand, shift);
+        exponent = 1;
+    }
+    let guard = pow2(GUARD_BITS);
+    let mut mantissa = significand / guard;
+    if rounds_up(ROUND_EVEN, negative, significand % guard, guard / 2, mantissa % 2 == 1) {
+        mantissa += 1;
+    }
+    if mantissa == 2 * implicit_bit(format) {
+        mantissa = implicit_bit(format);
+        exponent += 1;
+    }
+    if exponent >= u128_to_i64(max_exponent(format)) {
+        return infinity(negative, format);
+    }
+    // The subnormals don't have the implicit bit and their exponent is 0.
+    let exponent = if mantissa >= implicit_bit(format) {
+        i64_to_u128(exponent)
+    } else {
+        0
+    };
+    zero(negative, format) + exponent * implicit_bit(format) + mantissa % implicit_bit(format)
+}
+
+fn float_neg(bits: u128, format: FloatFormat) -> u128 {
+    if bits >= sign_bit(format) {
+        bits - sign_bit(format)
+    } else {
+        bits + sign_bit(format)
+    }
+}
+
+fn float_abs(bits: u128, format: FloatFormat) -> u128 {
+    bits % sign_bit(format)
+}
+
+fn float_copysign(bits: u128, sign: u128, format: FloatFormat) -> u128 {
+    zero(sign >= sign_bit(format), format) + bits % sign_bit(format)
+}
+
+fn float_add(a: u128, b: u128, format: FloatFormat) -> u128 {
+    let x = unpack(a, format);
+    let y = unpack(b, format);
+    if x.kind == NAN || y.kind == NAN {
+        return nan(format);
+    }
+    if x.kind == INFINITE {
+        if y.kind == INFINITE && x.negative != y.negative {
+            return nan(format);
+        }
+        return a;
+    }
+    if y.kind == INFINITE {
+        return b;
+    }
+    if x.kind == ZERO {
+        if y.kind == ZERO {
+            return zero(x.negative && y.negative, format);
+        }
+        return b;
+    }
+    if y.kind == ZERO {
+        return a;
+    }
+    let (big, small) = if x.exponent > y.exponent
+        || (x.exponent == y.exponent && x.significand >= y.significand) {
+        (x, y)
+    } else {
+        (y, x)
+    };
+    let difference = big.exponent - small.exponent;
+    let shift = if difference > 200 {
+        200
+    } else {
+        i64_to_u32(difference)
+    };
+    let big_significand = big.significand * pow2(GUARD_BITS);
+    let small_significand = shift_right_sticky(small.significand * pow2(GUARD_BITS), shift);
+    if big.negative == small.negative {
+        return round_pack(big.negative, big.exponent, big_significand + small_significand, format);
+    }
+    if big_significand == small_significand {
+        return zero(false, format);
+    }
+    round_pack(big.negative, big.exponent, big_significand - small_significand, format)
+}
+
+fn float_sub(a: u128, b: u128, format: FloatFormat) -> u128 {
+    float_add(a, float_neg(b, format), format)
+}
+
+fn float_mul(a: u128, b: u128, format: FloatFormat) -> u128 {
+    let x = unpack(a, format);
+    let y = unpack(b, format);
+    let negative = x.negative != y.negative;
+    if x.kind == NAN || y.kind == NAN {
+        return nan(format);
+    }
+    if x.kind == INFINITE || y.kind == INFINITE {
+        if x.kind == ZERO || y.kind == ZERO {
+            return nan(format);
+        }
+        return infinity(negative, format);
+    }
+    if x.kind == ZERO || y.kind == ZERO {
+        return zero(negative, format);
+    }
+    let exponent = x.exponent + y.exponent - bias(format) - u32_to_i64(format.mantissa_bits);
+    round_pack(negative, exponent, x.significand * y.significand * pow2(GUARD_BITS), format)
+}
+
+fn float_div(a: u128, b: u128, format: FloatFormat) -> u128 {
+    let x = unpack(a, format);
+    let y = unpack(b, format);
+    let negative = x.negative != y.negative;
+    if x.kind == NAN || y.kind == NAN || (x.kind == INFINITE && y.kind == INFINITE)
+        || (x.kind == ZERO && y.kind == ZERO) {
+        return nan(format);
+    }
+    if x.kind == INFINITE || y.kind == ZERO {
+        return infinity(negative, format);
+    }
+    if x.kind == ZERO || y.kind == INFINITE {
+        return zero(negative, format);
+    }
+    // Enough bits in the quotient for the guard bits, the remainder is the sticky bit.
+    let shift = format.mantissa_bits + GUARD_BITS + 2;
+    let dividend = x.significand * pow2(shift);
+    let mut quotient = dividend / y.significand;
+    if dividend % y.significand != 0 && quotient % 2 == 0 {
+        quotient += 1;
+    }
+    round_pack(negative, x.exponent - y.exponent + bias(format) - 2, quotient, format)
+}
+
+// Remainder of the division truncated toward zero, like C's `fmod`. It's always exact.
+fn float_rem(a: u128, b: u128, format: FloatFormat) -> u128 {
+    let x = unpack(a, format);
+    let y = unpack(b, format);
+    if x.kind == NAN || y.kind == NAN || x.kind == INFINITE || y.kind == ZERO {
+        return nan(format);
+    }
+    if x.kind == ZERO || y.kind == INFINITE {
+        return a;
+    }
+    if x.exponent < y.exponent || (x.exponent == y.exponent && x.significand < y.significand) {
+        return a;
+    }
+    // Long division of the significands, 64 bits at a time so it doesn't overflow.
+    let mut remainder = x.significand % y.significand;
+    let mut difference = i64_to_u32(x.exponent - y.exponent);
+    while difference != 0 {
+        let step = if difference > 64 {
+            64
+        } else {
+            difference
+        };
+        remainder = remainder * pow2(step) % y.significand;
+        difference -= step;
+    }
+    if remainder == 0 {
+        return zero(x.negative, format);
+    }
+    round_pack(x.negative, y.exponent, remainder * pow2(GUARD_BITS), format)
+}
+
+fn float_sqrt(a: u128, format: FloatFormat) -> u128 {
+    let x = unpack(a, format);
+    if x.kind == NAN || (x.negative && x.kind != ZERO) {
+        return nan(format);
+    }
+    if x.kind != FINITE {
+        // +inf, +0 and -0 are their own square root.
+        return a;
+    }
+    // x = significand * 2^power with an even power so its square root is easy to get. The offset
+    // keeps the power positive while we halve it.
+    let offset = 0x10000_i64;
+    let mut power = x.exponent - bias(format) - u32_to_i64(format.mantissa_bits) + offset;
+    let mut significand = x.significand;
+    if i64_to_u128(power) % 2 == 1 {
+        significand = significand * 2;
+        power -= 1;
+    }
+    // Scale the significand so the root has enough bits for the guard bits.
+    let scale = format.mantissa_bits + 2 * GUARD_BITS + 4 + format.mantissa_bits % 2;
+    let scaled = significand * pow2(scale);
+    let root: u128 = u128_sqrt(scaled).into();
+    // One more bit for the sticky bit.
+    let sticky = if root * root != scaled {
+        1
+    } else {
+        0
+    };
+    let half_power = u128_to_i64(i64_to_u128(power - u32_to_i64(scale)) / 2) - offset / 2;
+    let exponent = half_power - 1 + bias(format) + u32_to_i64(format.mantissa_bits + GUARD_BITS);
+    round_pack(false, exponent, root * 2 + sticky, format)
+}
+
+// `a * b + c`. It's not fused: the product is rounded before the addition.
+fn float_fma(a: u128, b: u128, c: u128, format: FloatFormat) -> u128 {
+    float_add(float_mul(a, b, format), c, format)
+}
+
+// Key that orders the floats like their values, +0 and -0 have the same key.
+fn order_key(bits: u128, format: FloatFormat) -> i128 {
+    let magnitude = bits % sign_bit(format);
+    if bits >= sign_bit(format) {
+        negate(magnitude)
+    } else {
+        u128_to_i128(magnitude)
+    }
+}
+
+fn float_unordered(a: u128, b: u128, format: FloatFormat) -> bool {
+    is_nan(a, format) || is_nan(b, format)
+}
+
+fn float_eq(a: u128, b: u128, format: FloatFormat) -> bool {
+    !float_unordered(a, b, format) && order_key(a, format) == order_key(b, format)
+}
+
+fn float_lt(a: u128, b: u128, format: FloatFormat) -> bool {
+    !float_unordered(a, b, format) && order_key(a, format) < order_key(b, format)
+}
+
+fn float_le(a: u128, b: u128, format: FloatFormat) -> bool {
+    !float_unordered(a, b, format) && order_key(a, format) <= order_key(b, format)
+}
+
+// The smallest value, NaN is ignored (`llvm.minnum`).
+fn float_minnum(a: u128, b: u128, format: FloatFormat) -> u128 {
+    if is_nan(a, format) {
+        return if is_nan(b, format) {
+            nan(format)
+        } else {
+            b
+        };
+    }
+    if is_nan(b, format) || !float_lt(b, a, format) {
+        a
+    } else {
+        b
+    }
+}
+
+// The largest value, NaN is ignored (`llvm.maxnum`).
+fn float_maxnum(a: u128, b: u128, format: FloatFormat) -> u128 {
+    if is_nan(a, format) {
+        return if is_nan(b, format) {
+            nan(format)
+        } else {
+            b
+        };
+    }
+    if is_nan(b, format) || !float_lt(a, b, format) {
+        a
+    } else {
+        b
+    }
+}
+
+// The smallest value, NaN wins and -0 is smaller than +0 (`llvm.minimum`).
+fn float_minimum(a: u128, b: u128, format: FloatFormat) -> u128 {
+    if float_unordered(a, b, format) {
+        return nan(format);
+    }
+    if float_lt(a, b, format) || (float_eq(a, b, format) && a >= sign_bit(format)) {
+        a
+    } else {
+        b
+    }
+}
+
+// The largest value, NaN wins and +0 is larger than -0 (`llvm.maximum`).
+fn float_maximum(a: u128, b: u128, format: FloatFormat) -> u128 {
+    if float_unordered(a, b, format) {
+        return nan(format);
+    }
+    if float_lt(b, a, format) || (float_eq(a, b, format) && a < sign_bit(format)) {
+        a
+    } else {
+        b
+    }
+}
+
+// Round to an integral value in the given rounding mode, see the modes in the integer helpers.
+fn round_to_integral(bits: u128, format: FloatFormat, mode: u8) -> u128 {
+    let x = unpack(bits, format);
+    if x.kind == NAN {
+        return nan(format);
+    }
+    if x.kind != FINITE {
+        return bits;
+    }
+    let sign = zero(x.negative, format);
+    let magnitude = bits % sign_bit(format);
+    let exponent = x.exponent - bias(format);
+    if exponent >= u32_to_i64(format.mantissa_bits) {
+        return bits;
+    }
+    let one = i64_to_u128(bias(format)) * implicit_bit(format);
+    if exponent < 0 {
+        // |x| < 1, the result is 0 or 1 with the sign of x. 0.5 has the exponent of 1 minus one.
+        let half = one - implicit_bit(format);
+        let fraction = if mode == ROUND || mode == ROUND_EVEN {
+            magnitude
+        } else {
+            1
+        };
+        return if rounds_up(mode, x.negative, fraction, half, false) {
+            sign + one
+        } else {
+            sign
+        };
+    }
+    // The lowest `mantissa_bits - exponent` bits of the mantissa are the fraction.
+    let unit = pow2(format.mantissa_bits - i64_to_u32(exponent));
+    let fraction = magnitude % unit;
+    let truncated = magnitude - fraction;
+    // Adding a unit to the mantissa carries into the exponent when needed.
+    if rounds_up(mode, x.negative, fraction, unit / 2, (truncated / unit) % 2 == 1) {
+        sign + truncated + unit
+    } else {
+        sign + truncated
+    }
+}
+
+// Integer part of a finite value, and whether it doesn't fit in 128 bits.
+fn integer_part(x: Unpacked, format: FloatFormat) -> (u128, bool) {
+    let shift = x.exponent - bias(format) - u32_to_i64(format.mantissa_bits);
+    if shift >= 0 {
+        if shift >= 128 || u32_to_i64(bit_length(x.significand)) + shift > 128 {
+            return (0, true);
+        }
+        return (x.significand * pow2(i64_to_u32(shift)), false);
+    }
+    if -shift >= 128 {
+        return (0, false);
+    }
+    (x.significand / pow2(i64_to_u32(-shift)), false)
+}
+
+// Convert to a `width` bits signed integer, truncating toward zero. Out of range values saturate and
+// NaN is 0 like `llvm.fptosi.sat`.
+fn float_to_signed(bits: u128, format: FloatFormat, width: u32) -> i128 {
+    let x = unpack(bits, format);
+    if x.kind == NAN || x.kind == ZERO {
+        return 0;
+    }
+    if x.kind == INFINITE {
+        return saturate_signed(x.negative, 0, true, width);
+    }
+    let (magnitude, overflow) = integer_part(x, format);
+    saturate_signed(x.negative, magnitude, overflow, width)
+}
+
+// Convert to a `width` bits unsigned integer, see `float_to_signed`. The result has the bit pattern
+// of the unsigned integer.
+fn float_to_unsigned(bits: u128, format: FloatFormat, width: u32) -> i128 {
+    let x = unpack(bits, format);
+    if x.kind == NAN || x.kind == ZERO {
+        return 0;
+    }
+    if x.kind == INFINITE {
+        return saturate_unsigned(x.negative, 0, true, width);
+    }
+    let (magnitude, overflow) = integer_part(x, format);
+    saturate_unsigned(x.negative && magnitude != 0, magnitude, overflow, width)
+}
+
+fn float_from_magnitude(negative: bool, magnitude: u128, format: FloatFormat) -> u128 {
+    if magnitude == 0 {
+        return zero(false, format);
+    }
+    let exponent = bias(format) + u32_to_i64(format.mantissa_bits + GUARD_BITS);
+    round_pack(negative, exponent, magnitude, format)
+}
+
+fn float_from_signed(value: i128, format: FloatFormat) -> u128 {
+    float_from_magnitude(value < 0, magnitude(value), format)
+}
+
+// `value` has the bit pattern of a `width` bits unsigned integer.
+fn float_from_unsigned(value: i128, width: u32, format: FloatFormat) -> u128 {
+    float_from_magnitude(false, signed_to_unsigned(value, width), format)
+}
+
+fn float_convert(bits: u128, from: FloatFormat, to: FloatFormat) -> u128 {
+    let x = unpack(bits, from);
+    if x.kind == NAN {
+        return nan(to);
+    }
+    if x.kind == INFINITE {
+        return infinity(x.negative, to);
+    }
+    if x.kind == ZERO {
+        return zero(x.negative, to);
+    }
+    let exponent = x.exponent - bias(from) - u32_to_i64(from.mantissa_bits) + bias(to)
+        + u32_to_i64(to.mantissa_bits);
+    round_pack(x.negative, exponent, x.significand * pow2(GUARD_BITS), to)
+}
+
+pub fn f32_to_f64(x: F32) -> F64 {
+    f64_wrap(float_convert(x.bits.into(), f32_format(), f64_format()))
+}
+
+pub fn f64_to_f32(x: F64) -> F32 {
+    f32_wrap(float_convert(x.bits.into(), f64_format(), f32_format()))
+}
@@ -0,0 +1,108 @@
+//! Floats in cairo. Cairo doesn't have floats so `float` and `double` become the `F32` and `F64`
+//! structs and the float instructions become calls to the functions of a library that is emitted
+//! with the translated code:
+//! ```cairo
+//! let sum = f32_add(a, F32 { bits: 0x3fc00000_u32 });
+//! let is_less = f32_lt(sum, b);
+//! ```
+//! The library depends on [FloatMode]:
+//! * [FloatMode::Ieee] implements IEEE-754 on the bit patterns of the floats: add, sub, mul, div,
+//!   rem and sqrt are correctly rounded (to nearest, ties to even) so they give the same results as
+//!   a native FPU, the comparisons follow the NaN semantics of `fcmp`.
+//! * [FloatMode::FixedPoint] implements the same functions on fixed-point numbers, it's much
+//!   cheaper but the results are approximations.
+//!
+//! The code of the library is in the `.cairo` files next to this module, the functions of each
+//! float type are generated from a template where `$F` is the struct (`F32`), `$f` the prefix of
+//! the functions (`f32`) and `$w` the bit width (`32`).
+
+use inkwell::module::Module;
+use inkwell::types::BasicTypeEnum;
+
+use super::options::FloatMode;
+
+/// Name of the module the library is in when the translation is a Scarb package.
+pub const MODULE_NAME: &str = "soft_float";
+
+/// Integer helpers shared by both modes.
+const COMMON: &str = include_str!("common.cairo");
+const IEEE: &str = include_str!("ieee.cairo");
+const IEEE_TYPE: &str = include_str!("ieee_type.cairo.in");
+const FIXED: &str = include_str!("fixed.cairo");
+const FIXED_TYPE: &str = include_str!("fixed_type.cairo.in");
+
+/// Bit widths of the supported float types: `float` and `double`.
+pub const FLOAT_WIDTHS: [u32; 2] = [32, 64];
+
+/// Cairo code of the library for `mode`, indented with 4 spaces.
+pub fn library(mode: FloatMode) -> String {
+    let (implementation, template) = match mode {
+        FloatMode::Ieee => (IEEE, IEEE_TYPE),
+        FloatMode::FixedPoint => (FIXED, FIXED_TYPE),
+    };
+    let types = FLOAT_WIDTHS.iter().map(|width| {
+        template
+            .replace("$F", &format!("F{width}"))
+            .replace("$f", &format!("f{width}"))
+            .replace("$w", &width.to_string())
+    });
+    [COMMON, implementation]
+        .into_iter()
+        .map(str::to_owned)
+        .chain(types)
+        .map(|code| code.trim().to_owned())
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+/// Is `name` a public function of the library (`f32_add`, `f64_to_f32`...). They're the same in
+/// both modes.
+pub fn is_library_function(name: &str) -> bool {
+    let Some((prefix, operation)) = name.split_once('_') else {
+        return false;
+    };
+    if !FLOAT_WIDTHS.iter().any(|width| prefix == format!("f{width}")) {
+        return false;
+    }
+    // The conversions between the float types aren't in the template.
+    let conversion = operation.strip_prefix("to_f").is_some_and(|width| {
+        FLOAT_WIDTHS.iter().any(|other| width == other.to_string() && prefix != format!("f{other}"))
+    });
+    conversion
+        || IEEE_TYPE
+            .lines()
+            .filter_map(|line| line.strip_prefix("pub fn $f_"))
+            .any(|function| function.split('(').next() == Some(operation))
+}
+
+/// Cairo name of the struct of the `width` bits floats.
+pub fn float_struct(width: u32) -> String {
+    format!("F{width}")
+}
+
+/// Does the module use floats? If so the library has to be emitted with it.
+pub fn uses_floats(module: &Module) -> bool {
+    module.get_functions().filter(|function| function.count_basic_blocks() > 0).any(|function| {
+        function.get_type().get_return_type().is_some_and(is_float_type)
+            || function.get_param_iter().any(|param| is_float_type(param.get_type()))
+            || function.get_basic_block_iter().flat_map(|bb| bb.get_instructions()).any(|instruction| {
+                // `fcmp` returns an `i1` and `fptosi` an integer so look at the operands too.
+                BasicTypeEnum::try_from(instruction.get_type()).is_ok_and(is_float_type)
+                    || (0..instruction.get_num_operands()).any(|index| {
+                        instruction
+                            .get_operand(index)
+                            .and_then(|operand| operand.left())
+                            .is_some_and(|operand| is_float_type(operand.get_type()))
+                    })
+            })
+    })
+}
+
+/// `float`, `double` or a vector of them.
+fn is_float_type(ty: BasicTypeEnum) -> bool {
+    match ty {
+        BasicTypeEnum::FloatType(_) => true,
+        BasicTypeEnum::VectorType(vector_ty) => vector_ty.get_element_type().is_float_type(),
+        _ => false,
+    }
+}
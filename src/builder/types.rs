@@ -0,0 +1,293 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt::Display;
+use std::rc::Rc;
+
+use inkwell::module::Module;
+use inkwell::types::{BasicTypeEnum, FloatType, StructType};
+use inkwell::values::InstructionOpcode;
+
+use super::naming::NameScope;
+use super::soft_float::float_struct;
+use super::{generated_item_names, CairoBuilder};
+use crate::ast::printer::Printer;
+use crate::ast::{Expr, Item};
+
+/// Cairo type of the pointers. Cairo doesn't have pointers so they're addresses in an emulated
+/// memory, 0 is `null` and all the address spaces share the same memory. As they're plain integers
+/// they round-trip through `ptrtoint`/`inttoptr` and `icmp` compares them like integers.
+pub const POINTER_TYPE: &str = "u64";
+
+/// Bit width of [POINTER_TYPE].
+pub const POINTER_WIDTH: u32 = 64;
+
+/// LLVM struct name => cairo name of the named struct types of a module, see
+/// [CairoBuilder::translate_struct_types].
+pub type StructNames = HashMap<String, String>;
+
+/// The `null` pointer, address 0 is never allocated.
+pub fn null_pointer() -> Expr {
+    Expr::literal(format!("0_{POINTER_TYPE}"))
+}
+
+/// Translate an LLVM type to its cairo equivalent.
+///
+/// * `i1` is a boolean in disguise so it becomes `bool`.
+/// * Named structs (`%"core::option::Option<u64>"`) become the cairo struct emitted by
+///   [CairoBuilder::translate_struct_types].
+/// * Literal structs (`{ i64, i1 }`) don't have a name so they become tuples.
+/// * Arrays (`[4 x i8]`) and vectors (`<4 x i32>`) become fixed-size arrays (`[i8; 4]`).
+/// * `float` and `double` become the structs of the float library (`F32`, `F64`), see
+///   [crate::builder::soft_float].
+/// * Pointers (`ptr`, in any address space) become [POINTER_TYPE] addresses.
+pub fn cairo_type(ty: BasicTypeEnum, struct_names: &StructNames) -> String {
+    match ty {
+        BasicTypeEnum::PointerType(_) => POINTER_TYPE.to_owned(),
+        BasicTypeEnum::IntType(int_ty) if int_ty.get_bit_width() == 1 => "bool".to_owned(),
+        BasicTypeEnum::FloatType(float_ty) if float_width(float_ty).is_some() => {
+            float_struct(float_width(float_ty).expect("Float width was just checked"))
+        }
+        BasicTypeEnum::StructType(struct_ty) => match struct_name(&struct_ty, struct_names) {
+            Some(name) => name,
+            None => cairo_tuple(
+                struct_ty.get_field_types().into_iter().map(|field_ty| cairo_type(field_ty, struct_names)).collect(),
+            ),
+        },
+        // LLVM arrays `[N x T]` and SIMD vectors `<N x T>` both become cairo fixed-size arrays. Vector
+        // operations are scalarized so we only need to be able to destructure them.
+        BasicTypeEnum::ArrayType(array_ty) => {
+            format!("[{}; {}]", cairo_type(array_ty.get_element_type(), struct_names), array_ty.len())
+        }
+        BasicTypeEnum::VectorType(vector_ty) => {
+            format!("[{}; {}]", cairo_type(vector_ty.get_element_type(), struct_names), vector_ty.get_size())
+        }
+        // TODO(Lucas): support the other types.
+        _ => ty.print_to_string().to_string(),
+    }
+}
+
+/// Get the value a variable of type `ty` is initialized with before we know its actual value.
+/// This is used for the mutable variables that we declare before a new scope.
+pub fn cairo_default_value(ty: BasicTypeEnum, struct_names: &StructNames) -> Expr {
+    match ty {
+        BasicTypeEnum::IntType(int_ty) if int_ty.get_bit_width() == 1 => Expr::literal("false"),
+        BasicTypeEnum::IntType(_) => Expr::literal(format!("0_{}", cairo_type(ty, struct_names))),
+        BasicTypeEnum::PointerType(_) => null_pointer(),
+        BasicTypeEnum::StructType(struct_ty) => {
+            let fields = struct_ty
+                .get_field_types()
+                .into_iter()
+                .map(|field_ty| cairo_default_value(field_ty, struct_names))
+                .collect::<Vec<_>>();
+            match struct_name(&struct_ty, struct_names) {
+                Some(name) => Expr::Struct {
+                    name,
+                    fields: fields.into_iter().enumerate().map(|(index, value)| (field_name(index), value)).collect(),
+                },
+                None => Expr::Tuple(fields),
+            }
+        }
+        BasicTypeEnum::ArrayType(array_ty) => {
+            Expr::FixedArray(vec![
+                cairo_default_value(array_ty.get_element_type(), struct_names);
+                array_ty.len() as usize
+            ])
+        }
+        BasicTypeEnum::VectorType(vector_ty) => {
+            Expr::FixedArray(vec![
+                cairo_default_value(vector_ty.get_element_type(), struct_names);
+                vector_ty.get_size() as usize
+            ])
+        }
+        _ => Expr::call("Default::default", Vec::new()),
+    }
+}
+
+/// Bit width of the float types the float library supports: 32 for `float` and 64 for `double`.
+/// `None` for the other ones (`half`, `fp128`...).
+pub fn float_width(float_ty: FloatType) -> Option<u32> {
+    match float_ty.print_to_string().to_string().as_str() {
+        "float" => Some(32),
+        "double" => Some(64),
+        _ => None,
+    }
+}
+
+/// Cairo name of a named LLVM struct. Returns `None` for literal structs. The structs that aren't
+/// in `struct_names` (not reachable from the module) get their sanitized name.
+pub fn struct_name(struct_ty: &StructType, struct_names: &StructNames) -> Option<String> {
+    let name = struct_ty.get_name()?.to_str().expect("Struct name should be utf-8");
+    struct_names.get(name).cloned().or_else(|| Some(sanitize_type_name(name)).filter(|name| !name.is_empty()))
+}
+
+/// Name of the `index`th field of a translated struct. LLVM struct fields are positional only.
+pub fn field_name(index: usize) -> String {
+    format!("field{index}")
+}
+
+/// Turn an LLVM struct name such as `core::option::Option<u64>` or `MyStruct.0` into a valid cairo
+/// type name (`core_option_Option_u64`, `MyStruct_0`).
+fn sanitize_type_name(name: &str) -> String {
+    let mut sanitized = String::with_capacity(name.len());
+    for c in name.chars() {
+        if c.is_ascii_alphanumeric() {
+            sanitized.push(c);
+        } else if !sanitized.is_empty() && !sanitized.ends_with('_') {
+            sanitized.push('_');
+        }
+    }
+    let sanitized = sanitized.trim_end_matches('_');
+    if sanitized.starts_with(|c: char| c.is_ascii_digit()) {
+        format!("S{sanitized}")
+    } else {
+        sanitized.to_owned()
+    }
+}
+
+/// Format a list of cairo types as a tuple. A single element tuple needs a trailing comma.
+fn cairo_tuple(elements: Vec<String>) -> String {
+    if elements.len() == 1 {
+        format!("({},)", elements[0])
+    } else {
+        format!("({})", elements.join(", "))
+    }
+}
+
+/// Add `ty` and the types it contains to `types` if they're not in it yet. Named structs are added
+/// after the types of their fields so nested structs come first.
+fn collect_struct_types<'ctx>(ty: BasicTypeEnum<'ctx>, seen: &mut HashSet<String>, types: &mut Vec<StructType<'ctx>>) {
+    match ty {
+        BasicTypeEnum::StructType(struct_ty) => {
+            let name = struct_ty.get_name().map(|name| name.to_string_lossy().into_owned());
+            // Named structs are unique in a module, LLVM renames the colliding ones (`%Pair.0`).
+            if name.as_ref().is_some_and(|name| !seen.insert(name.clone())) {
+                return;
+            }
+            for field_ty in struct_ty.get_field_types() {
+                collect_struct_types(field_ty, seen, types);
+            }
+            // Literal structs are translated to tuples and opaque structs don't have a body so there
+            // is nothing to emit.
+            if name.is_some() && !struct_ty.is_opaque() {
+                types.push(struct_ty);
+            }
+        }
+        BasicTypeEnum::ArrayType(array_ty) => collect_struct_types(array_ty.get_element_type(), seen, types),
+        BasicTypeEnum::VectorType(vector_ty) => collect_struct_types(vector_ty.get_element_type(), seen, types),
+        _ => (),
+    }
+}
+
+/// Get the named struct types used by the globals and the functions of the module, in the order
+/// they're first used with the nested structs before the structs that contain them.
+fn module_struct_types<'ctx>(module: &Module<'ctx>) -> Vec<StructType<'ctx>> {
+    let mut seen = HashSet::new();
+    let mut types = Vec::new();
+    for global in module.get_globals() {
+        if let Ok(ty) = BasicTypeEnum::try_from(global.get_value_type()) {
+            collect_struct_types(ty, &mut seen, &mut types);
+        }
+    }
+    for function in module.get_functions() {
+        for param in function.get_param_iter() {
+            collect_struct_types(param.get_type(), &mut seen, &mut types);
+        }
+        if let Some(return_ty) = function.get_type().get_return_type() {
+            collect_struct_types(return_ty, &mut seen, &mut types);
+        }
+        for instruction in function.get_basic_block_iter().flat_map(|bb| bb.get_instructions()) {
+            if let Ok(ty) = BasicTypeEnum::try_from(instruction.get_type()) {
+                collect_struct_types(ty, &mut seen, &mut types);
+            }
+            for operand in instruction.get_operands().flatten().filter_map(|operand| operand.left()) {
+                collect_struct_types(operand.get_type(), &mut seen, &mut types);
+            }
+            // With opaque pointers the type a pointer points to is only known by the instructions that
+            // use it.
+            let pointee_ty = match instruction.get_opcode() {
+                InstructionOpcode::Alloca => instruction.get_allocated_type().ok(),
+                InstructionOpcode::GetElementPtr => instruction.get_gep_source_element_type().ok(),
+                _ => None,
+            };
+            if let Some(ty) = pointee_ty {
+                collect_struct_types(ty, &mut seen, &mut types);
+            }
+        }
+    }
+    types
+}
+
+impl<'ctx> CairoBuilder<'ctx> {
+    /// Translate the named struct types of the module to cairo structs. LLVM doesn't let us iterate
+    /// over the types of a module so we walk the types used by its globals and functions. Each LLVM
+    /// struct gets its own cairo name, even when their sanitized names are the same:
+    /// %"core::option::Option<u64>" = type { i64, [1 x i64] } => core_option_Option_u64
+    /// %core_option_Option_u64 = type { i32 }                 => core_option_Option_u64_1
+    /// The names of the items generated with the structs (`Memory`, `F32`...) are reserved, see
+    /// [super::generated_item_names].
+    pub fn translate_struct_types(&mut self, module: &Module<'ctx>) {
+        let types = module_struct_types(module);
+        let mut scope = NameScope::default();
+        for name in generated_item_names() {
+            scope.fresh(&name);
+        }
+        let mut names = StructNames::new();
+        for struct_ty in types.iter() {
+            let name = struct_ty.get_name().expect("Only named structs are collected").to_string_lossy();
+            names.insert(name.to_string(), scope.fresh(&sanitize_type_name(&name)));
+        }
+        self.struct_names = Rc::new(names);
+        for struct_ty in types {
+            let name = struct_name(&struct_ty, &self.struct_names).expect("Only named structs are collected");
+            let fields = struct_ty
+                .get_field_types()
+                .into_iter()
+                .map(|field_ty| cairo_type(field_ty, &self.struct_names))
+                .collect();
+            self.structs.push_struct(CairoStruct::new(name, fields));
+        }
+    }
+}
+
+#[derive(Default, Clone, PartialEq, Debug)]
+pub struct CairoStructs(Vec<CairoStruct>);
+
+impl CairoStructs {
+    pub fn structs(&self) -> &[CairoStruct] {
+        &self.0
+    }
+
+    pub fn push_struct(&mut self, cairo_struct: CairoStruct) {
+        self.0.push(cairo_struct)
+    }
+}
+
+impl Display for CairoStructs {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0.iter().map(ToString::to_string).collect::<Vec<_>>().join("\n\n"))
+    }
+}
+
+#[derive(Default, Clone, PartialEq, Debug)]
+pub struct CairoStruct {
+    pub(crate) name: String,
+    /// Types of the fields. Their name is derived from their position see [field_name].
+    pub(crate) fields: Vec<String>,
+}
+
+impl CairoStruct {
+    pub fn new(name: String, fields: Vec<String>) -> Self {
+        Self { name, fields }
+    }
+
+    /// The struct as an item of the module. The values of the translated code are copied around
+    /// so the struct derives `Copy` and `Drop`.
+    pub fn item(&self) -> Item {
+        Item::attribute("derive(Copy, Drop)", Item::Struct(self.clone()))
+    }
+}
+
+impl Display for CairoStruct {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&Printer::default().item(&self.item(), 0))
+    }
+}
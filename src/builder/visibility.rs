@@ -0,0 +1,91 @@
+//! Visibility of the translated functions and removal of the ones that aren't used.
+//!
+//! The entry points of a module are the functions LLVM exports (`external` linkage, which is what
+//! rustc gives to `pub` and `#[no_mangle]` functions, or `dllexport`), or the functions of
+//! [crate::builder::options::TranslationOptions::entrypoints] if it isn't empty. They're `pub`,
+//! the other functions (`internal` helpers, `linkonce_odr` monomorphizations, `private`
+//! functions...) are private to the cairo module. The functions that can't be reached from an
+//! entry point are removed.
+
+use std::collections::{HashMap, HashSet};
+
+use inkwell::module::{Linkage, Module};
+use inkwell::values::FunctionValue;
+use inkwell::DLLStorageClass;
+
+use super::CairoBuilder;
+use crate::ast::Expr;
+use crate::builder::contract::CairoContract;
+
+/// Can the function be called from outside of its LLVM module?
+pub fn is_exported(function: &FunctionValue) -> bool {
+    matches!(function.get_linkage(), Linkage::External | Linkage::DLLExport)
+        || function.as_global_value().get_dll_storage_class() == DLLStorageClass::Export
+}
+
+impl<'ctx> CairoBuilder<'ctx> {
+    /// Make the entry points public and the other functions private, then remove the functions that
+    /// can't be reached from an entry point or from the contract. Must be called after all the
+    /// functions are translated.
+    pub fn apply_visibility(&mut self, module: &Module<'ctx>, contract: Option<&CairoContract>) {
+        let allowlist = &self.options.entrypoints;
+        let entry_points = module
+            .get_functions()
+            .filter(|function| function.count_basic_blocks() > 0)
+            .map(|function| (function, function.get_name().to_string_lossy().into_owned()))
+            .filter(|(function, symbol)| {
+                if allowlist.is_empty() {
+                    return is_exported(function);
+                }
+                allowlist.contains(symbol)
+                    || self.function_names.get(symbol).is_some_and(|name| allowlist.contains(name))
+            })
+            .map(|(_, symbol)| symbol)
+            .collect::<HashSet<_>>();
+        for function in self.functions.0.iter_mut() {
+            function.signature.public = entry_points.contains(&function.symbol);
+        }
+
+        // The functions exposed by the contract are called from its impl.
+        let mut reachable = self
+            .functions
+            .functions()
+            .iter()
+            .filter(|function| function.signature.public)
+            .map(|function| function.signature.name.clone())
+            .chain(
+                contract
+                    .into_iter()
+                    .flat_map(|contract| contract.entry_points().iter().map(|entry| entry.name.clone())),
+            )
+            .collect::<HashSet<_>>();
+        // A module without entry points is kept whole, there is nothing to start from.
+        if reachable.is_empty() {
+            return;
+        }
+        // Cairo name => names of the functions it calls.
+        let calls = self
+            .functions
+            .functions()
+            .iter()
+            .map(|function| {
+                let mut callees = Vec::new();
+                function.body.block().visit_exprs(&mut |expr| {
+                    if let Expr::Call { function, .. } = expr {
+                        callees.push(function.clone());
+                    }
+                });
+                (function.signature.name.clone(), callees)
+            })
+            .collect::<HashMap<_, _>>();
+        let mut stack = reachable.iter().cloned().collect::<Vec<_>>();
+        while let Some(name) = stack.pop() {
+            for callee in calls.get(&name).into_iter().flatten() {
+                if reachable.insert(callee.clone()) {
+                    stack.push(callee.clone());
+                }
+            }
+        }
+        self.functions.0.retain(|function| reachable.contains(&function.signature.name));
+    }
+}
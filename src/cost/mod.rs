@@ -0,0 +1,377 @@
+//! Estimation of the cost of the translated functions: the cairo steps, the range checks and the
+//! gas they use. The model is a rough approximation of what the sierra compiler generates for each
+//! construct of the AST, it's meant to compare functions and spot the expensive ones, not to
+//! predict the exact resources of a run.
+//!
+//! Loops run as many times as their trip count (see [trip_counts]), the loops whose trip count
+//! isn't known use [CostOptions::default_trip_count]. A recursive call only costs the call itself
+//! as the depth of the recursion isn't known.
+
+pub mod trip_counts;
+
+use std::collections::HashMap;
+use std::ops::{Add, AddAssign, Mul};
+
+use trip_counts::TripCounts;
+
+use crate::ast::{BinaryOperator, Block, Expr, Statement};
+use crate::builder::function::CairoFunction;
+use crate::source_map::json_string;
+
+/// Gas of a cairo step.
+pub const STEP_GAS: u64 = 100;
+/// Gas of a range check.
+pub const RANGE_CHECK_GAS: u64 = 70;
+
+#[derive(Clone, Debug)]
+pub struct CostOptions {
+    /// Number of iterations of the loops whose trip count can't be found in the LLVM module.
+    pub default_trip_count: u64,
+}
+
+impl Default for CostOptions {
+    fn default() -> Self {
+        Self { default_trip_count: 10 }
+    }
+}
+
+/// Resources used by a piece of code.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct Cost {
+    pub steps: u64,
+    pub range_checks: u64,
+}
+
+impl Cost {
+    pub fn new(steps: u64, range_checks: u64) -> Self {
+        Self { steps, range_checks }
+    }
+
+    pub fn gas(&self) -> u64 {
+        self.steps * STEP_GAS + self.range_checks * RANGE_CHECK_GAS
+    }
+
+    /// The most expensive of two branches, compared by gas.
+    fn max(self, other: Self) -> Self {
+        if other.gas() > self.gas() {
+            other
+        } else {
+            self
+        }
+    }
+}
+
+impl Add for Cost {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self::new(self.steps.saturating_add(other.steps), self.range_checks.saturating_add(other.range_checks))
+    }
+}
+
+impl AddAssign for Cost {
+    fn add_assign(&mut self, other: Self) {
+        *self = *self + other;
+    }
+}
+
+impl Mul<u64> for Cost {
+    type Output = Self;
+
+    fn mul(self, times: u64) -> Self {
+        Self::new(self.steps.saturating_mul(times), self.range_checks.saturating_mul(times))
+    }
+}
+
+/// Estimated cost of one call of a translated function, including the functions it calls.
+#[derive(Clone, PartialEq, Debug)]
+pub struct FunctionCost {
+    /// Cairo name of the function.
+    pub name: String,
+    pub cost: Cost,
+    /// Number of loops in the function.
+    pub loops: usize,
+    /// Number of loops that use the default trip count.
+    pub assumed_loops: usize,
+    /// The function calls itself (directly or not), the cost only counts one call. Comes from the
+    /// call graph of the module, see [crate::builder::call_graph::CallGraph::recursive_cycles].
+    pub recursive: bool,
+}
+
+#[derive(Clone, Default, PartialEq, Debug)]
+pub struct CostReport {
+    pub functions: Vec<FunctionCost>,
+}
+
+impl CostReport {
+    /// Cost of the function with this cairo name.
+    pub fn function(&self, name: &str) -> Option<&FunctionCost> {
+        self.functions.iter().find(|function| function.name == name)
+    }
+
+    /// Serialize the report to JSON:
+    /// ```json
+    /// {
+    ///   "step_gas": 100,
+    ///   "range_check_gas": 70,
+    ///   "functions": [
+    ///     {"name": "fib", "steps": 127, "range_checks": 20, "gas": 14100, "loops": 1, "assumed_loops": 1, "recursive": false}
+    ///   ]
+    /// }
+    /// ```
+    pub fn to_json(&self) -> String {
+        let functions = self
+            .functions
+            .iter()
+            .map(|function| {
+                format!(
+                    "    {{\"name\": {}, \"steps\": {}, \"range_checks\": {}, \"gas\": {}, \"loops\": {}, \
+                     \"assumed_loops\": {}, \"recursive\": {}}}",
+                    json_string(&function.name),
+                    function.cost.steps,
+                    function.cost.range_checks,
+                    function.cost.gas(),
+                    function.loops,
+                    function.assumed_loops,
+                    function.recursive
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(",\n");
+        format!(
+            "{{\n  \"step_gas\": {STEP_GAS},\n  \"range_check_gas\": {RANGE_CHECK_GAS},\n  \"functions\": \
+             [\n{functions}\n  ]\n}}\n"
+        )
+    }
+
+    /// Format the report as a table:
+    /// ```text
+    /// function  steps  range checks    gas  notes
+    /// fib         127            20  14100  1 loop with an assumed trip count
+    /// ```
+    pub fn to_table(&self) -> String {
+        let header = ["function", "steps", "range checks", "gas", "notes"].map(str::to_owned);
+        let rows = self
+            .functions
+            .iter()
+            .map(|function| {
+                let mut notes = Vec::new();
+                if function.assumed_loops > 0 {
+                    let plural = if function.assumed_loops > 1 { "s" } else { "" };
+                    notes.push(format!("{} loop{plural} with an assumed trip count", function.assumed_loops));
+                }
+                if function.recursive {
+                    notes.push("recursive, counted once".to_owned());
+                }
+                [
+                    function.name.clone(),
+                    function.cost.steps.to_string(),
+                    function.cost.range_checks.to_string(),
+                    function.cost.gas().to_string(),
+                    notes.join(", "),
+                ]
+            })
+            .collect::<Vec<_>>();
+        let widths = (0..header.len())
+            .map(|column| rows.iter().chain([&header]).map(|row| row[column].len()).max().unwrap_or_default())
+            .collect::<Vec<_>>();
+        [header].iter().chain(rows.iter()).fold(String::new(), |mut table, row| {
+            // The name is aligned to the left and the numbers to the right, the notes aren't padded.
+            let line = format!(
+                "{:<name$}  {:>steps$}  {:>range_checks$}  {:>gas$}  {}",
+                row[0],
+                row[1],
+                row[2],
+                row[3],
+                row[4],
+                name = widths[0],
+                steps = widths[1],
+                range_checks = widths[2],
+                gas = widths[3],
+            );
+            table.push_str(line.trim_end());
+            table.push('\n');
+            table
+        })
+    }
+}
+
+/// Estimate the cost of the translated functions.
+///
+/// # Arguments
+///
+/// * `functions` - The translated functions of a module.
+/// * `trip_counts` - The trip counts of the loops of the LLVM module the functions come from.
+/// * `options` - The cost options.
+pub fn estimate(functions: &[CairoFunction], trip_counts: &TripCounts, options: &CostOptions) -> CostReport {
+    let mut estimator = Estimator {
+        functions: functions.iter().map(|function| (function.signature.name.as_str(), function)).collect(),
+        trip_counts,
+        options,
+        costs: HashMap::new(),
+        stack: Vec::new(),
+    };
+    let functions =
+        functions.iter().map(|function| estimator.function_cost(function.signature.name.as_str())).collect();
+    CostReport { functions }
+}
+
+/// Walks the functions, the cost of each function is computed once and reused by its callers.
+struct Estimator<'a> {
+    /// Cairo name => function.
+    functions: HashMap<&'a str, &'a CairoFunction>,
+    trip_counts: &'a TripCounts,
+    options: &'a CostOptions,
+    /// Functions that are already estimated.
+    costs: HashMap<&'a str, FunctionCost>,
+    /// Functions being estimated, a call to one of them is a recursive call that is cut.
+    stack: Vec<&'a str>,
+}
+
+/// What we find while walking a function besides its cost.
+#[derive(Default)]
+struct Walk {
+    loops: usize,
+    assumed_loops: usize,
+    /// A recursive call was cut, directly or in a callee.
+    cut: bool,
+}
+
+impl<'a> Estimator<'a> {
+    fn function_cost(&mut self, name: &'a str) -> FunctionCost {
+        if let Some(cost) = self.costs.get(name) {
+            return cost.clone();
+        }
+        let function = self.functions[name];
+        self.stack.push(name);
+        let mut walk = Walk::default();
+        let cost = self.block_cost(function, function.body.block(), &mut walk);
+        self.stack.pop();
+        let function_cost = FunctionCost {
+            name: name.to_owned(),
+            cost,
+            loops: walk.loops,
+            assumed_loops: walk.assumed_loops,
+            recursive: function.recursive(),
+        };
+        // A function in a recursive cycle that isn't complete yet would be cached with the cost of
+        // the part of the cycle that was already walked.
+        if !walk.cut || self.stack.is_empty() {
+            self.costs.insert(name, function_cost.clone());
+        }
+        function_cost
+    }
+
+    fn block_cost(&mut self, function: &'a CairoFunction, block: &'a Block, walk: &mut Walk) -> Cost {
+        block
+            .statements
+            .iter()
+            .map(|statement| self.statement_cost(function, statement, walk))
+            .fold(Cost::default(), |total, cost| total + cost)
+    }
+
+    fn statement_cost(&mut self, function: &'a CairoFunction, statement: &'a Statement, walk: &mut Walk) -> Cost {
+        match statement {
+            Statement::Let { value, .. } | Statement::Assign { value, .. } | Statement::Return(Some(value)) => {
+                self.expr_cost(value, walk) + Cost::new(1, 0)
+            }
+            Statement::Expr(value) | Statement::Tail(value) => self.expr_cost(value, walk),
+            Statement::Return(None) | Statement::Break | Statement::Continue => Cost::new(1, 0),
+            Statement::If { condition, then_block, else_block } => {
+                let then_cost = self.block_cost(function, then_block, walk);
+                let else_cost =
+                    else_block.as_ref().map(|block| self.block_cost(function, block, walk)).unwrap_or_default();
+                self.expr_cost(condition, walk) + Cost::new(2, 0) + then_cost.max(else_cost)
+            }
+            Statement::Loop(body) => {
+                walk.loops += 1;
+                // The loop is matched with the LLVM loop by the block its body starts with.
+                let header = body.statements.iter().find_map(|statement| match statement {
+                    Statement::Origin(origin) => Some(origin.block.clone()),
+                    _ => None,
+                });
+                let trips = header
+                    .and_then(|header| self.trip_counts.get(&(function.symbol.clone(), header)).copied())
+                    .unwrap_or_else(|| {
+                        walk.assumed_loops += 1;
+                        self.options.default_trip_count
+                    });
+                // Each iteration jumps back to the start of the loop.
+                (self.block_cost(function, body, walk) + Cost::new(2, 0)) * trips
+            }
+            Statement::Origin(_) => Cost::default(),
+        }
+    }
+
+    fn expr_cost(&mut self, expr: &'a Expr, walk: &mut Walk) -> Cost {
+        match expr {
+            Expr::Variable(_) | Expr::Ref(_) => Cost::default(),
+            Expr::Literal(_) => Cost::new(1, 0),
+            Expr::Binary { operator, left, right } => {
+                let operation = match operator {
+                    BinaryOperator::Add | BinaryOperator::Sub => Cost::new(4, 1),
+                    BinaryOperator::Mul => Cost::new(6, 1),
+                    BinaryOperator::Div | BinaryOperator::Rem => Cost::new(10, 3),
+                    BinaryOperator::Eq | BinaryOperator::Ne => Cost::new(2, 0),
+                    BinaryOperator::Lt | BinaryOperator::Le | BinaryOperator::Gt | BinaryOperator::Ge => {
+                        Cost::new(4, 1)
+                    }
+                    BinaryOperator::And | BinaryOperator::Or => Cost::new(2, 0),
+                    BinaryOperator::BitAnd | BinaryOperator::BitOr | BinaryOperator::BitXor => Cost::new(5, 0),
+                };
+                self.expr_cost(left, walk) + self.expr_cost(right, walk) + operation
+            }
+            Expr::Not(value) => self.expr_cost(value, walk) + Cost::new(1, 0),
+            Expr::Desnap(value) => self.expr_cost(value, walk),
+            Expr::If { condition, then_value, else_value } => {
+                let branches = self.expr_cost(then_value, walk).max(self.expr_cost(else_value, walk));
+                self.expr_cost(condition, walk) + Cost::new(2, 0) + branches
+            }
+            Expr::Call { function, arguments } => {
+                let arguments = self.exprs_cost(arguments, walk);
+                let callee = self.functions.get_key_value(function.as_str()).map(|(name, _)| *name);
+                let callee = match callee {
+                    // The depth of the recursion isn't known, only the call is counted.
+                    Some(name) if self.stack.contains(&name) => {
+                        walk.cut = true;
+                        Cost::default()
+                    }
+                    Some(name) => {
+                        let callee = self.function_cost(name);
+                        walk.loops += callee.loops;
+                        walk.assumed_loops += callee.assumed_loops;
+                        // The callee isn't cached when one of its calls was cut.
+                        walk.cut |= !self.costs.contains_key(name);
+                        callee.cost
+                    }
+                    // Functions of the core library or of other modules, counted as a call.
+                    None => Cost::default(),
+                };
+                arguments + Cost::new(3, 0) + callee
+            }
+            Expr::MethodCall { receiver, method, arguments } => {
+                let call = match method.as_str() {
+                    "into" => Cost::new(1, 0),
+                    "try_into" => Cost::new(5, 1),
+                    "unwrap" => Cost::new(2, 0),
+                    _ => Cost::new(3, 0),
+                };
+                self.expr_cost(receiver, walk) + self.exprs_cost(arguments, walk) + call
+            }
+            Expr::Index { array, index } => self.expr_cost(array, walk) + self.expr_cost(index, walk) + Cost::new(3, 0),
+            Expr::Tuple(values) | Expr::FixedArray(values) => self.exprs_cost(values, walk),
+            Expr::Struct { fields, .. } => {
+                fields.iter().map(|(_, value)| self.expr_cost(value, walk)).fold(Cost::default(), |a, b| a + b)
+            }
+            Expr::Panic { arguments, .. } => self.exprs_cost(arguments, walk) + Cost::new(10, 0),
+            Expr::Match { value, arms } => {
+                let arms = arms.iter().map(|(_, arm)| self.expr_cost(arm, walk)).fold(Cost::default(), Cost::max);
+                self.expr_cost(value, walk) + Cost::new(2, 0) + arms
+            }
+        }
+    }
+
+    fn exprs_cost(&mut self, exprs: &'a [Expr], walk: &mut Walk) -> Cost {
+        exprs.iter().map(|expr| self.expr_cost(expr, walk)).fold(Cost::default(), |a, b| a + b)
+    }
+}
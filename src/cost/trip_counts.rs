@@ -0,0 +1,182 @@
+//! Number of iterations of the LLVM loops, when it's known at compile time. It comes from:
+//! * the induction variable of the loop: a phi that starts at a constant, is incremented by a
+//!   constant at each iteration and compared to a constant to exit the loop, as SCEV would see it,
+//! * the `llvm.loop.estimated_trip_count` property of the `!llvm.loop` metadata of the branch that
+//!   jumps back to the loop header.
+
+use std::collections::HashMap;
+
+use inkwell::basic_block::BasicBlock;
+use inkwell::module::Module;
+use inkwell::values::{AsValueRef, BasicValueEnum, InstructionOpcode, InstructionValue, IntValue, PhiValue};
+use inkwell::IntPredicate;
+
+use crate::source_map::DebugMetadata;
+
+/// (LLVM function symbol, loop header block) => number of times the loop body runs.
+pub type TripCounts = HashMap<(String, String), u64>;
+
+/// The induction variable is simulated to find the trip count, the loops that run longer than that
+/// are considered unknown.
+const MAX_SIMULATED_TRIPS: u64 = 1 << 20;
+
+/// Property of the `!llvm.loop` metadata with the estimated trip count.
+const ESTIMATED_TRIP_COUNT: &str = "llvm.loop.estimated_trip_count";
+
+/// Find the trip counts of the loops of a module. Loops whose header block doesn't have a name are
+/// ignored as they can't be matched with the translation.
+pub fn loop_trip_counts(module: &Module) -> TripCounts {
+    let metadata = DebugMetadata::parse(&module.print_to_string().to_string());
+    let mut trip_counts = HashMap::new();
+    for function in module.get_functions() {
+        let symbol = function.get_name().to_string_lossy().into_owned();
+        let blocks = function.get_basic_blocks();
+        for (index, latch) in blocks.iter().enumerate() {
+            let Some(branch) = latch.get_terminator().filter(|terminator| {
+                terminator.get_opcode() == InstructionOpcode::Br && terminator.get_num_operands() == 3
+            }) else {
+                continue;
+            };
+            // The operands of a conditional branch are the condition, the false target and the true target.
+            let (Some(on_false), Some(on_true)) = (
+                branch.get_operand(1).and_then(|operand| operand.right()),
+                branch.get_operand(2).and_then(|operand| operand.right()),
+            ) else {
+                continue;
+            };
+            // A jump back to a block that comes before is a jump to the header of a loop.
+            let is_before = |block: &BasicBlock| blocks[..=index].contains(block);
+            let header = match (is_before(&on_true), is_before(&on_false)) {
+                (true, false) => on_true,
+                (false, true) => on_false,
+                _ => continue,
+            };
+            let name = header.get_name().to_string_lossy().into_owned();
+            if name.is_empty() {
+                continue;
+            }
+            let trip_count = induction_trip_count(&branch, *latch, header, on_true == header)
+                .or_else(|| estimated_trip_count(&branch, &metadata));
+            if let Some(trip_count) = trip_count {
+                trip_counts.insert((symbol.clone(), name), trip_count);
+            }
+        }
+    }
+    trip_counts
+}
+
+/// Trip count from the `!llvm.loop` metadata of the back edge:
+/// ```llvm
+/// br i1 %exit, label %end, label %loop, !llvm.loop !5
+/// !5 = distinct !{!5, !6}
+/// !6 = !{!"llvm.loop.estimated_trip_count", i32 100}
+/// ```
+fn estimated_trip_count(branch: &InstructionValue, metadata: &DebugMetadata) -> Option<u64> {
+    let printed = branch.print_to_string().to_string();
+    let (_, reference) = printed.split_once("!llvm.loop ")?;
+    let loop_node = metadata.node(reference.split([',', ' ']).next()?)?;
+    loop_node.trim_start_matches("!{").trim_end_matches('}').split(", ").find_map(|property| {
+        let property = metadata.node(property)?;
+        let (_, count) = property.split_once(&format!("!\"{ESTIMATED_TRIP_COUNT}\", "))?;
+        count.split_whitespace().nth(1)?.trim_end_matches('}').parse().ok()
+    })
+}
+
+/// Constant value of an integer operand.
+fn constant(value: BasicValueEnum) -> Option<i128> {
+    match value {
+        BasicValueEnum::IntValue(value) => value.get_sign_extended_constant().map(i128::from),
+        _ => None,
+    }
+}
+
+/// If `value` is a phi of `header` get it.
+fn header_phi<'ctx>(value: IntValue<'ctx>, header: BasicBlock<'ctx>) -> Option<PhiValue<'ctx>> {
+    let instruction = value.as_instruction()?;
+    (instruction.get_opcode() == InstructionOpcode::Phi && instruction.get_parent() == Some(header))
+        .then(|| unsafe { PhiValue::new(instruction.as_value_ref()) })
+}
+
+/// Trip count of the loop from its induction variable. The exit condition of the latch has to
+/// compare the induction variable (before or after its increment) to a constant:
+/// ```llvm
+/// loop:
+///   %i = phi i32 [ 0, %start ], [ %i.next, %loop ]
+///   ...
+///   %i.next = add i32 %i, 1
+///   %exit = icmp eq i32 %i.next, 10
+///   br i1 %exit, label %end, label %loop
+/// ```
+fn induction_trip_count<'ctx>(
+    branch: &InstructionValue<'ctx>,
+    latch: BasicBlock<'ctx>,
+    header: BasicBlock<'ctx>,
+    continue_on_true: bool,
+) -> Option<u64> {
+    let condition = branch.get_operand(0)?.left()?.into_int_value().as_instruction()?;
+    let predicate = condition.get_icmp_predicate()?;
+    let tested = condition.get_operand(0)?.left()?;
+    let bound = constant(condition.get_operand(1)?.left()?)?;
+    if !tested.is_int_value() {
+        return None;
+    }
+    let tested = tested.into_int_value();
+    let width = tested.get_type().get_bit_width();
+    // The tested value is either the phi or the phi plus the step.
+    let (phi, after_step) = match header_phi(tested, header) {
+        Some(phi) => (phi, false),
+        None => {
+            let add = tested.as_instruction().filter(|add| add.get_opcode() == InstructionOpcode::Add)?;
+            (header_phi(add.get_operand(0)?.left()?.into_int_value(), header)?, true)
+        }
+    };
+    // [ start, %preheader ], [ %phi + step, %latch ]
+    let (mut start, mut step) = (None, None);
+    for index in 0..phi.count_incoming() {
+        let (value, block) = phi.get_incoming(index)?;
+        if block == latch {
+            let add =
+                value.into_int_value().as_instruction().filter(|add| add.get_opcode() == InstructionOpcode::Add)?;
+            let increments_phi = add.get_operand(0)?.left()? == phi.as_basic_value();
+            step = increments_phi.then(|| constant(add.get_operand(1)?.left()?)).flatten();
+        } else {
+            start = constant(value);
+        }
+    }
+    let (mut value, step) = (start?, step?);
+    for trip in 1..=MAX_SIMULATED_TRIPS {
+        let tested = if after_step { wrap(value + step, width) } else { value };
+        if compare(predicate, tested, bound, width) != continue_on_true {
+            return Some(trip);
+        }
+        value = wrap(value + step, width);
+    }
+    None
+}
+
+/// Wrap a value to a `width` bits signed integer.
+fn wrap(value: i128, width: u32) -> i128 {
+    if width >= 128 {
+        return value;
+    }
+    let shift = 128 - width;
+    (value << shift) >> shift
+}
+
+/// Evaluate `left <predicate> right` on `width` bits integers.
+fn compare(predicate: IntPredicate, left: i128, right: i128, width: u32) -> bool {
+    let unsigned = |value: i128| if width >= 128 { value as u128 } else { (value as u128) & ((1 << width) - 1) };
+    let (left, right) = (wrap(left, width), wrap(right, width));
+    match predicate {
+        IntPredicate::EQ => left == right,
+        IntPredicate::NE => left != right,
+        IntPredicate::SLT => left < right,
+        IntPredicate::SLE => left <= right,
+        IntPredicate::SGT => left > right,
+        IntPredicate::SGE => left >= right,
+        IntPredicate::ULT => unsigned(left) < unsigned(right),
+        IntPredicate::ULE => unsigned(left) <= unsigned(right),
+        IntPredicate::UGT => unsigned(left) > unsigned(right),
+        IntPredicate::UGE => unsigned(left) >= unsigned(right),
+    }
+}
@@ -1,38 +1,206 @@
 use std::path::Path;
 
-use builder::{CairoBuilder, CairoFunctions};
+use ast::printer::FormatOptions;
+use builder::call_graph::CallGraph;
+use builder::options::TranslationOptions;
+use builder::passes::run_pre_optimization;
+use builder::{soft_float, CairoBuilder, CairoModule};
+use cost::trip_counts::loop_trip_counts;
+use cost::{CostOptions, CostReport};
 use inkwell::context::Context;
 use inkwell::memory_buffer::MemoryBuffer;
+use inkwell::module::Module;
+use package::ScarbPackage;
 
+pub mod ast;
 pub mod builder;
+pub mod cost;
+pub mod optimizer;
+pub mod package;
+pub mod source_map;
+pub mod testing;
 
-pub fn compile(path: &str) -> CairoFunctions {
+pub fn compile(path: &str) -> Result<CairoModule, String> {
+    compile_with_options(path, TranslationOptions::default())
+}
+
+/// Parse the LLVM IR file at `path`.
+fn parse_module<'ctx>(context: &'ctx Context, path: &str) -> Result<Module<'ctx>, String> {
+    let buffer =
+        MemoryBuffer::create_from_file(Path::new(path)).map_err(|error| format!("Failed to load {path}: {error}"))?;
+    context.create_module_from_ir(buffer).map_err(|error| format!("Failed to parse {path}: {error}"))
+}
+
+/// Translate the LLVM IR file at `path` to cairo with custom translation options.
+pub fn compile_with_options(path: &str, options: TranslationOptions) -> Result<CairoModule, String> {
     // Initialize LLVM context
     let context = Context::create();
     // Parse the LLVM IR
-    let module = context
-        .create_module_from_ir(MemoryBuffer::create_from_file(Path::new(path)).expect("Failed to load llvm file"))
-        .expect("Failed to parse LLVM IR");
+    let module = parse_module(&context, path)?;
+    compile_module(&module, options)
+}
+
+/// Estimate the steps, range checks and gas used by each function of the LLVM IR file at `path`
+/// once translated with these options.
+pub fn estimate_cost(
+    path: &str,
+    options: TranslationOptions,
+    cost_options: &CostOptions,
+) -> Result<CostReport, String> {
+    let context = Context::create();
+    let module = parse_module(&context, path)?;
+    let cairo_module = compile_module(&module, options)?;
+    // The trip counts are read after the translation as the LLVM passes can change the loops.
+    Ok(cost::estimate(cairo_module.functions(), &loop_trip_counts(&module), cost_options))
+}
 
+/// Translate LLVM IR files to a Scarb package with one cairo module per file. The modules are
+/// named after the files.
+pub fn compile_package(
+    name: &str,
+    paths: &[&str],
+    options: TranslationOptions,
+    format: FormatOptions,
+) -> Result<ScarbPackage, String> {
+    let context = Context::create();
+    let modules = paths.iter().map(|path| parse_module(&context, path)).collect::<Result<Vec<_>, _>>()?;
+    let mut options = options;
+    // The functions called from another module are entry points as well.
+    if !options.entrypoints.is_empty() {
+        options.entrypoints.extend(modules.iter().flat_map(declared_functions));
+    }
+    let mut package = ScarbPackage::new(name, format);
+    for (path, module) in paths.iter().zip(modules.iter()) {
+        let module_name = Path::new(path).file_stem().and_then(|stem| stem.to_str()).unwrap_or(path);
+        package.add_module(module_name, compile_module(module, options.clone())?);
+    }
+    Ok(package)
+}
+
+/// Link the LLVM modules into the first one, like the LLVM linker does with the codegen units of a
+/// crate and its dependencies: the calls to a function declared in one module and defined in
+/// another are resolved, the `linkonce_odr`/`weak` definitions found in several modules are kept
+/// once and the colliding internal symbols are renamed. Fails if two modules define the same
+/// symbol.
+pub fn link_modules<'ctx>(modules: Vec<Module<'ctx>>) -> Result<Module<'ctx>, String> {
+    let mut modules = modules.into_iter();
+    let linked = modules.next().ok_or_else(|| "There is no module to link".to_owned())?;
+    for module in modules {
+        let name = module.get_name().to_string_lossy().into_owned();
+        linked.link_in_module(module).map_err(|error| format!("Failed to link {name}: {error}"))?;
+    }
+    Ok(linked)
+}
+
+/// Parse the LLVM IR files at `paths` and link them in one module, see [link_modules].
+fn load_linked_module<'ctx>(context: &'ctx Context, paths: &[&str]) -> Result<Module<'ctx>, String> {
+    let modules = paths.iter().map(|path| parse_module(context, path)).collect::<Result<Vec<_>, _>>()?;
+    link_modules(modules)
+}
+
+/// Translate several LLVM IR files (the codegen units of a crate and its dependencies) to one cairo
+/// module by linking them first.
+pub fn compile_linked(paths: &[&str], options: TranslationOptions) -> Result<CairoModule, String> {
+    let context = Context::create();
+    compile_module(&load_linked_module(&context, paths)?, options)
+}
+
+/// Estimate the cost of the functions of several LLVM IR files linked together, see
+/// [estimate_cost] and [compile_linked].
+pub fn estimate_linked_cost(
+    paths: &[&str],
+    options: TranslationOptions,
+    cost_options: &CostOptions,
+) -> Result<CostReport, String> {
+    let context = Context::create();
+    let module = load_linked_module(&context, paths)?;
+    let cairo_module = compile_module(&module, options)?;
+    Ok(cost::estimate(cairo_module.functions(), &loop_trip_counts(&module), cost_options))
+}
+
+/// Link LLVM IR files and translate them to a Scarb package with a single module named after the
+/// package, see [compile_linked].
+pub fn compile_linked_package(
+    name: &str,
+    paths: &[&str],
+    options: TranslationOptions,
+    format: FormatOptions,
+) -> Result<ScarbPackage, String> {
+    let mut package = ScarbPackage::new(name, format);
+    let module_name = package.name().to_owned();
+    package.add_module(&module_name, compile_linked(paths, options)?);
+    Ok(package)
+}
+
+/// Symbols of the functions that are declared in `module` but defined elsewhere.
+fn declared_functions(module: &Module) -> Vec<String> {
+    module
+        .get_functions()
+        .filter(|function| function.count_basic_blocks() == 0)
+        .map(|function| function.get_name().to_string_lossy().into_owned())
+        .collect()
+}
+
+/// Translate an already parsed LLVM module to cairo. Fails if the LLVM passes can't run, a function
+/// loads or stores a type the memory doesn't support or the contract can't be built (an exported
+/// function has a type that can't cross the ABI boundary).
+pub fn compile_module(module: &Module, options: TranslationOptions) -> Result<CairoModule, String> {
+    // Normalize the IR first if asked to.
+    run_pre_optimization(module, &options.pre_optimization)
+        .map_err(|error| format!("Failed to run the LLVM passes: {error}"))?;
     // Create a cairo builder that will hold all the translated code.
-    let mut builder = CairoBuilder::default();
-    // For each function on the llvm file translate it to cairo. Append the code to our file.
-    module.get_functions().for_each(|func| {
-        let translated_func = builder.translate_function(&func);
+    let mut builder = CairoBuilder::new(options);
+    // Translate the named struct types first so functions can reference them.
+    builder.translate_struct_types(module);
+    builder.translate_debug_metadata(module);
+    builder.translate_data_layout(module);
+    builder.translate_function_names(module);
+    // For each function on the llvm file translate it to cairo. Append the code to our file. Declared
+    // functions (intrinsics, rust runtime...) don't have a body, their calls are lowered directly. The
+    // callers come before their callees.
+    let call_graph = CallGraph::new(module);
+    for func in call_graph.emission_order() {
+        let translated_func = builder.translate_function(&func)?;
         builder.functions.push_function(translated_func);
-    });
-    builder.functions
+    }
+    builder.flag_recursive_functions(&call_graph);
+    // Wrap the functions in a starknet contract if asked to.
+    let mut contract = builder.translate_contract(module)?;
+    // Only the entry points are public, and the functions they don't use are removed.
+    builder.apply_visibility(module, contract.as_ref());
+    // The functions that allocate or access the memory get it.
+    let uses_allocator = builder.thread_memory(contract.as_mut());
+    let float_mode = builder.options.float_mode;
+    let mut cairo_module = CairoModule::new(builder.structs, builder.functions);
+    if let Some(contract) = contract {
+        cairo_module.set_contract(contract);
+    }
+    if uses_allocator {
+        cairo_module.set_allocator();
+    }
+    // Cairo doesn't have floats, emit the library that emulates them.
+    if soft_float::uses_floats(module) {
+        cairo_module.set_float_library(float_mode);
+    }
+    Ok(cairo_module)
 }
 
 #[cfg(test)]
 mod tests {
-    use builder::function::{CairoFunctionBody, CairoFunctionSignature, CairoParameter};
+    use ast::printer::FormatOptions;
+    use ast::BinaryOperator;
+    use builder::arithmetic::ArithmeticHelper;
+    use builder::function::{CairoFunctionSignature, CairoParameter};
+    use builder::options::{ArithmeticMode, ContractOptions, FloatMode, PreOptimization};
+    use builder::types::CairoStruct;
+    use cost::CostOptions;
+    use source_map::DebugLocation;
 
     use super::*;
 
     #[test]
     fn it_compiles() {
-        println!("Cairo code:\n{}", compile("examples/fib/fib.ll"));
+        println!("Cairo code:\n{}", compile("examples/fib/fib.ll").unwrap());
     }
 
     #[test]
@@ -43,7 +211,7 @@ mod tests {
             CairoParameter::new("left".to_owned(), "i64".to_owned()),
             CairoParameter::new("right".to_owned(), "i64".to_owned()),
         ];
-        let code = compile("examples/add/add.ll");
+        let code = compile("examples/add/add.ll").unwrap();
 
         // Check number of functions generated
         assert_eq!(code.count_functions(), 1, "Add function should generate exactly 1 function");
@@ -55,9 +223,25 @@ mod tests {
         );
 
         // Check function body
+        assert_eq!(function.body.to_string(), ["let _0 = right + left;", "return _0;"].join("\n"));
+    }
+
+    #[test]
+    fn test_format_options() {
+        let code = compile("examples/add/add.ll").unwrap();
+        // The signature doesn't fit in 30 characters so the parameters are on their own lines.
         assert_eq!(
-            function.body,
-            CairoFunctionBody::new(vec!["let _0 = right + left;".to_owned(), "return _0;".to_owned()])
+            code.format(FormatOptions { indent_width: 2, max_line_length: 30, source_comments: false }),
+            [
+                "pub fn add(",
+                "  left: i64,",
+                "  right: i64,",
+                ") -> i64 {",
+                "  let _0 = right + left;",
+                "  return _0;",
+                "}"
+            ]
+            .join("\n")
         );
     }
 
@@ -66,7 +250,7 @@ mod tests {
         let expected_name = "increment".to_owned();
         let expected_return_type = "i128".to_owned();
         let expected_params = vec![CairoParameter::new("left".to_owned(), "i128".to_owned())];
-        let code = compile("examples/increment/increment.ll");
+        let code = compile("examples/increment/increment.ll").unwrap();
 
         // Check number of functions generated
         assert_eq!(code.count_functions(), 1, "Add function should generate exactly 1 function");
@@ -79,11 +263,8 @@ mod tests {
 
         // Check function body
         assert_eq!(
-            function.body,
-            CairoFunctionBody::new(vec![
-                "let _0 = left + 170141183460469231731687303715884105727_i128;".to_owned(),
-                "return _0;".to_owned()
-            ])
+            function.body.to_string(),
+            ["let _0 = left + 170141183460469231731687303715884105727_i128;", "return _0;"].join("\n")
         );
     }
 
@@ -96,7 +277,7 @@ mod tests {
             CairoParameter::new("b".to_owned(), "i128".to_owned()),
             CairoParameter::new("n".to_owned(), "i128".to_owned()),
         ];
-        let code = compile("examples/fib/fib.ll");
+        let code = compile("examples/fib/fib.ll").unwrap();
 
         // Check number of functions generated
         assert_eq!(code.count_functions(), 1, "Add function should generate exactly 1 function");
@@ -109,47 +290,1941 @@ mod tests {
 
         // Check function body
         assert_eq!(
-            function.body,
-            CairoFunctionBody::new(vec![
-                "let mut is_from_start = false;".to_owned(),
-                "let mut is_from_bb2 = false;".to_owned(),
-                "let var3 = n == 0_i128;".to_owned(),
-                "is_from_start = true;".to_owned(),
-                "is_from_bb2 = false;".to_owned(),
-                "let mut n_tr3 = 0_i128;".to_owned(),
-                "let mut b_tr2 = 0_i128;".to_owned(),
-                "let mut a_tr1 = 0_i128;".to_owned(),
-                "let mut _4 = 0_i128;".to_owned(),
-                "let mut _5 = 0_i128;".to_owned(),
-                "let mut var9 = false;".to_owned(),
-                "if !var3 {".to_owned(),
-                "loop {".to_owned(),
-                "let n_tr3 = if is_from_bb2 { _5 } else if is_from_start { n } else { panic!(\"There is a bug in the \
-                 compiler at var n_tr3 please report it\")};"
-                    .to_owned(),
-                "let b_tr2_temp = if is_from_bb2 { _4 } else if is_from_start { b } else { panic!(\"There is a bug in \
-                 the compiler at var b_tr2_temp please report it\")};"
-                    .to_owned(),
-                "let a_tr1 = if is_from_bb2 { b_tr2 } else if is_from_start { a } else { panic!(\"There is a bug in \
-                 the compiler at var a_tr1 please report it\")};"
-                    .to_owned(),
-                "_4 = b_tr2_temp + a_tr1;".to_owned(),
-                "_5 = n_tr3 + -1_i128;".to_owned(),
-                "var9 = _5 == 0_i128;".to_owned(),
-                "b_tr2 = b_tr2_temp;".to_owned(),
-                "is_from_start = false;".to_owned(),
-                "is_from_bb2 = true;".to_owned(),
-                "if var9".to_owned(),
-                "{break;}".to_owned(),
-                "};".to_owned(),
-                "let mut a_tr_lcssa = 0_i128;".to_owned(),
-                "}".to_owned(),
-                "let a_tr_lcssa = if is_from_start { a } else if is_from_bb2 { b_tr2 } else { panic!(\"There is a bug \
-                 in the compiler at var a_tr_lcssa please report it\")};"
-                    .to_owned(),
-                "return a_tr_lcssa;".to_owned(),
-                "".to_owned(),
-            ]),
+            function.body.to_string(),
+            [
+                "let mut is_from_start = false;",
+                "let mut is_from_bb2 = false;",
+                "let var0 = n == 0_i128;",
+                "is_from_start = true;",
+                "is_from_bb2 = false;",
+                "let mut n_tr3 = 0_i128;",
+                "let mut b_tr2 = 0_i128;",
+                "let mut a_tr1 = 0_i128;",
+                "let mut _4 = 0_i128;",
+                "let mut _5 = 0_i128;",
+                "let mut var1 = false;",
+                "if !var0 {",
+                "    loop {",
+                "        let n_tr3 = if is_from_bb2 {",
+                "            _5",
+                "        } else if is_from_start {",
+                "            n",
+                "        } else {",
+                "            panic!(\"There is a bug in the compiler at var n_tr3 please report it\")",
+                "        };",
+                "        let b_tr2_temp = if is_from_bb2 {",
+                "            _4",
+                "        } else if is_from_start {",
+                "            b",
+                "        } else {",
+                "            panic!(\"There is a bug in the compiler at var b_tr2_temp please report it\")",
+                "        };",
+                "        let a_tr1 = if is_from_bb2 {",
+                "            b_tr2",
+                "        } else if is_from_start {",
+                "            a",
+                "        } else {",
+                "            panic!(\"There is a bug in the compiler at var a_tr1 please report it\")",
+                "        };",
+                "        _4 = b_tr2_temp + a_tr1;",
+                "        _5 = n_tr3 + -1_i128;",
+                "        var1 = _5 == 0_i128;",
+                "        b_tr2 = b_tr2_temp;",
+                "        is_from_start = false;",
+                "        is_from_bb2 = true;",
+                "        if var1 {",
+                "            break;",
+                "        }",
+                "    };",
+                "    let mut a_tr_lcssa = 0_i128;",
+                "}",
+                "let a_tr_lcssa = if is_from_start {",
+                "    a",
+                "} else if is_from_bb2 {",
+                "    b_tr2",
+                "} else {",
+                "    panic!(\"There is a bug in the compiler at var a_tr_lcssa please report it\")",
+                "};",
+                "return a_tr_lcssa;",
+            ]
+            .join("\n"),
+        );
+    }
+
+    /// Parse an LLVM module from a string and translate it.
+    fn compile_ir(ir: &str) -> CairoModule {
+        compile_ir_with_options(ir, TranslationOptions::default())
+    }
+
+    fn compile_ir_with_options(ir: &str, options: TranslationOptions) -> CairoModule {
+        let context = Context::create();
+        let module = context
+            .create_module_from_ir(MemoryBuffer::create_from_memory_range_copy(ir.as_bytes(), "test"))
+            .expect("Failed to parse LLVM IR");
+        compile_module(&module, options).unwrap()
+    }
+
+    #[test]
+    fn test_named_structs() {
+        let code = compile_ir(
+            r#"
+%"core::option::Option<u64>" = type { i64, [1 x i64] }
+%Pair = type { i32, %"core::option::Option<u64>", i1 }
+
+define i32 @second(%Pair %pair, i32 %value) {
+start:
+  ret i32 %value
+}
+"#,
+        );
+
+        assert_eq!(
+            code.structs().structs(),
+            &[
+                CairoStruct::new("core_option_Option_u64".to_owned(), vec!["i64".to_owned(), "[i64; 1]".to_owned()]),
+                CairoStruct::new(
+                    "Pair".to_owned(),
+                    vec!["i32".to_owned(), "core_option_Option_u64".to_owned(), "bool".to_owned()]
+                ),
+            ]
+        );
+        assert_eq!(
+            code.functions().first().unwrap().signature,
+            CairoFunctionSignature::new(
+                "second".to_owned(),
+                vec![
+                    CairoParameter::new("pair".to_owned(), "Pair".to_owned()),
+                    CairoParameter::new("value".to_owned(), "i32".to_owned())
+                ],
+                "i32".to_owned()
+            )
+        );
+    }
+
+    #[test]
+    fn test_colliding_struct_names() {
+        let code = compile_ir(
+            r#"
+%"core::option::Option<u64>" = type { i64, [1 x i64] }
+%core_option_Option_u64 = type { i32 }
+
+define i32 @first(%"core::option::Option<u64>" %option, %core_option_Option_u64 %other) {
+start:
+  ret i32 0
+}
+"#,
+        );
+
+        // Both structs sanitize to the same name but they're different types.
+        assert_eq!(
+            code.structs().structs(),
+            &[
+                CairoStruct::new("core_option_Option_u64".to_owned(), vec!["i64".to_owned(), "[i64; 1]".to_owned()]),
+                CairoStruct::new("core_option_Option_u64_1".to_owned(), vec!["i32".to_owned()]),
+            ]
+        );
+        let parameters = &code.functions()[0].signature.parameters.0;
+        assert_eq!(parameters[0].ty, "core_option_Option_u64");
+        assert_eq!(parameters[1].ty, "core_option_Option_u64_1");
+
+        // Nor do they collide with the structs of the allocator and the float library.
+        let code = compile_ir(
+            r#"
+%Memory = type { i64 }
+%F32 = type { i32 }
+
+define i32 @get(%Memory %memory, %F32 %float) {
+start:
+  ret i32 0
+}
+"#,
+        );
+        let names = code.structs().structs().iter().map(|cairo_struct| cairo_struct.name.clone()).collect::<Vec<_>>();
+        assert_eq!(names, ["Memory_1", "F32_1"]);
+    }
+
+    #[test]
+    fn test_vector_add() {
+        let code = compile_ir(
+            r#"
+define <4 x i32> @add_vectors(<4 x i32> %a, <4 x i32> %b) {
+start:
+  %res = add <4 x i32> %a, %b
+  ret <4 x i32> %res
+}
+"#,
+        );
+        let function = code.functions().first().unwrap();
+        assert_eq!(function.signature.return_type, "[i32; 4]");
+        assert_eq!(
+            function.body.to_string(),
+            [
+                "let [a_0, a_1, a_2, a_3] = a;",
+                "let [b_0, b_1, b_2, b_3] = b;",
+                "let res = [a_0 + b_0, a_1 + b_1, a_2 + b_2, a_3 + b_3];",
+                "return res;"
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn test_vector_elements() {
+        let code = compile_ir(
+            r#"
+define i8 @umax(<2 x i8> %v) {
+start:
+  %m = call i8 @llvm.vector.reduce.umax.v2i8(<2 x i8> %v)
+  ret i8 %m
+}
+
+define <2 x ptr> @insert(<2 x ptr> %v, ptr %p) {
+start:
+  %r = insertelement <2 x ptr> %v, ptr %p, i64 1
+  ret <2 x ptr> %r
+}
+
+declare i8 @llvm.vector.reduce.umax.v2i8(<2 x i8>)
+"#,
+        );
+
+        // The negative values are the biggest unsigned ones.
+        assert_eq!(
+            code.functions()[0].body.to_string(),
+            [
+                "let [v_0, v_1] = v;",
+                "let m_less = if v_0 < 0_i8 { v_1 < 0_i8 && v_0 < v_1 } else { v_1 < 0_i8 || v_0 < v_1 };",
+                "let m = if m_less { v_1 } else { v_0 };",
+                "return m;"
+            ]
+            .join("\n")
+        );
+        assert_eq!(
+            code.functions()[1].body.to_string(),
+            ["let [v_0, v_1] = v;", "let r = [v_0, p];", "return r;"].join("\n")
+        );
+    }
+
+    #[test]
+    fn test_undef_and_poison() {
+        let ir = r#"
+define i32 @frozen() {
+start:
+  %x = freeze i32 undef
+  ret i32 %x
+}
+
+define i32 @poisoned() {
+start:
+  ret i32 poison
+}
+"#;
+        let code = compile_ir(ir);
+        assert_eq!(code.functions()[0].body.to_string(), ["let x = 0_i32;", "return x;"].join("\n"));
+        assert_eq!(code.functions()[1].body.to_string(), "return 0_i32;");
+
+        let code = compile_ir_with_options(ir, TranslationOptions { poison_checks: true, ..Default::default() });
+        assert_eq!(
+            code.functions()[1].body.to_string(),
+            ["panic!(\"Poison value reached an observable use: return value\");", "return 0_i32;"].join("\n")
+        );
+    }
+
+    #[test]
+    fn test_undef_and_poison_branches() {
+        let ir = r#"
+define i32 @spin() {
+start:
+  br label %loop
+
+loop:
+  %i = phi i32 [ 0, %start ], [ %i.next, %loop ]
+  %i.next = add i32 %i, 1
+  br i1 poison, label %end, label %loop
+
+end:
+  ret i32 %i.next
+}
+
+define void @choose() {
+start:
+  br i1 undef, label %left, label %right
+
+left:
+  br label %end
+
+right:
+  br label %end
+
+end:
+  ret void
+}
+"#;
+        // The conditions don't have a variable, they're the default value.
+        let code = compile_ir(ir);
+        assert!(code.functions()[0].body.to_string().contains("if false {"));
+        assert!(code.functions()[1].body.to_string().contains("if !false {"));
+
+        let code = compile_ir_with_options(ir, TranslationOptions { poison_checks: true, ..Default::default() });
+        assert!(code.functions()[0]
+            .body
+            .to_string()
+            .contains("panic!(\"Poison value reached an observable use: branch condition\");"));
+        // `undef` isn't poison.
+        assert!(!code.functions()[1].body.to_string().contains("Poison value"));
+    }
+
+    #[test]
+    fn test_rust_panic() {
+        let code = compile_ir(
+            r#"
+@alloc_file = private unnamed_addr constant <{ [10 x i8] }> <{ [10 x i8] c"src/lib.rs" }>, align 1
+@alloc_loc = private unnamed_addr constant <{ ptr, [16 x i8] }> <{ ptr @alloc_file, [16 x i8] c"\0A\00\00\00\00\00\00\00\03\00\00\00\05\00\00\00" }>, align 8
+
+define void @overflow() {
+start:
+  tail call void @_ZN4core9panicking11panic_const24panic_const_add_overflow17h0123456789abcdefE(ptr @alloc_loc)
+  unreachable
+}
+
+declare void @_ZN4core9panicking11panic_const24panic_const_add_overflow17h0123456789abcdefE(ptr)
+"#,
+        );
+
+        assert_eq!(code.count_functions(), 1, "Declared functions shouldn't be translated");
+        assert_eq!(
+            code.functions()[0].body.to_string(),
+            ["panic!(\"src/lib.rs:3:5: attempt to add with overflow\");", "panic!(\"Entered unreachable code\");"]
+                .join("\n")
+        );
+    }
+
+    #[test]
+    fn test_rust_panic_v0_mangling() {
+        let code = compile_ir(
+            r#"
+define void @overflow() {
+start:
+  tail call void @_RNvNtNtCs1234_4core9panicking11panic_const24panic_const_sub_overflow()
+  unreachable
+}
+
+declare void @_RNvNtNtCs1234_4core9panicking11panic_const24panic_const_sub_overflow()
+"#,
+        );
+
+        assert_eq!(
+            code.functions()[0].body.to_string(),
+            ["panic!(\"attempt to subtract with overflow\");", "panic!(\"Entered unreachable code\");"].join("\n")
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Unsupported LLVM intrinsic llvm.ctpop.i32")]
+    fn test_unsupported_intrinsic() {
+        compile_ir(
+            r#"
+define i32 @count(i32 %a) {
+start:
+  %res = call i32 @llvm.ctpop.i32(i32 %a)
+  ret i32 %res
+}
+
+declare i32 @llvm.ctpop.i32(i32)
+"#,
+        );
+    }
+
+    #[test]
+    fn test_generated_names_are_reserved() {
+        let code = compile_ir_with_options(
+            r#"
+define i32 @wrapping_add_i32(i32 %a, i32 %b) {
+start:
+  %res = add i32 %a, %b
+  ret i32 %res
+}
+
+define i32 @rust_alloc(i32 %a) {
+start:
+  %res = call i32 @wrapping_add_i32(i32 %a, i32 %a)
+  ret i32 %res
+}
+"#,
+            TranslationOptions { arithmetic_mode: ArithmeticMode::Wrapping, ..Default::default() },
+        );
+
+        let names = code.functions().iter().map(|function| function.signature.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, ["wrapping_add_i32_1", "rust_alloc_1"]);
+        assert_eq!(code.functions()[0].body.to_string(), "let res = wrapping_add_i32(a, b);\nreturn res;");
+        assert_eq!(code.functions()[1].body.to_string(), "let res = wrapping_add_i32_1(a, a);\nreturn res;");
+    }
+
+    #[test]
+    fn test_scarb_package() {
+        let math = compile_ir(
+            r#"
+define i32 @add(i32 %a, i32 %b) {
+start:
+  %res = add i32 %a, %b
+  ret i32 %res
+}
+"#,
+        );
+        let main = compile_ir(
+            r#"
+define i32 @double(i32 %a) {
+start:
+  %res = call i32 @add(i32 %a, i32 %a)
+  ret i32 %res
+}
+
+declare i32 @add(i32, i32)
+"#,
+        );
+        let mut package = ScarbPackage::new("My-Program", FormatOptions::default());
+        package.add_module("math", math.clone());
+        package.add_module("main", main.clone());
+
+        let files = package.files();
+        let file = |path: &str| files.iter().find(|(file, _)| file == Path::new(path)).unwrap().1.clone();
+        assert_eq!(
+            file("Scarb.toml"),
+            "[package]\nname = \"my_program\"\nversion = \"0.1.0\"\nedition = \"2024_07\"\n\n[dependencies]\n"
+        );
+        assert_eq!(file("src/lib.cairo"), "mod math;\nmod main;\n");
+        // The function defined in the other module is imported.
+        assert_eq!(
+            file("src/main.cairo"),
+            [
+                "use my_program::math::add;",
+                "",
+                "pub fn double(a: i32) -> i32 {",
+                "    let res = add(a, a);",
+                "    return res;",
+                "}",
+                ""
+            ]
+            .join("\n")
+        );
+
+        // The module names are unique and don't take the names of the libraries.
+        let mut package = ScarbPackage::new("names", FormatOptions::default());
+        for name in ["math", "math_2", "math", "allocator"] {
+            package.add_module(name, CairoModule::default());
+        }
+        let names = package.modules().iter().map(|(name, _)| name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, ["math", "math_2", "math_1", "allocator_1"]);
+
+        // `add` is defined in two modules so the one `main` calls is ambiguous.
+        let mut package = ScarbPackage::new("ambiguous", FormatOptions::default());
+        package.add_module("math", math.clone());
+        package.add_module("other_math", math);
+        package.add_module("main", main);
+        let files = package.files();
+        let main_cairo = files.iter().find(|(file, _)| file == Path::new("src/main.cairo")).unwrap();
+        assert!(!main_cairo.1.contains("use "));
+    }
+
+    #[test]
+    fn test_source_map() {
+        let code = compile_ir(
+            r#"
+define i32 @add(i32 %a, i32 %b) !dbg !5 {
+start:
+  %res = add i32 %a, %b, !dbg !8
+  ret i32 %res, !dbg !9
+}
+
+!llvm.dbg.cu = !{!0}
+!llvm.module.flags = !{!2}
+
+!0 = distinct !DICompileUnit(language: DW_LANG_Rust, file: !1, producer: "rustc", isOptimized: true, runtimeVersion: 0, emissionKind: FullDebug)
+!1 = !DIFile(filename: "src/lib.rs", directory: "/tmp/add")
+!2 = !{i32 2, !"Debug Info Version", i32 3}
+!5 = distinct !DISubprogram(name: "add", scope: !1, file: !1, line: 1, type: !6, scopeLine: 1, spFlags: DISPFlagDefinition, unit: !0)
+!6 = !DISubroutineType(types: !7)
+!7 = !{}
+!8 = !DILocation(line: 2, column: 5, scope: !5)
+!9 = !DILocation(line: 3, column: 2, scope: !5)
+"#,
+        );
+        let options = FormatOptions { source_comments: true, ..Default::default() };
+        let (cairo, source_map) = code.format_with_source_map(options, "add.cairo");
+        assert_eq!(
+            cairo,
+            [
+                "pub fn add(a: i32, b: i32) -> i32 {",
+                "    // add::start: %res = add i32 %a, %b (src/lib.rs:2:5)",
+                "    let res = a + b;",
+                "    // add::start: ret i32 %res (src/lib.rs:3:2)",
+                "    return res;",
+                "}"
+            ]
+            .join("\n")
+        );
+        assert_eq!(source_map.entries.len(), 2);
+        let origin = source_map.origin(3).expect("The addition should have an origin");
+        assert_eq!(origin.instruction, "%res = add i32 %a, %b");
+        assert_eq!(origin.location, Some(DebugLocation { file: "src/lib.rs".to_owned(), line: 2, column: 5 }));
+        assert_eq!(source_map.origin(5).unwrap().instruction, "ret i32 %res");
+        // The comments are the only difference when they're disabled.
+        assert_eq!(code.format_with_source_map(FormatOptions::default(), "add.cairo").0, code.to_string());
+    }
+
+    #[test]
+    fn test_debug_names() {
+        let code = compile_ir(
+            r#"
+define i32 @add(i32 %0, i32 %1) !dbg !5 {
+start:
+  call void @llvm.dbg.value(metadata i32 %0, metadata !8, metadata !DIExpression()), !dbg !12
+  call void @llvm.dbg.value(metadata i32 %1, metadata !9, metadata !DIExpression()), !dbg !12
+  %2 = add i32 %1, %0, !dbg !12
+  call void @llvm.dbg.value(metadata i32 %2, metadata !10, metadata !DIExpression()), !dbg !12
+  ret i32 %2, !dbg !12
+}
+
+declare void @llvm.dbg.value(metadata, metadata, metadata)
+
+!llvm.dbg.cu = !{!0}
+!llvm.module.flags = !{!2}
+
+!0 = distinct !DICompileUnit(language: DW_LANG_Rust, file: !1, producer: "rustc", isOptimized: true, runtimeVersion: 0, emissionKind: FullDebug)
+!1 = !DIFile(filename: "src/lib.rs", directory: "/tmp/add")
+!2 = !{i32 2, !"Debug Info Version", i32 3}
+!5 = distinct !DISubprogram(name: "add", scope: !1, file: !1, line: 1, type: !6, scopeLine: 1, spFlags: DISPFlagDefinition, unit: !0, retainedNodes: !7)
+!6 = !DISubroutineType(types: !7)
+!7 = !{}
+!8 = !DILocalVariable(name: "left", arg: 1, scope: !5, file: !1, line: 1, type: !11)
+!9 = !DILocalVariable(name: "right", arg: 2, scope: !5, file: !1, line: 1, type: !11)
+!10 = !DILocalVariable(name: "sum", scope: !5, file: !1, line: 2, type: !11)
+!11 = !DIBasicType(name: "i32", size: 32, encoding: DW_ATE_signed)
+!12 = !DILocation(line: 2, column: 5, scope: !5)
+"#,
+        );
+        let function = code.functions().first().unwrap();
+        assert_eq!(function.signature.to_string(), "pub fn add(left: i32, right: i32) -> i32");
+        assert_eq!(function.body.to_string(), ["let sum = right + left;", "return sum;"].join("\n"));
+    }
+
+    #[test]
+    fn test_identifiers() {
+        let code = compile_ir(
+            r#"
+define i32 @_ZN7mycrate4math3add17h0123456789abcdefE(i32 %match, i32 %"x$y") {
+start:
+  %var0 = add i32 %match, %"x$y"
+  %0 = add i32 %var0, 1
+  ret i32 %0
+}
+
+define i32 @caller(i32 %a) {
+start:
+  %res = call i32 @_ZN7mycrate4math3add17h0123456789abcdefE(i32 %a, i32 %a)
+  ret i32 %res
+}
+"#,
+        );
+        let add = &code.functions()[0];
+        assert_eq!(add.signature.to_string(), "pub fn mycrate_math_add(match_: i32, x_y: i32) -> i32");
+        // The unnamed value doesn't reuse the name of `%var0`.
+        assert_eq!(
+            add.body.to_string(),
+            ["let var0 = match_ + x_y;", "let var0_1 = var0 + 1_i32;", "return var0_1;"].join("\n")
+        );
+        assert_eq!(
+            code.functions()[1].body.to_string(),
+            ["let res = mycrate_math_add(a, a);", "return res;"].join("\n")
+        );
+    }
+
+    /// Run the examples in LLVM and in cairo and compare the results. It needs `scarb` and the
+    /// examples generated with `scripts/generate_llvm.sh`.
+    #[test]
+    #[ignore = "needs scarb, lli and the generated examples"]
+    fn test_differential_examples() {
+        use testing::differential::{differential_test, DifferentialConfig};
+
+        for example in ["add", "increment", "fib"] {
+            let config = DifferentialConfig {
+                directory: std::env::temp_dir().join(format!("llvm-to-cairo-differential-{example}")),
+                ..Default::default()
+            };
+            let mismatches = differential_test(&format!("examples/{example}/{example}.ll"), &config).unwrap();
+            assert!(
+                mismatches.is_empty(),
+                "{}",
+                mismatches.iter().map(ToString::to_string).collect::<Vec<_>>().join("\n")
+            );
+        }
+    }
+
+    #[test]
+    fn test_differential_parsing() {
+        use testing::differential::{felt_bits, parse_cairo_output, parse_llvm_output, Outcome};
+
+        // Negative values are printed as `P - x`.
+        let minus_one = "3618502788666131213697322783095070105623107215331596699973092056135872020480";
+        let minus_five = "3618502788666131213697322783095070105623107215331596699973092056135872020476";
+        assert_eq!(felt_bits("55"), Some(55));
+        assert_eq!(felt_bits("0x37"), Some(55));
+        assert_eq!(felt_bits(minus_one), Some(u128::MAX));
+        assert_eq!(felt_bits(minus_five), Some(-5_i128 as u128));
+        assert_eq!(felt_bits("12a"), None);
+
+        assert_eq!(parse_cairo_output("Run completed successfully, returning [55]", Some(32)), Ok(Outcome::Value(55)));
+        assert_eq!(
+            parse_cairo_output(&format!("Run completed successfully, returning [{minus_one}]"), Some(8)),
+            Ok(Outcome::Value(0xff))
+        );
+        assert_eq!(parse_cairo_output("Run completed successfully, returning []", None), Ok(Outcome::Unit));
+        assert_eq!(
+            parse_cairo_output("Run panicked with [39878429859757942499084499860145094553463, ].", Some(32)),
+            Ok(Outcome::Panic)
+        );
+        assert!(parse_cairo_output("error: could not compile `differential`", Some(32)).is_err());
+
+        assert_eq!(parse_llvm_output(Some(0), "0 55\n", Some(32)), Ok(Outcome::Value(55)));
+        assert_eq!(parse_llvm_output(Some(0), "1 2\n", Some(128)), Ok(Outcome::Value((1 << 64) | 2)));
+        assert_eq!(parse_llvm_output(Some(0), "0 0\n", None), Ok(Outcome::Unit));
+        assert_eq!(parse_llvm_output(Some(101), "", Some(32)), Ok(Outcome::Panic));
+        assert!(parse_llvm_output(None, "", Some(32)).is_err());
+    }
+
+    #[test]
+    fn test_differential_cases() {
+        use testing::differential::{generate_cases, DifferentialConfig, TestedFunction};
+
+        let function = |parameters: Vec<u32>| TestedFunction {
+            symbol: "f".to_owned(),
+            cairo_name: "f".to_owned(),
+            parameters,
+            return_width: None,
+        };
+        let config = DifferentialConfig { cases: 6, seed: 1, range: 0..=32, ..Default::default() };
+        let cases = generate_cases(&[function(vec![8, 1]), function(vec![])], &config);
+        assert_eq!(cases.len(), 12);
+        // The edge cases of the range come first, clamped to the type.
+        let inputs = cases.iter().filter(|case| case.function == 0).map(|case| case.inputs.clone()).collect::<Vec<_>>();
+        assert_eq!(inputs[..4], [vec![0, 0], vec![32, 1], vec![0, 0], vec![1, 1]]);
+        assert!(inputs.iter().all(|inputs| (0..=32).contains(&inputs[0]) && (0..=1).contains(&inputs[1])));
+        assert!(cases.iter().filter(|case| case.function == 1).all(|case| case.inputs.is_empty()));
+        // Same seed, same cases.
+        let again = generate_cases(&[function(vec![8, 1])], &config);
+        assert_eq!(again.iter().map(|case| case.inputs.clone()).collect::<Vec<_>>(), inputs);
+
+        // A range that doesn't overlap the type uses the whole type.
+        let config = DifferentialConfig { cases: 4, range: 200..=300, ..Default::default() };
+        let cases = generate_cases(&[function(vec![8])], &config);
+        assert_eq!(cases.iter().map(|case| case.inputs[0]).collect::<Vec<_>>(), [-128, 127, 0, 1]);
+    }
+
+    #[test]
+    fn test_fuzz_programs_are_valid() {
+        use testing::fuzz::{FuzzConfig, FuzzProgram};
+        use testing::Rng;
+
+        let config = FuzzConfig { max_blocks: 6, ..Default::default() };
+        for seed in 0..50 {
+            let program = FuzzProgram::generate(&mut Rng::new(seed), &config);
+            let context = Context::create();
+            program.build(&context).verify().unwrap_or_else(|error| panic!("{error}\n{}", program.to_ir()));
+        }
+    }
+
+    /// A failing program is only shrunk to programs that fail the same way.
+    #[test]
+    fn test_fuzz_failure_kinds() {
+        use std::path::PathBuf;
+
+        use testing::fuzz::{FuzzFailure, Reproducer};
+
+        let panic = FuzzFailure::Panic("attempt to subtract with overflow".to_owned());
+        assert!(panic.same_kind(&FuzzFailure::Panic("index out of bounds".to_owned())));
+        assert!(!panic.same_kind(&FuzzFailure::InvalidIr("Instruction does not dominate all uses!".to_owned())));
+        assert!(!panic.same_kind(&FuzzFailure::Mismatches(Vec::new())));
+
+        // The reproducer tells when it couldn't be saved.
+        let path = PathBuf::from("fuzz/fuzz_1_0.ll");
+        let reproducer = Reproducer { path, failure: panic, save_error: Some("Permission denied".to_owned()) };
+        assert_eq!(
+            reproducer.to_string(),
+            "the translator panicked: attempt to subtract with overflow (failed to save fuzz/fuzz_1_0.ll: Permission \
+             denied)"
+        );
+    }
+
+    /// Translate random functions, failing ones are shrunk and saved in `examples/fuzz`.
+    #[test]
+    fn test_fuzz_translator() {
+        if let Err(reproducer) = testing::fuzz::fuzz(&testing::fuzz::FuzzConfig::default()) {
+            panic!("{reproducer}");
+        }
+    }
+
+    /// Compare the translation of each example with its golden `.cairo` file. Run with
+    /// `UPDATE_SNAPSHOTS=1` to update them.
+    #[test]
+    fn test_examples_corpus() {
+        use testing::corpus::{discover, update_snapshots};
+
+        let errors = discover(Path::new("examples"))
+            .unwrap()
+            .iter()
+            .filter_map(|example| example.check(update_snapshots()).err())
+            .collect::<Vec<_>>();
+        assert!(errors.is_empty(), "{}", errors.join("\n\n"));
+    }
+
+    #[test]
+    fn test_discover_examples() {
+        use std::fs;
+
+        use testing::corpus::discover;
+
+        let directory = std::env::temp_dir().join("llvm-to-cairo-discover");
+        let _ = fs::remove_dir_all(&directory);
+        for (file, content) in [
+            ("fib/fib.rs", "pub fn fib() {}"),
+            ("fib/fib.ll", ""),
+            ("fuzz/fuzz_1_0.ll", ""),
+            ("fuzz/fuzz_1_0.cairo", ""),
+            ("fuzz/fuzz_2_3.ll", ""),
+            ("fuzz/README.md", ""),
+        ] {
+            fs::create_dir_all(directory.join(file).parent().unwrap()).unwrap();
+            fs::write(directory.join(file), content).unwrap();
+        }
+        let examples = discover(&directory).unwrap();
+        assert_eq!(
+            examples.iter().map(|example| example.name.as_str()).collect::<Vec<_>>(),
+            ["fib", "fuzz/fuzz_1_0", "fuzz/fuzz_2_3"]
         );
+        assert_eq!(examples[0].source, Some(directory.join("fib/fib.rs")));
+        assert_eq!(examples[1].source, None);
+        assert_eq!(examples[1].ir, directory.join("fuzz/fuzz_1_0.ll"));
+        assert_eq!(examples[1].golden, directory.join("fuzz/fuzz_1_0.cairo"));
+        // The reproducers are translated from their checked-in IR.
+        assert_eq!(examples[1].regenerate_ir(), Ok(false));
+    }
+
+    #[test]
+    fn test_pre_optimization() {
+        // Unoptimized IR keeps the values on the stack.
+        let ir = r#"
+define i32 @double(i32 %x) {
+start:
+  %slot = alloca i32
+  store i32 %x, ptr %slot
+  %v = load i32, ptr %slot
+  %r = add i32 %v, %v
+  ret i32 %r
+}
+"#;
+        let options = TranslationOptions { pre_optimization: PreOptimization::Minimal, ..Default::default() };
+        let code = compile_ir_with_options(ir, options);
+        assert_eq!(code.functions()[0].body.to_string(), ["let r = x + x;", "return r;"].join("\n"));
+        assert_eq!("aggressive".parse(), Ok(PreOptimization::Aggressive));
+        assert!("O3".parse::<PreOptimization>().is_err());
+
+        // Any pipeline can be run.
+        let pre_optimization = "custom:mem2reg".parse().unwrap();
+        assert_eq!(pre_optimization, PreOptimization::Custom("mem2reg".to_owned()));
+        let code = compile_ir_with_options(ir, TranslationOptions { pre_optimization, ..Default::default() });
+        assert_eq!(code.functions()[0].body.to_string(), ["let r = x + x;", "return r;"].join("\n"));
+        assert!("custom:".parse::<PreOptimization>().is_err());
+        let context = Context::create();
+        let module =
+            context.create_module_from_ir(MemoryBuffer::create_from_memory_range_copy(ir.as_bytes(), "test")).unwrap();
+        let pre_optimization = PreOptimization::Custom("not-a-pass".to_owned());
+        let error = compile_module(&module, TranslationOptions { pre_optimization, ..Default::default() }).unwrap_err();
+        assert!(error.starts_with("Failed to run the LLVM passes: "), "{error}");
+    }
+
+    #[test]
+    fn test_parse_arguments() {
+        let parse = |arguments: &[&str]| {
+            let mut options = TranslationOptions::default();
+            let mut exports = Vec::new();
+            let mut args = arguments.iter().map(|arg| arg.to_string());
+            while let Some(arg) = args.next() {
+                assert!(options.parse_argument(&arg, &mut args, &mut exports)?, "{arg} should be parsed");
+            }
+            options.set_exports(exports)?;
+            Ok::<_, String>(options)
+        };
+        let options = parse(&[
+            "--export",
+            "transfer",
+            "--contract",
+            "Token",
+            "--entrypoint",
+            "main",
+            "-O",
+            "--pre-opt=minimal",
+            "--arithmetic",
+            "checked",
+            "--float",
+            "fixed",
+        ])
+        .unwrap();
+        let expected = TranslationOptions {
+            contract: Some(ContractOptions { name: "Token".to_owned(), exports: vec!["transfer".to_owned()] }),
+            entrypoints: vec!["main".to_owned()],
+            optimize: true,
+            pre_optimization: PreOptimization::Minimal,
+            arithmetic_mode: ArithmeticMode::Checked,
+            float_mode: FloatMode::FixedPoint,
+            ..Default::default()
+        };
+        assert_eq!(options, expected);
+        assert_eq!(parse(&["--pre-opt", "aggressive"]).unwrap().pre_optimization, PreOptimization::Aggressive);
+        assert_eq!(parse(&["--float"]).unwrap_err(), "--float expects a mode");
+        assert!(parse(&["--arithmetic", "saturating"]).unwrap_err().starts_with("unknown arithmetic mode"));
+        assert_eq!(parse(&["--export", "f"]).unwrap_err(), "--export needs a contract, use --contract");
+
+        let mut args = std::iter::empty::<String>();
+        assert_eq!(TranslationOptions::default().parse_argument("--link", &mut args, &mut Vec::new()), Ok(false));
+    }
+
+    #[test]
+    fn test_optimize() {
+        let ir = r#"
+define i32 @f(i32 %x) {
+start:
+  %y = freeze i32 %x
+  %a = add i32 2, 3
+  %b = sub i32 %y, %a
+  %c = icmp ult i32 %a, 10
+  %d = zext i1 %c to i32
+  %r = add i32 %b, %d
+  ret i32 %r
+}
+"#;
+        let optimized = ["let b = x - 5_i32;", "let r = b + 1_i32;", "return r;"].join("\n");
+        let code = compile_ir_with_options(ir, TranslationOptions::default());
+        assert_ne!(code.functions()[0].body.to_string(), optimized);
+        let code = compile_ir_with_options(ir, TranslationOptions { optimize: true, ..Default::default() });
+        assert_eq!(code.functions()[0].body.to_string(), optimized);
+    }
+
+    /// The placeholder of a variable that is never read is removed, its assignments that can panic
+    /// are kept without the variable.
+    #[test]
+    fn test_remove_dead_stores() {
+        use ast::{Block, Expr, Statement};
+        use builder::function::{CairoFunction, CairoFunctionBody};
+
+        let sum = || Expr::binary(BinaryOperator::Add, Expr::variable("a"), Expr::variable("b"));
+        let body = Block::new(vec![
+            Statement::declare_mut("x", Expr::literal("0_i32")),
+            Statement::declare_mut("y", Expr::literal("0_i32")),
+            Statement::If {
+                condition: Expr::variable("c"),
+                then_block: Block::new(vec![Statement::assign("x", sum()), Statement::assign("y", sum())]),
+                else_block: None,
+            },
+            Statement::assign("x", Expr::variable("a")),
+            Statement::Return(Some(Expr::variable("y"))),
+        ]);
+        let parameters = vec![
+            CairoParameter::new("a".to_owned(), "i32".to_owned()),
+            CairoParameter::new("b".to_owned(), "i32".to_owned()),
+            CairoParameter::new("c".to_owned(), "bool".to_owned()),
+        ];
+        let mut function = CairoFunction {
+            signature: CairoFunctionSignature::new("f".to_owned(), parameters, "i32".to_owned()),
+            body: CairoFunctionBody::new(body),
+            ..Default::default()
+        };
+        optimizer::optimize_function(&mut function);
+        assert_eq!(
+            function.body.to_string(),
+            ["let mut y = 0_i32;", "if c {", "    a + b;", "    y = a + b;", "}", "return y;"].join("\n")
+        );
+    }
+
+    #[test]
+    fn test_starknet_contract() {
+        let ir = r#"
+define i32 @add(i32 %a, i32 %b) #0 {
+start:
+  %res = add i32 %a, %b
+  ret i32 %res
+}
+
+define i1 @is_zero(i32 %a) {
+start:
+  %c = icmp eq i32 %a, 0
+  ret i1 %c
+}
+
+define i1 @internal(i1 %a) {
+start:
+  ret i1 %a
+}
+
+attributes #0 = { "starknet-external" }
+"#;
+        let contract = ContractOptions { name: "math".to_owned(), exports: vec!["is_zero".to_owned()] };
+        let code = compile_ir_with_options(ir, TranslationOptions { contract: Some(contract), ..Default::default() });
+        assert_eq!(
+            code.to_string(),
+            [
+                "#[starknet::interface]",
+                "pub trait IMath<TContractState> {",
+                "    fn add(self: @TContractState, a: u32, b: u32) -> u32;",
+                "    fn is_zero(self: @TContractState, a: u32) -> bool;",
+                "}",
+                "",
+                "#[starknet::contract]",
+                "pub mod math {",
+                "    #[storage]",
+                "    struct Storage {}",
+                "",
+                "    #[abi(embed_v0)]",
+                "    impl MathImpl of super::IMath<ContractState> {",
+                "        fn add(self: @ContractState, a: u32, b: u32) -> u32 {",
+                "            return abi_from_i32(add(abi_to_i32(a), abi_to_i32(b)));",
+                "        }",
+                "",
+                "        fn is_zero(self: @ContractState, a: u32) -> bool {",
+                "            return is_zero(abi_to_i32(a));",
+                "        }",
+                "    }",
+                "",
+                "    pub fn add(a: i32, b: i32) -> i32 {",
+                "        let res = a + b;",
+                "        return res;",
+                "    }",
+                "",
+                "    pub fn is_zero(a: i32) -> bool {",
+                "        let c = a == 0_i32;",
+                "        return c;",
+                "    }",
+                "",
+                "    pub fn internal(a: bool) -> bool {",
+                "        return a;",
+                "    }",
+                "",
+                "    fn abi_to_i32(value: u32) -> i32 {",
+                "        let felt: felt252 = value.into();",
+                "        if value < 0x80000000 {",
+                "            felt.try_into().unwrap()",
+                "        } else {",
+                "            (felt - 0x100000000).try_into().unwrap()",
+                "        }",
+                "    }",
+                "",
+                "    fn abi_from_i32(value: i32) -> u32 {",
+                "        let felt: felt252 = value.into();",
+                "        if value < 0 {",
+                "            (felt + 0x100000000).try_into().unwrap()",
+                "        } else {",
+                "            felt.try_into().unwrap()",
+                "        }",
+                "    }",
+                "}",
+            ]
+            .join("\n")
+        );
+
+        let mut package = ScarbPackage::new("math", FormatOptions::default());
+        package.add_module("math", code);
+        assert!(package.manifest().ends_with("starknet = \"2.8.0\"\n\n[[target.starknet-contract]]\n"));
+    }
+
+    /// A function that can't be an entry point is an error, not a panic.
+    #[test]
+    fn test_contract_errors() {
+        let ir = r#"
+define ptr @id(ptr %p) #0 {
+start:
+  ret ptr %p
+}
+
+attributes #0 = { "starknet-external" }
+"#;
+        let context = Context::create();
+        let module = context
+            .create_module_from_ir(MemoryBuffer::create_from_memory_range_copy(ir.as_bytes(), "test"))
+            .expect("Failed to parse LLVM IR");
+        let contract = ContractOptions { name: "pointers".to_owned(), exports: Vec::new() };
+        let options = TranslationOptions { contract: Some(contract), ..Default::default() };
+        assert_eq!(
+            compile_module(&module, options).map(|_| ()),
+            Err("id can't be exported: u64 isn't supported in the ABI".to_owned())
+        );
+    }
+
+    #[test]
+    fn test_visibility() {
+        let ir = r#"
+define i32 @main(i32 %x) {
+start:
+  %r = call i32 @helper(i32 %x)
+  ret i32 %r
+}
+
+define internal i32 @helper(i32 %x) {
+start:
+  %r = call i32 @twice(i32 %x)
+  ret i32 %r
+}
+
+define linkonce_odr i32 @twice(i32 %x) {
+start:
+  %r = add i32 %x, %x
+  ret i32 %r
+}
+
+define private i32 @unused(i32 %x) {
+start:
+  ret i32 %x
+}
+"#;
+        assert_eq!(
+            compile_ir_with_options(ir, TranslationOptions::default()).to_string(),
+            [
+                "pub fn main(x: i32) -> i32 {",
+                "    let r = helper(x);",
+                "    return r;",
+                "}",
+                "",
+                "fn helper(x: i32) -> i32 {",
+                "    let r = twice(x);",
+                "    return r;",
+                "}",
+                "",
+                "fn twice(x: i32) -> i32 {",
+                "    let r = x + x;",
+                "    return r;",
+                "}",
+            ]
+            .join("\n")
+        );
+        // With an allowlist only the functions it reaches are kept.
+        let options = TranslationOptions { entrypoints: vec!["twice".to_owned()], ..Default::default() };
+        assert_eq!(
+            compile_ir_with_options(ir, options).to_string(),
+            ["pub fn twice(x: i32) -> i32 {", "    let r = x + x;", "    return r;", "}"].join("\n")
+        );
+    }
+
+    #[test]
+    fn test_recursion() {
+        let ir = r#"
+define i32 @ping(i32 %n) {
+start:
+  %r = call i32 @pong(i32 %n)
+  ret i32 %r
+}
+
+define i32 @helper(i32 %n) {
+start:
+  %r = add i32 %n, 1
+  ret i32 %r
+}
+
+define i32 @pong(i32 %n) {
+start:
+  %m = call i32 @helper(i32 %n)
+  %r = call i32 @ping(i32 %m)
+  ret i32 %r
+}
+
+define i32 @main(i32 %n) {
+start:
+  %r = call i32 @ping(i32 %n)
+  ret i32 %r
+}
+"#;
+        // The callers come first and the recursive functions are kept together.
+        let code = compile_ir(ir);
+        let names = code.functions().iter().map(|function| function.signature.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, ["main", "ping", "pong", "helper"]);
+        let recursive = code
+            .functions()
+            .iter()
+            .filter(|function| function.recursive())
+            .map(|function| function.signature.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(recursive, ["ping", "pong"]);
+        // Only the functions of the cycle are flagged in the cost report, not their callers.
+        let report = cost::estimate(code.functions(), &Default::default(), &CostOptions::default());
+        assert!(!report.function("main").unwrap().recursive);
+        assert!(report.function("ping").unwrap().recursive);
+        assert!(!report.function("helper").unwrap().recursive);
+        let context = Context::create();
+        let module =
+            context.create_module_from_ir(MemoryBuffer::create_from_memory_range_copy(ir.as_bytes(), "test")).unwrap();
+        let mut recursive = CallGraph::new(&module)
+            .recursive_functions()
+            .into_iter()
+            .map(|function| function.get_name().to_string_lossy().into_owned())
+            .collect::<Vec<_>>();
+        recursive.sort();
+        assert_eq!(recursive, ["ping", "pong"]);
+
+        let ir = r#"
+define i32 @count(i32 %n, i32 %acc) {
+start:
+  %n1 = sub i32 %n, 1
+  %a1 = add i32 %acc, 1
+  %r = tail call i32 @count(i32 %n1, i32 %a1)
+  ret i32 %r
+}
+"#;
+        let code = compile_ir_with_options(ir, TranslationOptions { tail_calls_to_loops: true, ..Default::default() });
+        assert_eq!(
+            code.functions()[0].body.to_string(),
+            [
+                "let mut n = n;",
+                "let mut acc = acc;",
+                "loop {",
+                "    let n1 = n - 1_i32;",
+                "    let a1 = acc + 1_i32;",
+                "    let n_next = n1;",
+                "    let acc_next = a1;",
+                "    n = n_next;",
+                "    acc = acc_next;",
+                "    continue;",
+                "};",
+            ]
+            .join("\n")
+        );
+        // The recursion became a loop.
+        assert!(!code.functions()[0].recursive());
+        assert!(compile_ir(ir).functions()[0].recursive());
+    }
+
+    /// The optimized examples shouldn't take more cairo steps than the unoptimized ones. Needs
+    /// `scarb` like [test_differential_examples].
+    #[test]
+    #[ignore = "needs scarb and the generated examples"]
+    fn test_optimize_steps() {
+        use testing::differential::{cairo_steps, DifferentialConfig};
+
+        for example in ["add", "increment", "fib"] {
+            let path = format!("examples/{example}/{example}.ll");
+            let steps = |optimize| {
+                let config = DifferentialConfig {
+                    options: TranslationOptions { optimize, ..Default::default() },
+                    directory: std::env::temp_dir().join(format!("llvm-to-cairo-steps-{example}-{optimize}")),
+                    ..Default::default()
+                };
+                cairo_steps(&path, &config).unwrap()
+            };
+            let (unoptimized, optimized) = (steps(false), steps(true));
+            println!("{example}: {unoptimized} steps => {optimized} steps");
+            assert!(optimized <= unoptimized, "{example}: {unoptimized} steps => {optimized} steps");
+        }
+    }
+
+    #[test]
+    fn test_cost() {
+        let ir = r#"
+define i32 @add(i32 %a, i32 %b) {
+start:
+  %r = add i32 %a, %b
+  ret i32 %r
+}
+
+define i32 @add_twice(i32 %a, i32 %b) {
+start:
+  %c = call i32 @add(i32 %a, i32 %b)
+  %r = call i32 @add(i32 %c, i32 %b)
+  ret i32 %r
+}
+"#;
+        let code = compile_ir(ir);
+        let report = cost::estimate(code.functions(), &Default::default(), &CostOptions::default());
+        // let r = a + b; return r;
+        let add = report.function("add").unwrap();
+        assert_eq!((add.cost.steps, add.cost.range_checks, add.cost.gas()), (6, 1, 670));
+        // Two calls of `add` and the return.
+        let add_twice = report.function("add_twice").unwrap();
+        assert_eq!(add_twice.cost, cost::Cost::new(2 * (6 + 3 + 1) + 1, 2));
+        assert!(report.to_table().lines().nth(1).unwrap().starts_with("add "));
+        assert!(report.to_json().contains(r#"{"name": "add", "steps": 6, "range_checks": 1, "gas": 670"#));
+
+        let ir = r#"
+define i32 @sum(i32 %n) {
+start:
+  br label %loop
+
+loop:
+  %i = phi i32 [ 0, %start ], [ %i.next, %loop ]
+  %acc = phi i32 [ 0, %start ], [ %acc.next, %loop ]
+  %acc.next = add i32 %acc, %i
+  %i.next = add i32 %i, 2
+  %done = icmp sge i32 %i.next, 10
+  br i1 %done, label %end, label %loop
+
+end:
+  ret i32 %acc.next
+}
+
+define i32 @sum_to(i32 %n) {
+start:
+  br label %loop
+
+loop:
+  %i = phi i32 [ 0, %start ], [ %i.next, %loop ]
+  %i.next = add i32 %i, 1
+  %done = icmp eq i32 %i.next, %n
+  br i1 %done, label %end, label %loop, !llvm.loop !0
+
+end:
+  ret i32 %i.next
+}
+
+!0 = distinct !{!0, !1}
+!1 = !{!"llvm.loop.estimated_trip_count", i32 100}
+"#;
+        let context = Context::create();
+        let module =
+            context.create_module_from_ir(MemoryBuffer::create_from_memory_range_copy(ir.as_bytes(), "test")).unwrap();
+        let trip_counts = loop_trip_counts(&module);
+        assert_eq!(trip_counts.get(&("sum".to_owned(), "loop".to_owned())), Some(&5));
+        assert_eq!(trip_counts.get(&("sum_to".to_owned(), "loop".to_owned())), Some(&100));
+    }
+
+    #[test]
+    fn test_pointer_casts() {
+        let code = compile_ir(
+            r#"
+define i1 @roundtrip(ptr %p, ptr addrspace(1) %q) {
+start:
+  %addr = ptrtoint ptr %p to i64
+  %back = inttoptr i64 %addr to ptr
+  %cast = addrspacecast ptr addrspace(1) %q to ptr
+  %same = bitcast ptr %cast to ptr
+  %eq = icmp eq ptr %back, %same
+  ret i1 %eq
+}
+
+define i1 @is_null(ptr %p) {
+start:
+  %empty = icmp eq ptr %p, null
+  ret i1 %empty
+}
+
+define i32 @low_bits(ptr %p) {
+start:
+  %low = ptrtoint ptr %p to i32
+  ret i32 %low
+}
+
+define ptr @all_ones() {
+start:
+  %p = inttoptr i64 -1 to ptr
+  ret ptr %p
+}
+"#,
+        );
+        let roundtrip = &code.functions()[0];
+        assert_eq!(roundtrip.signature.to_string(), "pub fn roundtrip(p: u64, q: u64) -> bool");
+        assert_eq!(
+            roundtrip.body.to_string(),
+            [
+                "let addr = ptr_to_i64(p);",
+                "let back = ptr_from_i64(addr);",
+                "let cast = q;",
+                "let same = cast;",
+                "let eq = back == same;",
+                "return eq;"
+            ]
+            .join("\n")
+        );
+        assert_eq!(code.functions()[1].body.to_string(), ["let empty = p == 0_u64;", "return empty;"].join("\n"));
+        // The conversions keep the bit pattern: -1 is the last address and the addresses past
+        // `i32::MAX` are negative.
+        assert_eq!(code.functions()[3].body.to_string(), ["let p = ptr_from_i64(-1_i64);", "return p;"].join("\n"));
+        let code = code.to_string();
+        assert!(code.contains("fn ptr_from_i64(value: i64) -> u64 {"));
+        assert!(code.contains("(felt + 0x10000000000000000).try_into().unwrap()"));
+        assert!(code.contains("fn ptr_to_i32(value: u64) -> i32 {\n    let bits = value % 0x100000000;"));
+        assert!(code.contains("(felt - 0x100000000).try_into().unwrap()"));
+        assert!(!code.contains("fn ptr_from_i32"));
+    }
+
+    /// Differential test of the bit patterns of the conversions between pointers and integers.
+    #[test]
+    #[ignore = "needs scarb and lli"]
+    fn test_differential_pointer_casts() {
+        use std::fs;
+
+        use testing::differential::{differential_test, DifferentialConfig};
+
+        let ir = r#"
+define i64 @roundtrip(i64 %a) {
+start:
+  %p = inttoptr i64 %a to ptr
+  %r = ptrtoint ptr %p to i64
+  ret i64 %r
+}
+
+define i32 @low_bits(i64 %a) {
+start:
+  %p = inttoptr i64 %a to ptr
+  %r = ptrtoint ptr %p to i32
+  ret i32 %r
+}
+
+define i128 @truncated(i128 %a) {
+start:
+  %p = inttoptr i128 %a to ptr
+  %r = ptrtoint ptr %p to i128
+  ret i128 %r
+}
+"#;
+        let directory = std::env::temp_dir().join("llvm-to-cairo-differential-pointers");
+        fs::create_dir_all(&directory).unwrap();
+        let path = directory.join("pointers.ll");
+        fs::write(&path, ir).unwrap();
+        // The edge cases of the range are the most negative integer and -1.
+        let config = DifferentialConfig { range: i128::MIN..=-1, directory, ..Default::default() };
+        let mismatches = differential_test(path.to_str().unwrap(), &config).unwrap();
+        assert!(mismatches.is_empty(), "{}", mismatches.iter().map(ToString::to_string).collect::<Vec<_>>().join("\n"));
+    }
+
+    #[test]
+    fn test_memory_instructions() {
+        let code = compile_ir(
+            r#"
+target datalayout = "e-m:e-p270:32:32-p271:32:32-p272:64:64-i64:64-i128:128-f80:128-n8:16:32:64-S128"
+
+define i64 @sum_pair(i64 %a, i64 %b, i64 %i) {
+start:
+  %pairs = alloca [2 x { i64, i64 }], align 8
+  %pair = getelementptr inbounds [2 x { i64, i64 }], ptr %pairs, i64 0, i64 %i
+  %second = getelementptr inbounds { i64, i64 }, ptr %pair, i64 0, i32 1
+  store i64 %a, ptr %pair, align 8
+  store i64 %b, ptr %second, align 8
+  %x = load i64, ptr %pair, align 8
+  %y = load i64, ptr %second, align 8
+  %sum = add i64 %x, %y
+  ret i64 %sum
+}
+
+define i64 @caller(i64 %a) {
+start:
+  %s = call i64 @sum_pair(i64 %a, i64 %a, i64 1)
+  ret i64 %s
+}
+
+define ptr @previous(ptr %p) {
+start:
+  %q = getelementptr inbounds i32, ptr %p, i64 -1
+  ret ptr %q
+}
+"#,
+        );
+        let function = |name: &str| code.functions().iter().find(|function| function.signature.name == name).unwrap();
+        let sum_pair = function("sum_pair");
+        assert_eq!(
+            sum_pair.signature.to_string(),
+            "pub fn sum_pair(ref memory: Memory, a: i64, b: i64, i: i64) -> i64"
+        );
+        assert_eq!(
+            sum_pair.body.to_string(),
+            [
+                "let pairs = rust_alloc(ref memory, 32_i64, 8_i64);",
+                "let pair = ptr_add(pairs, i.into(), 16);",
+                "let second = ptr_add(pair, 8, 1);",
+                "store_i64(ref memory, pair, a);",
+                "store_i64(ref memory, second, b);",
+                "let x = load_i64(ref memory, pair);",
+                "let y = load_i64(ref memory, second);",
+                "let sum = x + y;",
+                "return sum;",
+            ]
+            .join("\n")
+        );
+        // The memory is threaded through the callers, the address computations don't need it.
+        assert_eq!(
+            function("caller").body.to_string(),
+            ["let s = sum_pair(ref memory, a, a, 1_i64);", "return s;"].join("\n")
+        );
+        assert_eq!(function("previous").signature.to_string(), "pub fn previous(p: u64) -> u64");
+        assert_eq!(function("previous").body.to_string(), ["let q = ptr_add(p, -4, 1);", "return q;"].join("\n"));
+        assert!(code.allocator());
+        let code = code.to_string();
+        assert!(code.contains("pub fn load_i64(ref memory: Memory, address: u64) -> i64 {"));
+        assert!(code.contains("pub fn ptr_add(address: u64, index: felt252, size: felt252) -> u64 {"));
+    }
+
+    /// A value named `memory` doesn't shadow the memory parameter.
+    #[test]
+    fn test_memory_name() {
+        let code = compile_ir(
+            r#"
+define i32 @keep(i32 %memory) {
+start:
+  %slot = alloca i32, align 4
+  store i32 %memory, ptr %slot, align 4
+  %v = load i32, ptr %slot, align 4
+  ret i32 %v
+}
+"#,
+        );
+        let keep = &code.functions()[0];
+        assert_eq!(keep.signature.to_string(), "pub fn keep(ref memory: Memory, memory_1: i32) -> i32");
+        assert_eq!(
+            keep.body.to_string(),
+            [
+                "let slot = rust_alloc(ref memory, 4_i64, 4_i64);",
+                "store_i32(ref memory, slot, memory_1);",
+                "let v = load_i32(ref memory, slot);",
+                "return v;",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn test_float_memory() {
+        let ir = r#"
+define double @swap(ptr %p, double %x) {
+start:
+  %old = load double, ptr %p, align 8
+  store double %x, ptr %p, align 8
+  ret double %old
+}
+"#;
+        let code = compile_ir(ir);
+        assert_eq!(
+            code.functions()[0].body.to_string(),
+            [
+                "let old = f64_from_bits(load_i64(ref memory, p).into());",
+                "store_i64(ref memory, p, f64_to_bits(x).try_into().unwrap());",
+                "return old;",
+            ]
+            .join("\n")
+        );
+        assert!(code.float_library().is_some());
+
+        // The fixed-point numbers don't have a bit pattern and the aggregates aren't supported.
+        let context = Context::create();
+        let parse = |ir: &str| {
+            context.create_module_from_ir(MemoryBuffer::create_from_memory_range_copy(ir.as_bytes(), "test")).unwrap()
+        };
+        let options = TranslationOptions { float_mode: FloatMode::FixedPoint, ..Default::default() };
+        assert_eq!(
+            compile_module(&parse(ir), options).map(|_| ()),
+            Err("Failed to translate swap: Loading and storing double needs the IEEE floats (--float ieee), \
+                 Loading and storing double needs the IEEE floats (--float ieee)"
+                .to_owned())
+        );
+        let ir = r#"
+define void @read(ptr %p) {
+start:
+  %v = load [2 x i32], ptr %p, align 4
+  ret void
+}
+"#;
+        assert_eq!(
+            compile_module(&parse(ir), TranslationOptions::default()).map(|_| ()),
+            Err("Failed to translate read: Loading and storing [2 x i32] isn't supported, only the integers, the \
+                 floats and the pointers are"
+                .to_owned())
+        );
+    }
+
+    #[test]
+    fn test_allocator() {
+        let ir = r#"
+define i64 @make_box(i32 %value) {
+start:
+  %ptr = call ptr @__rust_alloc(i64 4, i64 4)
+  %addr = ptrtoint ptr %ptr to i64
+  ret i64 %addr
+}
+
+define i64 @outer(i32 %value) {
+start:
+  %addr = call i64 @make_box(i32 %value)
+  ret i64 %addr
+}
+
+define void @free(ptr %ptr) {
+start:
+  call void @__rust_dealloc(ptr %ptr, i64 4, i64 4)
+  ret void
+}
+
+define i32 @id(i32 %a) {
+start:
+  ret i32 %a
+}
+
+declare ptr @__rust_alloc(i64, i64)
+declare void @__rust_dealloc(ptr, i64, i64)
+"#;
+        let code = compile_ir(ir);
+        let function = |name: &str| code.functions().iter().find(|function| function.signature.name == name).unwrap();
+        assert_eq!(
+            function("make_box").signature.to_string(),
+            "pub fn make_box(ref memory: Memory, value: i32) -> i64"
+        );
+        assert!(function("make_box").body.to_string().starts_with("let ptr = rust_alloc(ref memory, 4_i64, 4_i64);"));
+        // The memory is a parameter and an argument passed by reference.
+        assert_eq!(
+            function("make_box").signature.parameters.0[0],
+            CairoParameter::by_ref("memory".to_owned(), "Memory".to_owned())
+        );
+        let mut references = Vec::new();
+        function("outer").body.block().visit_exprs(&mut |expr| {
+            if let ast::Expr::Ref(variable) = expr {
+                references.push(variable.clone());
+            }
+        });
+        assert_eq!(references, ["memory"]);
+        // The memory is threaded through the callers.
+        assert_eq!(
+            function("outer").body.to_string(),
+            ["let addr = make_box(ref memory, value);", "return addr;"].join("\n")
+        );
+        assert_eq!(function("free").body.to_string(), "rust_dealloc(ref memory, ptr, 4_i64, 4_i64);");
+        assert_eq!(function("id").signature.to_string(), "pub fn id(a: i32) -> i32");
+        assert!(code.allocator());
+        assert!(code.to_string().contains("pub fn rust_alloc(ref memory: Memory, size: i64, align: i64) -> u64 {"));
+        assert!(!compile("examples/add/add.ll").unwrap().allocator());
+
+        // The entry points of a contract create the memory.
+        let options = TranslationOptions {
+            contract: Some(ContractOptions { name: "boxes".to_owned(), exports: vec!["make_box".to_owned()] }),
+            ..Default::default()
+        };
+        let code = compile_ir_with_options(ir, options).to_string();
+        assert!(code.contains("fn make_box(self: @ContractState, value: u32) -> u64 {"));
+        assert!(code.contains("let mut memory = MemoryTrait::new();"));
+        assert!(code.contains("abi_from_i64(make_box(ref memory, abi_to_i32(value)))"));
+    }
+
+    #[test]
+    fn test_link_modules() {
+        let context = Context::create();
+        let parse = |ir: &str, name: &str| {
+            context.create_module_from_ir(MemoryBuffer::create_from_memory_range_copy(ir.as_bytes(), name)).unwrap()
+        };
+        let main = parse(
+            r#"
+define i32 @add_twice(i32 %a, i32 %b) {
+start:
+  %c = call i32 @add(i32 %a, i32 %b)
+  %r = call i32 @identity(i32 %c)
+  ret i32 %r
+}
+
+define linkonce_odr i32 @identity(i32 %x) {
+start:
+  ret i32 %x
+}
+
+declare i32 @add(i32, i32)
+"#,
+            "main",
+        );
+        let dependency = parse(
+            r#"
+define i32 @add(i32 %a, i32 %b) {
+start:
+  %r = call i32 @identity(i32 %a)
+  %s = add i32 %r, %b
+  ret i32 %s
+}
+
+define linkonce_odr i32 @identity(i32 %x) {
+start:
+  ret i32 %x
+}
+"#,
+            "dependency",
+        );
+        let module = link_modules(vec![main, dependency]).unwrap();
+        let code = compile_module(&module, TranslationOptions::default()).unwrap();
+        let mut names = code.functions().iter().map(|function| function.signature.name.as_str()).collect::<Vec<_>>();
+        names.sort();
+        // `identity` is defined once and `add` is resolved to its definition.
+        assert_eq!(names, ["add", "add_twice", "identity"]);
+        let add_twice = code.functions().iter().find(|function| function.signature.name == "add_twice").unwrap();
+        assert!(add_twice.body.to_string().starts_with("let c = add(a, b);"));
+
+        // Two definitions of the same symbol can't be linked.
+        let first = parse("define i32 @one() {\nstart:\n  ret i32 1\n}\n", "first");
+        let second = parse("define i32 @one() {\nstart:\n  ret i32 2\n}\n", "second");
+        let error = link_modules(vec![first, second]).unwrap_err();
+        assert!(error.starts_with("Failed to link second: "), "{error}");
+        assert_eq!(link_modules(Vec::new()).unwrap_err(), "There is no module to link");
+    }
+
+    #[test]
+    fn test_arithmetic_mode() {
+        use ast::{Block, Expr, Statement};
+        use builder::function::CairoFunction;
+        use builder::types::CairoStructs;
+        use builder::CairoFunctions;
+
+        let ir = r#"
+define i32 @ops(i32 %a, i32 %b) {
+start:
+  %s = add i32 %a, %b
+  %t = sub nsw i32 %s, %b
+  %u = add nuw nsw i32 %t, 1
+  ret i32 %u
+}
+
+define i8 @sum(<3 x i8> %v) {
+start:
+  %r = call i8 @llvm.vector.reduce.add.v3i8(<3 x i8> %v)
+  ret i8 %r
+}
+
+declare i8 @llvm.vector.reduce.add.v3i8(<3 x i8>)
+"#;
+        let function = |code: &CairoModule, name: &str| {
+            code.functions().iter().find(|function| function.signature.name == name).unwrap().body.to_string()
+        };
+        let compile_mode =
+            |arithmetic_mode| compile_ir_with_options(ir, TranslationOptions { arithmetic_mode, ..Default::default() });
+        let code = compile_mode(ArithmeticMode::Native);
+        assert!(function(&code, "ops").starts_with("let s = a + b;\nlet t = s - b;\nlet u = t + 1_i32;"));
+        assert!(function(&code, "sum").contains("let r = v_0 + v_1 + v_2;"));
+        assert!(!code.to_string().contains("wrapping_"));
+
+        // The flags are ignored, everything wraps around.
+        let code = compile_mode(ArithmeticMode::Wrapping);
+        assert!(function(&code, "ops").starts_with(
+            "let s = wrapping_add_i32(a, b);\nlet t = wrapping_sub_i32(s, b);\nlet u = wrapping_add_i32(t, 1_i32);"
+        ));
+        // So do the reductions, they don't have flags.
+        assert!(function(&code, "sum").contains("let r = wrapping_add_i8(wrapping_add_i8(v_0, v_1), v_2);"));
+        let code = code.to_string();
+        assert!(code.contains("fn wrapping_add_i32(a: i32, b: i32) -> i32 {"));
+        assert!(code.contains("fn wrapping_add_i8(a: i8, b: i8) -> i8 {"));
+        assert!(code.contains("(result - 0x100000000).try_into().unwrap()"));
+        assert!(!code.contains("nuw overflowed"));
+
+        // `nsw` alone is checked by the cairo operators.
+        let code = compile_mode(ArithmeticMode::Checked);
+        assert!(function(&code, "ops")
+            .starts_with("let s = wrapping_add_i32(a, b);\nlet t = s - b;\nlet u = add_nuw_nsw_i32(t, 1_i32);"));
+        let code = code.to_string();
+        assert!(code.contains("fn add_nuw_nsw_i32(a: i32, b: i32) -> i32 {"));
+        assert!(code.contains("if a_unsigned + b_unsigned >= 0x100000000_u256 {"));
+        assert!(code.contains("Option::None => panic!(\"add nsw overflowed\"),"));
+        assert!(!code.contains("fn wrapping_sub_i32"));
+
+        let helper = ArithmeticHelper::parse("sub_nuw_i8").unwrap();
+        assert_eq!((helper.operation, helper.width, helper.nuw, helper.nsw), (BinaryOperator::Sub, 8, true, false));
+        assert_eq!(helper.name(), "sub_nuw_i8");
+        assert_eq!(helper.modulus(), "0x100");
+        assert_eq!(ArithmeticHelper::parse("add_i32"), None);
+
+        let helper = ArithmeticHelper { operation: BinaryOperator::Add, width: 8, nuw: false, nsw: false };
+        assert_eq!(
+            helper.function().to_string(),
+            [
+                "fn wrapping_add_i8(a: i8, b: i8) -> i8 {",
+                "    let a_felt: felt252 = a.into();",
+                "    let b_felt: felt252 = b.into();",
+                "    let result = a_felt + b_felt;",
+                "    match result.try_into() {",
+                "        Option::Some(result) => result,",
+                "        Option::None => if a < 0 {",
+                "            (result + 0x100).try_into().unwrap()",
+                "        } else {",
+                "            (result - 0x100).try_into().unwrap()",
+                "        },",
+                "    }",
+                "}",
+            ]
+            .join("\n")
+        );
+
+        // The helpers are the ones the translation asked for, not the calls that look like one.
+        let code = compile_mode(ArithmeticMode::Checked);
+        assert_eq!(
+            code.functions().iter().find(|function| function.signature.name == "ops").unwrap().arithmetic_helpers,
+            [
+                ArithmeticHelper { operation: BinaryOperator::Add, width: 32, nuw: false, nsw: false },
+                ArithmeticHelper { operation: BinaryOperator::Add, width: 32, nuw: true, nsw: true }
+            ]
+        );
+        let mut body = Block::default();
+        body.push(Statement::Return(Some(Expr::call(
+            "wrapping_add_i32",
+            vec![Expr::variable("a"), Expr::variable("a")],
+        ))));
+        let parameters = vec![CairoParameter::new("a".to_owned(), "i32".to_owned())];
+        let function =
+            CairoFunction::new(CairoFunctionSignature::new("f".to_owned(), parameters, "i32".to_owned()), body);
+        let mut functions = CairoFunctions::default();
+        functions.push_function(function);
+        assert!(!CairoModule::new(CairoStructs::default(), functions).to_string().contains("fn wrapping_add_i32"));
+    }
+
+    #[test]
+    fn test_soft_float() {
+        let ir = r#"
+define float @scale(float %a, i32 %n) {
+start:
+  %b = sitofp i32 %n to float
+  %sum = fadd float %a, 1.5
+  %prod = fmul float %sum, %b
+  %root = call float @llvm.sqrt.f32(float %prod)
+  %less = fcmp olt float %root, %a
+  %r = select i1 %less, float %root, float %a
+  ret float %r
+}
+
+declare float @llvm.sqrt.f32(float)
+"#;
+        let code = compile_ir(ir);
+        let body = code.functions()[0].body.to_string();
+        for expected in [
+            "f32_sitofp(n.into())",
+            "f32_add(a, F32 { bits: 0x3fc00000_u32 })",
+            "f32_mul(sum, b)",
+            "f32_sqrt(prod)",
+            "f32_lt(root, a)",
+        ] {
+            assert!(body.contains(expected), "{expected} not in\n{body}");
+        }
+        assert_eq!(code.float_library(), Some(FloatMode::Ieee));
+        let code = code.to_string();
+        assert!(code.contains("pub fn scale(a: F32, n: i32) -> F32"));
+        assert!(code.contains("pub struct F32 {"));
+        assert!(code.contains("pub fn f32_add(a: F32, b: F32) -> F32 {"));
+
+        // 1.5 * 2^32
+        let options = TranslationOptions { float_mode: FloatMode::FixedPoint, ..Default::default() };
+        let code = compile_ir_with_options(ir, options);
+        assert!(code.functions()[0].body.to_string().contains("f32_add(a, F32 { raw: 6442450944_i128 })"));
+        assert!(code.to_string().contains("pub struct F32 {\n    pub raw: i128,\n}"));
+        assert!(soft_float::is_library_function("f64_to_f32"));
+        assert!(!soft_float::is_library_function("f32_to_f32"));
+
+        // Modules without floats don't get the library.
+        assert_eq!(compile("examples/add/add.ll").unwrap().float_library(), None);
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "Unsupported LLVM intrinsic llvm.sin.f32: the float library only has the basic operations"
+    )]
+    fn test_transcendental_intrinsic() {
+        compile_ir(
+            r#"
+define float @wave(float %a) {
+start:
+  %res = call float @llvm.sin.f32(float %a)
+  ret float %res
+}
+
+declare float @llvm.sin.f32(float)
+"#,
+        );
+    }
+
+    /// Run the IEEE float library on the special values and on the rounding edge cases and compare
+    /// it with the rust floats. LLVM doesn't specify the payload of the NaNs it produces so a NaN
+    /// result only has to be a NaN.
+    #[test]
+    #[ignore = "needs scarb"]
+    fn test_soft_float_edge_cases() {
+        use std::fs;
+        use std::process::Command;
+
+        use package::ScarbPackage;
+        use testing::differential::{parse_cairo_output, Outcome};
+
+        // ±0, the smallest and largest subnormals, the smallest normal, 0.5, ±1, 1 + ulp, 2, 3, the
+        // first integer that has no successor (so +1 and +3 are ties), the largest finite value,
+        // ±∞, a quiet NaN and a signaling NaN with a payload.
+        let f32_values: [u32; 17] = [
+            0x0000_0000,
+            0x8000_0000,
+            0x0000_0001,
+            0x007f_ffff,
+            0x0080_0000,
+            0x3f00_0000,
+            0x3f80_0000,
+            0xbf80_0000,
+            0x3f80_0001,
+            0x4000_0000,
+            0x4040_0000,
+            0x4b80_0000,
+            0x7f7f_ffff,
+            0x7f80_0000,
+            0xff80_0000,
+            0x7fc0_0001,
+            0xff80_0001,
+        ];
+        // The same for f64, with the values that are ties or overflow once converted to f32:
+        // 1 + 2^-24, 1 + 3 * 2^-24, 2^-149 and 2^-150.
+        let f64_values: [u64; 21] = [
+            0x0000_0000_0000_0000,
+            0x8000_0000_0000_0000,
+            0x0000_0000_0000_0001,
+            0x000f_ffff_ffff_ffff,
+            0x0010_0000_0000_0000,
+            0x3fe0_0000_0000_0000,
+            0x3ff0_0000_0000_0000,
+            0xbff0_0000_0000_0000,
+            0x3ff0_0000_0000_0001,
+            0x4000_0000_0000_0000,
+            0x4008_0000_0000_0000,
+            0x4340_0000_0000_0000,
+            0x7fef_ffff_ffff_ffff,
+            0x7ff0_0000_0000_0000,
+            0xfff0_0000_0000_0000,
+            0x7ff8_0000_0000_0001,
+            0xfff0_0000_0000_0001,
+            0x3ff0_0000_1000_0000,
+            0x3ff0_0000_3000_0000,
+            0x36a0_0000_0000_0000,
+            0x3690_0000_0000_0000,
+        ];
+
+        // (cairo expression, width of the result, expected bits)
+        let mut checks = Vec::<(String, u32, u64)>::new();
+        let f32_operations: [(&str, fn(f32, f32) -> f32); 4] =
+            [("add", |a, b| a + b), ("sub", |a, b| a - b), ("mul", |a, b| a * b), ("div", |a, b| a / b)];
+        for (name, operation) in f32_operations {
+            for a in f32_values {
+                for b in f32_values {
+                    let expected = operation(f32::from_bits(a), f32::from_bits(b));
+                    let call = format!("f32_{name}(F32 {{ bits: {a:#x} }}, F32 {{ bits: {b:#x} }})");
+                    checks.push((call, 32, expected.to_bits().into()));
+                }
+            }
+        }
+        let f64_operations: [(&str, fn(f64, f64) -> f64); 4] =
+            [("add", |a, b| a + b), ("sub", |a, b| a - b), ("mul", |a, b| a * b), ("div", |a, b| a / b)];
+        for (name, operation) in f64_operations {
+            for a in f64_values {
+                for b in f64_values {
+                    let expected = operation(f64::from_bits(a), f64::from_bits(b));
+                    let call = format!("f64_{name}(F64 {{ bits: {a:#x} }}, F64 {{ bits: {b:#x} }})");
+                    checks.push((call, 64, expected.to_bits()));
+                }
+            }
+        }
+        for a in f32_values {
+            let value = f32::from_bits(a);
+            checks.push((format!("f32_sqrt(F32 {{ bits: {a:#x} }})"), 32, value.sqrt().to_bits().into()));
+            checks.push((format!("f32_to_f64(F32 {{ bits: {a:#x} }})"), 64, (value as f64).to_bits()));
+        }
+        for a in f64_values {
+            let value = f64::from_bits(a);
+            checks.push((format!("f64_sqrt(F64 {{ bits: {a:#x} }})"), 64, value.sqrt().to_bits()));
+            checks.push((format!("f64_to_f32(F64 {{ bits: {a:#x} }})"), 32, (value as f32).to_bits().into()));
+        }
+
+        // `main` returns the number of the first check that fails, 0 if they all pass.
+        let mut cases = [
+            "use super::soft_float::{F32, F64, f32_add, f32_sub, f32_mul, f32_div, f32_sqrt, f32_unordered, \
+             f32_to_f64};",
+            "use super::soft_float::{f64_add, f64_sub, f64_mul, f64_div, f64_sqrt, f64_unordered, f64_to_f32};",
+            "",
+            "fn main() -> u32 {",
+        ]
+        .join("\n");
+        for (index, (call, width, expected)) in checks.iter().enumerate() {
+            let is_nan = match width {
+                32 => f32::from_bits(*expected as u32).is_nan(),
+                _ => f64::from_bits(*expected).is_nan(),
+            };
+            let condition = match is_nan {
+                true => format!("f{width}_unordered(r, r)"),
+                false => format!("r.bits == {expected:#x}"),
+            };
+            cases.push_str(&format!(
+                "\n    let r = {call};\n    if !({condition}) {{\n        return {};\n    }}",
+                index + 1
+            ));
+        }
+        cases.push_str("\n    0\n}\n");
+
+        let directory = std::env::temp_dir().join("llvm-to-cairo-soft-float");
+        let manifest = directory.join("Scarb.toml");
+        let package = ScarbPackage::new("soft_float_edge_cases", FormatOptions::default());
+        fs::create_dir_all(directory.join("src")).unwrap();
+        fs::write(&manifest, format!("{}\n[cairo]\nenable-gas = false\n", package.manifest())).unwrap();
+        fs::write(directory.join("src").join("lib.cairo"), "mod soft_float;\nmod cases;\n").unwrap();
+        fs::write(directory.join("src").join("soft_float.cairo"), soft_float::library(FloatMode::Ieee)).unwrap();
+        fs::write(directory.join("src").join("cases.cairo"), cases).unwrap();
+
+        let output = Command::new("scarb")
+            .arg("--manifest-path")
+            .arg(&manifest)
+            .arg("cairo-run")
+            .arg("--function")
+            .arg("main")
+            .output()
+            .unwrap();
+        let output = format!("{}{}", String::from_utf8_lossy(&output.stdout), String::from_utf8_lossy(&output.stderr));
+        match parse_cairo_output(&output, Some(32)).unwrap() {
+            Outcome::Value(0) => (),
+            Outcome::Value(failed) => {
+                let (call, _, expected) = &checks[failed as usize - 1];
+                panic!("{call} isn't {expected:#x}");
+            }
+            outcome => panic!("The checks didn't run: {outcome:?}"),
+        }
+    }
+
+    /// Differential test of integer functions that compute with floats.
+    #[test]
+    #[ignore = "needs scarb and lli"]
+    fn test_differential_soft_float() {
+        use std::fs;
+
+        use testing::differential::{differential_test, DifferentialConfig};
+
+        let ir = r#"
+define i32 @mean(i32 %a, i32 %b) {
+start:
+  %x = sitofp i32 %a to float
+  %y = sitofp i32 %b to float
+  %sum = fadd float %x, %y
+  %half = fmul float %sum, 5.000000e-01
+  %r = fptosi float %half to i32
+  ret i32 %r
+}
+
+define i64 @scaled_root(i64 %a) {
+start:
+  %x = sitofp i64 %a to double
+  %abs = call double @llvm.fabs.f64(double %x)
+  %root = call double @llvm.sqrt.f64(double %abs)
+  %scaled = fmul double %root, 1.000000e+03
+  %r = fptosi double %scaled to i64
+  ret i64 %r
+}
+
+define i1 @ratio_less(i32 %a, i32 %b) {
+start:
+  %x = sitofp i32 %a to float
+  %y = sitofp i32 %b to float
+  %ratio = fdiv float %x, %y
+  %less = fcmp olt float %ratio, 1.500000e+00
+  ret i1 %less
+}
+
+declare double @llvm.fabs.f64(double)
+declare double @llvm.sqrt.f64(double)
+"#;
+        let directory = std::env::temp_dir().join("llvm-to-cairo-differential-soft-float");
+        fs::create_dir_all(&directory).unwrap();
+        let path = directory.join("soft_float.ll");
+        fs::write(&path, ir).unwrap();
+        let config = DifferentialConfig { cases: 16, range: -64..=64, directory, ..Default::default() };
+        let mismatches = differential_test(path.to_str().unwrap(), &config).unwrap();
+        assert!(mismatches.is_empty(), "{}", mismatches.iter().map(ToString::to_string).collect::<Vec<_>>().join("\n"));
     }
 }
@@ -0,0 +1,183 @@
+use std::path::{Path, PathBuf};
+use std::process::exit;
+
+use llvm_to_cairo::ast::printer::FormatOptions;
+use llvm_to_cairo::builder::options::TranslationOptions;
+use llvm_to_cairo::cost::CostOptions;
+use llvm_to_cairo::{
+    compile_linked, compile_linked_package, compile_package, compile_with_options, estimate_cost, estimate_linked_cost,
+};
+
+const USAGE: &str = "Usage: llvm-to-cairo [OPTIONS] <INPUT.ll>...
+
+Translate LLVM IR files to cairo. The code is printed unless an output directory is given.
+
+Options:
+  -o, --output <DIR>     Write a Scarb package with one cairo module per input file in <DIR>
+      --name <NAME>      Name of the Scarb package [default: name of the first input file]
+      --link             Link the inputs (the codegen units of a crate and its dependencies) and
+                         translate them to a single module
+      --contract <NAME>  Wrap the functions in a starknet contract named <NAME>
+      --export <FUNCTION>
+                         Expose <FUNCTION> (LLVM symbol or cairo name) as an entry point of the
+                         contract. Can be repeated. The functions with the \"starknet-external\"
+                         attribute or the starknet_external annotation are always exposed
+      --entrypoint <FUNCTION>
+                         Make <FUNCTION> (LLVM symbol or cairo name) an entry point of the module.
+                         Can be repeated. The entry points are public and the functions they don't
+                         call are removed [default: the functions with an external linkage]
+      --poison-checks    Panic when a poison value reaches an observable use
+  -O, --optimize         Simplify the generated cairo code so it runs in fewer steps
+      --pre-opt <PRESET> Run LLVM passes that normalize the IR before translating it: none, minimal
+                         (mem2reg, lower-switch, loop-simplify, lcssa), aggressive (minimal plus
+                         instcombine and simplifycfg) or custom:<passes> (any pipeline in the syntax
+                         of opt -passes) [default: none]
+      --tail-calls-to-loops
+                         Turn the calls of a function to itself whose result is returned right away
+                         into a loop
+      --arithmetic <MODE>
+                         How the integer add and sub overflow: native (the cairo operators, they
+                         panic on signed overflow), wrapping (wrap around like LLVM) or checked
+                         (wrap around but panic when the nuw/nsw flags are violated, like a rust
+                         debug build) [default: native]
+      --float <MODE>     How float and double are emulated: ieee (exact IEEE-754 results) or fixed
+                         (fixed-point numbers with 32 fractional bits, cheaper but approximate)
+                         [default: ieee]
+      --cost <FORMAT>    Print the estimated steps, range checks and gas of each function instead of
+                         the code, as a table or json
+      --trip-count <N>   Number of iterations of the loops whose trip count can't be found in the
+                         IR when estimating the cost [default: 10]
+      --source-comments  Comment the statements with the LLVM instruction and source line they come
+                         from. A JSON source map is always written next to the modules of a package
+  -h, --help             Print this message";
+
+/// Command line arguments.
+#[derive(Default)]
+struct Arguments {
+    inputs: Vec<String>,
+    output: Option<PathBuf>,
+    name: Option<String>,
+    options: TranslationOptions,
+    format: FormatOptions,
+    /// Functions to expose from the contract.
+    exports: Vec<String>,
+    /// Print the cost of the functions in this format instead of the code.
+    cost: Option<CostFormat>,
+    cost_options: CostOptions,
+    /// Link the inputs in one module before translating them.
+    link: bool,
+}
+
+/// Format of the cost report.
+#[derive(Clone, Copy)]
+enum CostFormat {
+    Table,
+    Json,
+}
+
+/// Print the error and the usage then exit.
+fn fail(error: &str) -> ! {
+    eprintln!("error: {error}\n\n{USAGE}");
+    exit(1)
+}
+
+/// Print the error then exit, for the errors that aren't caused by the arguments.
+fn error(message: &str) -> ! {
+    eprintln!("error: {message}");
+    exit(1)
+}
+
+fn parse_arguments() -> Arguments {
+    let mut arguments = Arguments::default();
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => {
+                println!("{USAGE}");
+                exit(0)
+            }
+            "-o" | "--output" => {
+                arguments.output = Some(args.next().unwrap_or_else(|| fail("--output expects a directory")).into())
+            }
+            "--name" => arguments.name = Some(args.next().unwrap_or_else(|| fail("--name expects a value"))),
+            "--link" => arguments.link = true,
+            "--poison-checks" => arguments.options.poison_checks = true,
+            "--tail-calls-to-loops" => arguments.options.tail_calls_to_loops = true,
+            "--source-comments" => arguments.format.source_comments = true,
+            "--cost" => {
+                arguments.cost = match args.next().unwrap_or_else(|| fail("--cost expects a format")).as_str() {
+                    "table" => Some(CostFormat::Table),
+                    "json" => Some(CostFormat::Json),
+                    format => fail(&format!("unknown cost format {format}, expected table or json")),
+                }
+            }
+            "--trip-count" => {
+                let trip_count = args.next().unwrap_or_else(|| fail("--trip-count expects a number"));
+                arguments.cost_options.default_trip_count =
+                    trip_count.parse().unwrap_or_else(|_| fail(&format!("invalid trip count {trip_count}")))
+            }
+            // The translation options are shared with `cargo cairo`.
+            _ => match arguments.options.parse_argument(&arg, &mut args, &mut arguments.exports) {
+                Ok(true) => (),
+                Ok(false) if arg.starts_with('-') => fail(&format!("unknown option {arg}")),
+                Ok(false) => arguments.inputs.push(arg),
+                Err(error) => fail(&error),
+            },
+        }
+    }
+    if arguments.inputs.is_empty() {
+        fail("no input file");
+    }
+    let exports = std::mem::take(&mut arguments.exports);
+    arguments.options.set_exports(exports).unwrap_or_else(|error| fail(&error));
+    arguments
+}
+
+fn main() {
+    let arguments = parse_arguments();
+    let inputs = arguments.inputs.iter().map(String::as_str).collect::<Vec<_>>();
+    if let Some(format) = arguments.cost {
+        let reports = if arguments.link {
+            vec![estimate_linked_cost(&inputs, arguments.options.clone(), &arguments.cost_options)]
+        } else {
+            inputs
+                .iter()
+                .map(|input| estimate_cost(input, arguments.options.clone(), &arguments.cost_options))
+                .collect()
+        };
+        let reports = reports.into_iter().collect::<Result<Vec<_>, _>>().unwrap_or_else(|message| error(&message));
+        for report in reports {
+            match format {
+                CostFormat::Table => print!("{}", report.to_table()),
+                CostFormat::Json => print!("{}", report.to_json()),
+            }
+        }
+        return;
+    }
+    let Some(output) = arguments.output else {
+        if arguments.link {
+            let module = compile_linked(&inputs, arguments.options).unwrap_or_else(|message| error(&message));
+            println!("{}", module.format(arguments.format));
+            return;
+        }
+        for input in inputs {
+            let module =
+                compile_with_options(input, arguments.options.clone()).unwrap_or_else(|message| error(&message));
+            println!("{}", module.format(arguments.format));
+        }
+        return;
+    };
+    let name = arguments.name.unwrap_or_else(|| {
+        let first_input = Path::new(&arguments.inputs[0]);
+        first_input.file_stem().and_then(|stem| stem.to_str()).unwrap_or("cairo_program").to_owned()
+    });
+    let package = if arguments.link {
+        compile_linked_package(&name, &inputs, arguments.options, arguments.format)
+    } else {
+        compile_package(&name, &inputs, arguments.options, arguments.format)
+    };
+    let package = package.unwrap_or_else(|message| error(&message));
+    if let Err(e) = package.write(&output) {
+        error(&format!("failed to write the package in {}: {e}", output.display()))
+    }
+}
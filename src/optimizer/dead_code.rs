@@ -0,0 +1,120 @@
+//! Removal of the statements that have no effect and of the placeholders of the mutable
+//! variables.
+
+use crate::ast::{Block, Expr, Pattern, Statement};
+use crate::optimizer::usage::{mentions, Usage};
+
+/// Replace the variables of `pattern` that are never read by `_`. Returns whether the pattern
+/// still binds something.
+fn prune_pattern(pattern: &mut Pattern, usage: &Usage) -> bool {
+    match pattern {
+        Pattern::Variable { name, .. } if usage.reads(name) == 0 => {
+            *pattern = Pattern::Wildcard;
+            false
+        }
+        Pattern::Variable { .. } => true,
+        Pattern::Tuple(patterns) | Pattern::FixedArray(patterns) => {
+            patterns.iter_mut().fold(false, |binds, pattern| prune_pattern(pattern, usage) || binds)
+        }
+        Pattern::Typed { pattern, .. } => prune_pattern(pattern, usage),
+        // The variant is part of what the pattern checks so it's kept even if it doesn't bind anything.
+        Pattern::Variant { value, .. } => {
+            if let Some(value) = value {
+                prune_pattern(value, usage);
+            }
+            true
+        }
+        Pattern::Wildcard => false,
+    }
+}
+
+/// Remove the declarations and assignments of the variables that are never read, when computing
+/// their value has no other effect. The assignments that have an effect only keep their value, the
+/// declaration of the variable (usually a placeholder) can be gone:
+/// ```text
+/// let mut x = 0_i32;        =>
+/// x = a + b;                   a + b;
+/// ```
+pub(crate) fn remove_dead_stores(block: &mut Block) {
+    let usage = Usage::of(block);
+    block.visit_blocks_mut(&mut |block| {
+        let mut statements = Vec::with_capacity(block.statements.len());
+        for mut statement in block.statements.drain(..) {
+            if let Statement::Assign { variable, value } = &statement {
+                if usage.reads(variable) == 0 {
+                    statement = Statement::Expr(value.clone());
+                }
+            }
+            let keep = match &mut statement {
+                Statement::Let { pattern: Pattern::Variable { name, mutable }, value } => {
+                    // Nothing is ever assigned to it (or the assignments are gone) so it doesn't have
+                    // to be mutable.
+                    *mutable &= usage.is_assigned(name) && usage.reads(name) > 0;
+                    usage.reads(name) > 0 || !value.is_pure()
+                }
+                // `let (a, b) = f();` where `b` isn't used => `let (a, _) = f();`
+                Statement::Let { pattern: pattern @ (Pattern::Tuple(_) | Pattern::FixedArray(_)), value } => {
+                    let binds = prune_pattern(pattern, &usage);
+                    binds || !value.is_pure()
+                }
+                Statement::Expr(value) => !value.is_pure(),
+                Statement::If { condition, then_block, else_block } => {
+                    !(then_block.is_empty() && else_block.as_ref().map_or(true, Block::is_empty) && condition.is_pure())
+                }
+                _ => true,
+            };
+            if keep {
+                statements.push(statement);
+            }
+        }
+        block.statements = statements;
+    });
+}
+
+/// The mutable variables are declared with a default value before the scope in which they get
+/// their actual value. When the first use of the variable is an assignment in the same block, the
+/// default value is never read so the declaration is moved to the assignment:
+/// ```text
+/// let mut x = 0_i32;        =>
+/// let y = a + b;               let y = a + b;
+/// x = y;                       let mut x = y;
+/// ```
+pub(crate) fn sink_placeholders(block: &mut Block) {
+    block.visit_blocks_mut(&mut |block| {
+        let mut index = 0;
+        while index < block.statements.len() {
+            if let Some(assignment) = first_use_is_assignment(block, index) {
+                let Statement::Assign { variable, value } = block.statements[assignment].clone() else {
+                    unreachable!("The first use is an assignment")
+                };
+                block.statements[assignment] = Statement::declare_mut(variable, value);
+                block.statements.remove(index);
+            } else {
+                index += 1;
+            }
+        }
+    });
+}
+
+/// If the statement at `index` declares a mutable placeholder and the first following statement
+/// that uses it is an assignment (that doesn't read it), get the index of that assignment.
+fn first_use_is_assignment(block: &Block, index: usize) -> Option<usize> {
+    let Statement::Let { pattern: Pattern::Variable { name, mutable: true }, value } = &block.statements[index] else {
+        return None;
+    };
+    if !value.is_pure() {
+        return None;
+    }
+    let (offset, statement) =
+        block.statements[index + 1..].iter().enumerate().find(|(_, statement)| mentions(statement, name))?;
+    match statement {
+        Statement::Assign { variable, value } if variable == name && !reads(value, name) => Some(index + 1 + offset),
+        _ => None,
+    }
+}
+
+fn reads(expr: &Expr, name: &str) -> bool {
+    let mut found = false;
+    expr.visit(&mut |expr| found |= matches!(expr, Expr::Variable(variable) | Expr::Ref(variable) if variable == name));
+    found
+}
@@ -0,0 +1,179 @@
+//! Constant folding: compute the operations whose operands are literals and remove the branches
+//! that can't be taken.
+
+use crate::ast::{BinaryOperator, Block, Expr, Statement};
+
+/// A literal the folding understands.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Constant<'a> {
+    Bool(bool),
+    /// `<value>_<type>` such as `-1_i8`.
+    Integer(i128, &'a str),
+}
+
+impl<'a> Constant<'a> {
+    fn parse(literal: &'a str) -> Option<Self> {
+        match literal {
+            "true" => Some(Constant::Bool(true)),
+            "false" => Some(Constant::Bool(false)),
+            _ => {
+                let (value, ty) = literal.rsplit_once('_')?;
+                // Make sure the type is one we know the range of.
+                integer_range(ty)?;
+                Some(Constant::Integer(value.parse().ok()?, ty))
+            }
+        }
+    }
+
+    fn to_expr(self) -> Expr {
+        match self {
+            Constant::Bool(value) => Expr::literal(value.to_string()),
+            Constant::Integer(value, ty) => Expr::literal(format!("{value}_{ty}")),
+        }
+    }
+}
+
+/// Range of the values of the integer type `ty` (`i8`, `u32`...). `None` for the types we can't
+/// represent with an `i128`.
+fn integer_range(ty: &str) -> Option<(i128, i128)> {
+    let (signed, width) = match (ty.strip_prefix('i'), ty.strip_prefix('u')) {
+        (Some(width), _) => (true, width.parse::<u32>().ok()?),
+        (_, Some(width)) => (false, width.parse::<u32>().ok()?),
+        _ => return None,
+    };
+    match (signed, width) {
+        (true, 1..=128) => Some((i128::MIN >> (128 - width), i128::MAX >> (128 - width))),
+        (false, 1..=127) => Some((0, i128::MAX >> (127 - width))),
+        _ => None,
+    }
+}
+
+fn literal(expr: &Expr) -> Option<Constant<'_>> {
+    match expr {
+        Expr::Literal(literal) => Constant::parse(literal),
+        _ => None,
+    }
+}
+
+/// Is `expr` the integer literal `value` (of any type)?
+fn is_integer(expr: &Expr, value: i128) -> bool {
+    matches!(literal(expr), Some(Constant::Integer(literal, _)) if literal == value)
+}
+
+/// Compute `left <operator> right`. Operations that would overflow aren't folded so they still
+/// panic at runtime.
+fn fold_binary<'a>(operator: BinaryOperator, left: Constant<'a>, right: Constant<'a>) -> Option<Constant<'a>> {
+    match (left, right) {
+        (Constant::Integer(left, ty), Constant::Integer(right, right_ty)) if ty == right_ty => {
+            let value = match operator {
+                BinaryOperator::Add => left.checked_add(right)?,
+                BinaryOperator::Sub => left.checked_sub(right)?,
+                BinaryOperator::Mul => left.checked_mul(right)?,
+                // The bitwise operations of two values in range are in range.
+                BinaryOperator::BitAnd => left & right,
+                BinaryOperator::BitOr => left | right,
+                BinaryOperator::BitXor => left ^ right,
+                BinaryOperator::Eq => return Some(Constant::Bool(left == right)),
+                BinaryOperator::Ne => return Some(Constant::Bool(left != right)),
+                BinaryOperator::Lt => return Some(Constant::Bool(left < right)),
+                BinaryOperator::Le => return Some(Constant::Bool(left <= right)),
+                BinaryOperator::Gt => return Some(Constant::Bool(left > right)),
+                BinaryOperator::Ge => return Some(Constant::Bool(left >= right)),
+                // The division and remainder of negative numbers aren't worth the risk.
+                _ => return None,
+            };
+            let (min, max) = integer_range(ty)?;
+            (min..=max).contains(&value).then_some(Constant::Integer(value, ty))
+        }
+        (Constant::Bool(left), Constant::Bool(right)) => match operator {
+            BinaryOperator::And | BinaryOperator::BitAnd => Some(Constant::Bool(left && right)),
+            BinaryOperator::Or | BinaryOperator::BitOr => Some(Constant::Bool(left || right)),
+            BinaryOperator::BitXor | BinaryOperator::Ne => Some(Constant::Bool(left != right)),
+            BinaryOperator::Eq => Some(Constant::Bool(left == right)),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Fold an expression whose sub-expressions are already folded.
+fn fold_expr(expr: &mut Expr) {
+    let folded = match expr {
+        Expr::Binary { operator, left, right } => match (literal(left), literal(right)) {
+            (Some(left), Some(right)) => fold_binary(*operator, left, right).map(Constant::to_expr),
+            // `x + 0`, `x - 0`, `x * 1` and their symmetric can't overflow.
+            _ => match operator {
+                BinaryOperator::Add | BinaryOperator::Sub | BinaryOperator::BitOr | BinaryOperator::BitXor
+                    if is_integer(right, 0) =>
+                {
+                    Some((**left).clone())
+                }
+                BinaryOperator::Add | BinaryOperator::BitOr | BinaryOperator::BitXor if is_integer(left, 0) => {
+                    Some((**right).clone())
+                }
+                BinaryOperator::Mul if is_integer(right, 1) => Some((**left).clone()),
+                BinaryOperator::Mul if is_integer(left, 1) => Some((**right).clone()),
+                BinaryOperator::And if literal(right) == Some(Constant::Bool(true)) => Some((**left).clone()),
+                BinaryOperator::And if literal(left) == Some(Constant::Bool(true)) => Some((**right).clone()),
+                BinaryOperator::Or if literal(right) == Some(Constant::Bool(false)) => Some((**left).clone()),
+                BinaryOperator::Or if literal(left) == Some(Constant::Bool(false)) => Some((**right).clone()),
+                _ => None,
+            },
+        },
+        Expr::Not(value) => match &**value {
+            Expr::Literal(_) => match literal(value) {
+                Some(Constant::Bool(value)) => Some(Constant::Bool(!value).to_expr()),
+                _ => None,
+            },
+            Expr::Not(inner) => Some((**inner).clone()),
+            _ => None,
+        },
+        Expr::If { condition, then_value, else_value } => match literal(condition) {
+            Some(Constant::Bool(true)) => Some((**then_value).clone()),
+            Some(Constant::Bool(false)) => Some((**else_value).clone()),
+            // `if c { true } else { false }` is `c`, the phis of booleans look like that.
+            _ => match (literal(then_value), literal(else_value)) {
+                (Some(Constant::Bool(true)), Some(Constant::Bool(false))) => Some((**condition).clone()),
+                (Some(Constant::Bool(false)), Some(Constant::Bool(true))) => Some(Expr::not((**condition).clone())),
+                _ if then_value == else_value && condition.is_pure() => Some((**then_value).clone()),
+                _ => None,
+            },
+        },
+        _ => None,
+    };
+    if let Some(folded) = folded {
+        *expr = folded;
+    }
+}
+
+/// Replace the `if` statements with a literal condition by the branch that is taken.
+fn fold_branches(block: &mut Block) {
+    let mut statements = Vec::with_capacity(block.statements.len());
+    for statement in block.statements.drain(..) {
+        let Statement::If { condition, then_block, else_block } = statement else {
+            statements.push(statement);
+            continue;
+        };
+        let taken = match literal(&condition) {
+            Some(Constant::Bool(true)) => then_block,
+            Some(Constant::Bool(false)) => else_block.unwrap_or_default(),
+            _ => {
+                statements.push(Statement::If { condition, then_block, else_block });
+                continue;
+            }
+        };
+        // The variables declared in the branch would leak in the enclosing scope so only inline the
+        // branches that don't declare anything.
+        if taken.statements.iter().any(|statement| matches!(statement, Statement::Let { .. })) {
+            statements.push(Statement::If { condition: Expr::literal("true"), then_block: taken, else_block: None });
+        } else {
+            statements.extend(taken.statements);
+        }
+    }
+    block.statements = statements;
+}
+
+pub(crate) fn fold_constants(block: &mut Block) {
+    block.visit_exprs_mut(&mut fold_expr);
+    block.visit_blocks_mut(&mut fold_branches);
+}
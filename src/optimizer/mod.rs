@@ -0,0 +1,38 @@
+//! Optimizations of the generated cairo code.
+//!
+//! The translation is instruction by instruction so it leaves a lot of work to the cairo compiler:
+//! copies (`let x = y;`), flags that are only ever set to one value, constants that could be
+//! folded, mutable placeholders that are declared at the top of a scope and overwritten before
+//! being read... The cairo compiler doesn't remove all of it and each of those costs steps at
+//! runtime. The passes here rewrite the AST of each function until none of them changes anything.
+//!
+//! All the passes work on variable names. The translation gives each value a unique name in its
+//! function so a name is a variable, the only variables that are declared more than once are the
+//! ones declared in a loop body.
+
+mod dead_code;
+mod fold;
+mod propagate;
+mod usage;
+
+use crate::builder::function::CairoFunction;
+
+/// Maximum number of rounds of all the passes. Each round enables the next one (folding creates
+/// constants to propagate, propagating creates dead stores...) but a handful are always enough.
+const MAX_ROUNDS: usize = 16;
+
+/// Optimize the body of `function` in place.
+pub fn optimize_function(function: &mut CairoFunction) {
+    let parameters = function.signature.parameters.0.iter().map(|parameter| parameter.name.clone()).collect::<Vec<_>>();
+    let body = function.body.block_mut();
+    for _ in 0..MAX_ROUNDS {
+        let before = body.clone();
+        fold::fold_constants(body);
+        propagate::propagate(body, &parameters);
+        dead_code::sink_placeholders(body);
+        dead_code::remove_dead_stores(body);
+        if *body == before {
+            break;
+        }
+    }
+}
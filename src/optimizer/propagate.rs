@@ -0,0 +1,74 @@
+//! Constant and copy propagation.
+//!
+//! * `let x = 5_i32;` where `x` is never assigned anything but `5_i32`: the reads of `x` become
+//!   `5_i32`. This also catches the `is_from_<block>` flags of the phis that only have one incoming
+//!   block and are only ever set to `false`.
+//! * `let x = y;` where neither `x` nor `y` is ever reassigned: the reads of `x` become `y`.
+
+use std::collections::HashMap;
+
+use crate::ast::{Block, Expr, Pattern, Statement};
+use crate::optimizer::usage::Usage;
+
+/// Find the variables that always have the value of their declaration.
+fn replacements(usage: &Usage, parameters: &[String]) -> HashMap<String, Expr> {
+    let is_parameter = |name: &str| parameters.iter().any(|parameter| parameter == name);
+    let mut replacements = HashMap::new();
+    for (name, value) in usage.initial_values.iter() {
+        if usage.declarations(name) != 1 || is_parameter(name) {
+            continue;
+        }
+        let replace = match value {
+            Expr::Literal(_) => {
+                !usage.references.contains(name)
+                    && usage.assignments.get(name).map_or(true, |values| values.iter().all(|v| v == value))
+            }
+            // A copy of a variable that is declared only once (so it isn't shadowed or redeclared in a
+            // loop) and never changes.
+            Expr::Variable(source) => {
+                source != name
+                    && !usage.is_assigned(name)
+                    && !usage.is_assigned(source)
+                    && usage.declarations(source) + usize::from(is_parameter(source)) == 1
+            }
+            _ => false,
+        };
+        if replace {
+            replacements.insert(name.clone(), value.clone());
+        }
+    }
+    replacements
+}
+
+pub(crate) fn propagate(block: &mut Block, parameters: &[String]) {
+    let replacements = replacements(&Usage::of(block), parameters);
+    if replacements.is_empty() {
+        return;
+    }
+    // Follow the chains of copies `let b = a; let c = b;` => `c` is replaced by `a`. A copy is declared
+    // after its source so there is no cycle and the chain is at most as long as the replacements.
+    let resolve = |name: &str| {
+        let mut value = replacements[name].clone();
+        for _ in 0..replacements.len() {
+            match &value {
+                Expr::Variable(source) if replacements.contains_key(source) => value = replacements[source].clone(),
+                _ => break,
+            }
+        }
+        value
+    };
+    block.visit_exprs_mut(&mut |expr| {
+        if let Expr::Variable(name) = expr {
+            if replacements.contains_key(name) {
+                *expr = resolve(name);
+            }
+        }
+    });
+    block.visit_blocks_mut(&mut |block| {
+        block.statements.retain(|statement| match statement {
+            Statement::Let { pattern: Pattern::Variable { name, .. }, .. } => !replacements.contains_key(name),
+            Statement::Assign { variable, .. } => !replacements.contains_key(variable),
+            _ => true,
+        })
+    });
+}
@@ -0,0 +1,85 @@
+//! How each variable of a function is used.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::ast::{Block, Expr, Pattern, Statement};
+
+/// Uses of the variables of a block, nested blocks included.
+#[derive(Default, Clone, Debug)]
+pub(crate) struct Usage {
+    /// Number of times each variable is read.
+    pub(crate) reads: HashMap<String, usize>,
+    /// Number of `let` that bind each variable.
+    pub(crate) declarations: HashMap<String, usize>,
+    /// Value of the `let` of the variables declared with a plain `let name = value;`.
+    pub(crate) initial_values: HashMap<String, Expr>,
+    /// Values assigned to each variable after its declaration.
+    pub(crate) assignments: HashMap<String, Vec<Expr>>,
+    /// Variables passed by reference (`ref memory`), each call can change them.
+    pub(crate) references: HashSet<String>,
+}
+
+impl Usage {
+    pub(crate) fn of(block: &Block) -> Self {
+        let mut usage = Usage::default();
+        usage.collect(block);
+        block.visit_exprs(&mut |expr| match expr {
+            Expr::Variable(name) => *usage.reads.entry(name.clone()).or_default() += 1,
+            Expr::Ref(name) => {
+                *usage.reads.entry(name.clone()).or_default() += 1;
+                usage.references.insert(name.clone());
+            }
+            _ => (),
+        });
+        usage
+    }
+
+    fn collect(&mut self, block: &Block) {
+        for statement in block.statements.iter() {
+            match statement {
+                Statement::Let { pattern, value } => {
+                    for name in pattern.names() {
+                        *self.declarations.entry(name.to_owned()).or_default() += 1;
+                    }
+                    if let Pattern::Variable { name, .. } = pattern {
+                        self.initial_values.insert(name.clone(), value.clone());
+                    }
+                }
+                Statement::Assign { variable, value } => {
+                    self.assignments.entry(variable.clone()).or_default().push(value.clone())
+                }
+                Statement::If { then_block, else_block, .. } => {
+                    self.collect(then_block);
+                    else_block.iter().for_each(|block| self.collect(block));
+                }
+                Statement::Loop(body) => self.collect(body),
+                Statement::Expr(_)
+                | Statement::Tail(_)
+                | Statement::Return(_)
+                | Statement::Break
+                | Statement::Continue
+                | Statement::Origin(_) => (),
+            }
+        }
+    }
+
+    pub(crate) fn reads(&self, name: &str) -> usize {
+        self.reads.get(name).copied().unwrap_or_default()
+    }
+
+    pub(crate) fn declarations(&self, name: &str) -> usize {
+        self.declarations.get(name).copied().unwrap_or_default()
+    }
+
+    /// Can the variable change after its declaration, with an assignment or by reference?
+    pub(crate) fn is_assigned(&self, name: &str) -> bool {
+        self.assignments.contains_key(name) || self.references.contains(name)
+    }
+}
+
+/// Does `statement` mention the variable `name` anywhere (read, assignment or declaration)?
+pub(crate) fn mentions(statement: &Statement, name: &str) -> bool {
+    let block = Block::new(vec![statement.clone()]);
+    let usage = Usage::of(&block);
+    usage.reads(name) > 0 || usage.declarations(name) > 0 || usage.is_assigned(name)
+}
@@ -0,0 +1,222 @@
+//! Assemble the translated LLVM modules in a Scarb package that can be built as is:
+//! ```text
+//! <package>/
+//! ├── Scarb.toml
+//! └── src/
+//!     ├── lib.cairo
+//!     ├── <module>.cairo (one per LLVM module)
+//!     ├── soft_float.cairo (the float library, if a module uses floats)
+//!     ├── allocator.cairo (the allocator and the memory, if a module allocates)
+//!     └── <module>.cairo.map.json (source map of the module)
+//! ```
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::ast::printer::{FormatOptions, Printer};
+use crate::ast::{Expr, Item};
+use crate::builder::allocator::{self, MEMORY_TYPE};
+use crate::builder::naming::NameScope;
+use crate::builder::options::FloatMode;
+use crate::builder::soft_float::{self, float_struct, is_library_function, FLOAT_WIDTHS};
+use crate::builder::CairoModule;
+
+/// Cairo edition of the generated package.
+pub const CAIRO_EDITION: &str = "2024_07";
+
+/// Version of the starknet dependency of the packages that have contracts.
+pub const STARKNET_VERSION: &str = "2.8.0";
+
+/// Traits that have to be imported to call their methods on the generated values.
+const METHOD_TRAITS: [(&str, &str); 1] = [("span", "core::array::ToSpanTrait")];
+
+/// Turn a file or crate name (`my-crate`, `Fib.2`) into a valid cairo module/package name
+/// (`my_crate`, `fib_2`).
+pub fn sanitize_module_name(name: &str) -> String {
+    let sanitized =
+        name.chars().map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_lowercase() } else { '_' }).collect::<String>();
+    let sanitized = sanitized.trim_matches('_');
+    if sanitized.is_empty() || sanitized.starts_with(|c: char| c.is_ascii_digit()) {
+        format!("m_{sanitized}")
+    } else {
+        sanitized.to_owned()
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ScarbPackage {
+    /// Name of the package, also the root of the `use` paths.
+    pub(crate) name: String,
+    /// Translated modules with their cairo name in the order they were added.
+    pub(crate) modules: Vec<(String, CairoModule)>,
+    /// Names of the modules of `src/`, the ones of the libraries are reserved.
+    pub(crate) module_names: NameScope,
+    pub(crate) format: FormatOptions,
+}
+
+impl ScarbPackage {
+    pub fn new(name: &str, format: FormatOptions) -> Self {
+        let mut module_names = NameScope::default();
+        module_names.fresh(allocator::MODULE_NAME);
+        module_names.fresh(soft_float::MODULE_NAME);
+        Self { name: sanitize_module_name(name), modules: Vec::new(), module_names, format }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn modules(&self) -> &[(String, CairoModule)] {
+        &self.modules
+    }
+
+    /// Add a translated LLVM module to the package. It will be in `src/<name>.cairo`, with a suffix
+    /// if the name is already taken (two input files can have the same name in different
+    /// directories).
+    pub fn add_module(&mut self, name: &str, module: CairoModule) {
+        let name = self.module_names.fresh(&sanitize_module_name(name));
+        self.modules.push((name, module));
+    }
+
+    /// Content of `Scarb.toml`. The packages with contracts depend on starknet and build them.
+    pub fn manifest(&self) -> String {
+        let manifest = format!(
+            "[package]\nname = \"{}\"\nversion = \"0.1.0\"\nedition = \"{CAIRO_EDITION}\"\n\n[dependencies]\n",
+            self.name
+        );
+        if self.modules.iter().all(|(_, module)| module.contract().is_none()) {
+            return manifest;
+        }
+        format!("{manifest}starknet = \"{STARKNET_VERSION}\"\n\n[[target.starknet-contract]]\n")
+    }
+
+    /// The float library shared by the modules that use floats, see [soft_float].
+    fn float_library(&self) -> Option<FloatMode> {
+        self.modules.iter().find_map(|(_, module)| module.float_library())
+    }
+
+    /// Whether a module allocates, the allocator is shared too, see [allocator].
+    fn allocator(&self) -> bool {
+        self.modules.iter().any(|(_, module)| module.allocator())
+    }
+
+    /// Content of `src/lib.cairo`, it only declares the modules.
+    pub fn lib_cairo(&self) -> String {
+        let allocator = self.allocator().then(|| format!("mod {};\n", allocator::MODULE_NAME));
+        let float_library = self.float_library().map(|_| format!("mod {};\n", soft_float::MODULE_NAME));
+        self.modules.iter().map(|(name, _)| format!("mod {name};\n")).chain(allocator).chain(float_library).collect()
+    }
+
+    /// Add the `use` statements each module needs: the functions it calls that are defined in
+    /// another module and the traits of the methods it calls.
+    fn resolve_uses(&self) -> Vec<CairoModule> {
+        // Function name => modules it's defined in.
+        let mut definitions = HashMap::<&str, Vec<&str>>::new();
+        for (module_name, module) in self.modules.iter() {
+            for function in module.functions() {
+                definitions.entry(&function.signature.name).or_default().push(module_name);
+            }
+        }
+        // The functions of a contract are in the contract module.
+        let paths = self
+            .modules
+            .iter()
+            .map(|(module_name, module)| match module.contract() {
+                Some(contract) => (module_name.clone(), format!("{module_name}::{}", contract.name())),
+                None => (module_name.clone(), module_name.clone()),
+            })
+            .collect::<HashMap<_, _>>();
+        self.modules
+            .iter()
+            .map(|(module_name, module)| {
+                let mut module = module.clone();
+                let mut uses = Vec::new();
+                // The float library is in its own module instead of being repeated in each module.
+                let float_path = format!("{}::{}", self.name, soft_float::MODULE_NAME);
+                if module.float_library.take().is_some() {
+                    uses.extend(FLOAT_WIDTHS.iter().map(|width| format!("{float_path}::{}", float_struct(*width))));
+                }
+                // So is the allocator.
+                let allocator_path = format!("{}::{}", self.name, allocator::MODULE_NAME);
+                if std::mem::take(&mut module.allocator) {
+                    uses.push(format!("{allocator_path}::{MEMORY_TYPE}"));
+                }
+                if module.contract().is_some_and(|contract| contract.entry_points().iter().any(|entry| entry.memory)) {
+                    uses.push(format!("{allocator_path}::MemoryTrait"));
+                }
+                for cairo_function in module.functions() {
+                    cairo_function.body.block().visit_exprs(&mut |expr| match expr {
+                        Expr::Call { function, .. } => {
+                            let defined_in = definitions.get(function.as_str()).map(Vec::as_slice).unwrap_or_default();
+                            match defined_in {
+                                [definition] if definition != module_name => {
+                                    uses.push(format!("{}::{}::{}", self.name, paths[*definition], function))
+                                }
+                                // Defined in this module, or in several modules and we can't tell which one is
+                                // called. Without a `use` cairo reports it if it isn't the local one.
+                                [_, ..] => (),
+                                [] if is_library_function(function) => uses.push(format!("{float_path}::{function}")),
+                                [] if allocator::is_library_item(function) => {
+                                    uses.push(format!("{allocator_path}::{function}"))
+                                }
+                                [] => (),
+                            }
+                        }
+                        Expr::MethodCall { method, .. } => {
+                            if let Some((_, path)) = METHOD_TRAITS.iter().find(|(name, _)| name == method) {
+                                uses.push(path.to_string());
+                            }
+                        }
+                        _ => (),
+                    });
+                }
+                uses.sort();
+                uses.into_iter().for_each(|path| module.push_use(path));
+                module
+            })
+            .collect()
+    }
+
+    /// All the files of the package with their path relative to the package root.
+    pub fn files(&self) -> Vec<(PathBuf, String)> {
+        let mut files = vec![
+            (PathBuf::from("Scarb.toml"), self.manifest()),
+            (PathBuf::from("src").join("lib.cairo"), self.lib_cairo()),
+        ];
+        for ((name, _), module) in self.modules.iter().zip(self.resolve_uses()) {
+            let file = format!("{name}.cairo");
+            let (code, source_map) = module.format_with_source_map(self.format, &file);
+            files.push((PathBuf::from("src").join(format!("{file}.map.json")), source_map.to_json()));
+            files.push((PathBuf::from("src").join(file), format!("{code}\n")));
+        }
+        if self.allocator() {
+            let allocator = Printer::new(self.format).item(&Item::Verbatim(allocator::library()), 0);
+            files.push((
+                PathBuf::from("src").join(format!("{}.cairo", allocator::MODULE_NAME)),
+                format!("{allocator}\n"),
+            ));
+        }
+        if let Some(mode) = self.float_library() {
+            let library = Printer::new(self.format).item(&Item::Verbatim(soft_float::library(mode)), 0);
+            files.push((
+                PathBuf::from("src").join(format!("{}.cairo", soft_float::MODULE_NAME)),
+                format!("{library}\n"),
+            ));
+        }
+        files
+    }
+
+    /// Write the package in `directory`. It's created if it doesn't exist and existing files are
+    /// overwritten.
+    pub fn write(&self, directory: &Path) -> std::io::Result<()> {
+        for (path, content) in self.files() {
+            let path = directory.join(path);
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(path, content)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,218 @@
+//! Map the generated cairo code back to the LLVM instructions it was translated from and, when the
+//! IR has debug info, to the original source code.
+
+use std::collections::HashMap;
+use std::fmt::Display;
+
+/// Where a cairo statement comes from.
+#[derive(Default, Clone, PartialEq, Eq, Debug)]
+pub struct SourceOrigin {
+    /// Name of the LLVM function.
+    pub function: String,
+    /// Name of the LLVM basic block.
+    pub block: String,
+    /// The LLVM instruction as printed in the IR, without its metadata.
+    pub instruction: String,
+    /// Location in the original source code if the IR has debug info.
+    pub location: Option<DebugLocation>,
+}
+
+impl Display for SourceOrigin {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&format!("{}::{}: {}", self.function, self.block, self.instruction))?;
+        if let Some(location) = &self.location {
+            f.write_str(&format!(" ({location})"))?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Default, Clone, PartialEq, Eq, Debug)]
+pub struct DebugLocation {
+    pub file: String,
+    pub line: u32,
+    pub column: u32,
+}
+
+impl Display for DebugLocation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&format!("{}:{}:{}", self.file, self.line, self.column))
+    }
+}
+
+/// Get the value of a field of a printed metadata node.
+/// `!DILocation(line: 3, column: 5, scope: !7)` with `column` gives `5`.
+pub(crate) fn metadata_field<'a>(node: &'a str, field: &str) -> Option<&'a str> {
+    let pattern = format!("{field}: ");
+    // The field name has to be preceded by `(` or a space so `line` doesn't match `baseline`.
+    let start = node
+        .match_indices(&pattern)
+        .find(|(index, _)| *index > 0 && matches!(node.as_bytes()[index - 1], b'(' | b' '))
+        .map(|(index, _)| index + pattern.len())?;
+    let value = &node[start..];
+    if let Some(string) = value.strip_prefix('"') {
+        return string.split('"').next();
+    }
+    value.split([',', ')']).next().map(str::trim)
+}
+
+/// Debug info metadata of a module. LLVM doesn't let us walk the metadata nodes so we get them from
+/// the printed module where they're all defined at the end:
+/// !15 = !DILocation(line: 3, column: 5, scope: !7)
+/// !7 = distinct !DISubprogram(name: "fib", scope: !8, file: !8, line: 2, ...)
+/// !8 = !DIFile(filename: "src/lib.rs", directory: "/home/user/fib")
+#[derive(Default, Clone, PartialEq, Debug)]
+pub struct DebugMetadata {
+    /// Metadata id => printed node without `distinct`.
+    nodes: HashMap<u32, String>,
+}
+
+impl DebugMetadata {
+    pub fn parse(module: &str) -> Self {
+        let nodes = module
+            .lines()
+            .filter_map(|line| {
+                let (id, node) = line.strip_prefix('!')?.split_once(" = ")?;
+                Some((id.parse().ok()?, node.trim_start_matches("distinct ").to_owned()))
+            })
+            .collect();
+        Self { nodes }
+    }
+
+    /// Get a node from a reference `!12`.
+    pub fn node(&self, reference: &str) -> Option<&str> {
+        self.nodes.get(&reference.strip_prefix('!')?.parse().ok()?).map(String::as_str)
+    }
+
+    /// Get the `!dbg` attachment of a printed instruction. `%res = add i32 %a, %b, !dbg !15`
+    pub fn instruction_location(&self, instruction: &str) -> Option<DebugLocation> {
+        let (_, reference) = instruction.split_once("!dbg ")?;
+        let reference = reference.split([',', ' ']).next()?;
+        self.location(self.node(reference)?)
+    }
+
+    /// Resolve a `!DILocation` node.
+    fn location(&self, node: &str) -> Option<DebugLocation> {
+        if !node.starts_with("!DILocation(") {
+            return None;
+        }
+        let line = metadata_field(node, "line")?.parse().ok()?;
+        let column = metadata_field(node, "column").and_then(|column| column.parse().ok()).unwrap_or_default();
+        let file = self.scope_file(metadata_field(node, "scope")?).unwrap_or_default();
+        Some(DebugLocation { file, line, column })
+    }
+
+    /// Get the file of a scope (subprogram, lexical block...). Scopes that don't have a `file`
+    /// field point to their parent scope.
+    fn scope_file(&self, reference: &str) -> Option<String> {
+        let node = self.node(reference)?;
+        if node.starts_with("!DIFile(") {
+            return metadata_field(node, "filename").map(str::to_owned);
+        }
+        match metadata_field(node, "file") {
+            Some(file) => self.scope_file(file),
+            None => self.scope_file(metadata_field(node, "scope")?),
+        }
+    }
+}
+
+/// Lines of the generated cairo file that come from the same LLVM instruction.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct SourceMapEntry {
+    /// First line, starting at 1.
+    pub start_line: usize,
+    /// Last line, included.
+    pub end_line: usize,
+    pub origin: SourceOrigin,
+}
+
+/// Source map of a generated cairo file.
+#[derive(Default, Clone, PartialEq, Eq, Debug)]
+pub struct SourceMap {
+    /// Name of the cairo file the map describes.
+    pub file: String,
+    pub entries: Vec<SourceMapEntry>,
+}
+
+impl SourceMap {
+    pub fn new(file: String) -> Self {
+        Self { file, entries: Vec::new() }
+    }
+
+    /// Map the line `line` of the generated file to `origin`. Consecutive lines with the same
+    /// origin are merged in a single entry.
+    pub fn add_line(&mut self, line: usize, origin: &SourceOrigin) {
+        match self.entries.last_mut() {
+            Some(entry) if entry.end_line + 1 == line && &entry.origin == origin => entry.end_line = line,
+            _ => self.entries.push(SourceMapEntry { start_line: line, end_line: line, origin: origin.clone() }),
+        }
+    }
+
+    /// Get the origin of a line of the generated file.
+    pub fn origin(&self, line: usize) -> Option<&SourceOrigin> {
+        self.entries.iter().find(|entry| (entry.start_line..=entry.end_line).contains(&line)).map(|entry| &entry.origin)
+    }
+
+    /// Serialize the map to JSON:
+    /// ```json
+    /// {
+    ///   "version": 1,
+    ///   "file": "fib.cairo",
+    ///   "mappings": [
+    ///     {"lines": [3, 3], "function": "fib", "block": "start", "instruction": "%3 = icmp eq i128 %n, 0", "source": null}
+    ///   ]
+    /// }
+    /// ```
+    pub fn to_json(&self) -> String {
+        let mappings = self
+            .entries
+            .iter()
+            .map(|entry| {
+                let source = entry.origin.location.as_ref().map_or_else(
+                    || "null".to_owned(),
+                    |location| {
+                        format!(
+                            "{{\"file\": {}, \"line\": {}, \"column\": {}}}",
+                            json_string(&location.file),
+                            location.line,
+                            location.column
+                        )
+                    },
+                );
+                format!(
+                    "    {{\"lines\": [{}, {}], \"function\": {}, \"block\": {}, \"instruction\": {}, \"source\": {}}}",
+                    entry.start_line,
+                    entry.end_line,
+                    json_string(&entry.origin.function),
+                    json_string(&entry.origin.block),
+                    json_string(&entry.origin.instruction),
+                    source
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(",\n");
+        format!(
+            "{{\n  \"version\": 1,\n  \"file\": {},\n  \"mappings\": [\n{}\n  ]\n}}\n",
+            json_string(&self.file),
+            mappings
+        )
+    }
+}
+
+/// Quote and escape a string for JSON.
+pub(crate) fn json_string(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len() + 2);
+    escaped.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\t' => escaped.push_str("\\t"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
@@ -0,0 +1,164 @@
+//! Snapshot tests of the examples. Each directory of `examples/` with a `<name>.rs` file is an
+//! example:
+//! ```text
+//! examples/fib/
+//! ├── fib.rs     (source)
+//! ├── fib.ll     (generated from the source, not checked in)
+//! └── fib.cairo  (expected translation, checked in)
+//! ```
+//! The IR is regenerated from the source with the same command as `scripts/generate_llvm.sh`
+//! then translated and compared with the golden `.cairo` file. With `UPDATE_SNAPSHOTS=1` the golden
+//! files are overwritten with the current translation instead.
+//!
+//! The other directories hold examples that only have their IR, checked in, like the reproducers
+//! the fuzzer saves in `examples/fuzz`. Each `.ll` file is an example with its golden file next
+//! to it:
+//! ```text
+//! examples/fuzz/
+//! ├── fuzz_61474_3.ll
+//! └── fuzz_61474_3.cairo
+//! ```
+
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::compile;
+
+/// Environment variable that turns the comparison into an update of the golden files.
+pub const UPDATE_SNAPSHOTS: &str = "UPDATE_SNAPSHOTS";
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Example {
+    /// `<name>` or `<directory>/<name>` for the examples that only have their IR.
+    pub name: String,
+    /// `examples/<name>/<name>.rs`, `None` if the IR is checked in.
+    pub source: Option<PathBuf>,
+    /// `examples/<name>/<name>.ll`
+    pub ir: PathBuf,
+    /// `examples/<name>/<name>.cairo`
+    pub golden: PathBuf,
+}
+
+/// Result of the check of an example that didn't fail.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Snapshot {
+    /// The translation is the same as the golden file.
+    Matches,
+    /// The golden file was written.
+    Updated,
+    /// There is no IR and `rustc` isn't available to generate it.
+    Skipped,
+}
+
+/// Is the update mode enabled.
+pub fn update_snapshots() -> bool {
+    std::env::var(UPDATE_SNAPSHOTS).is_ok_and(|value| !value.is_empty() && value != "0")
+}
+
+/// Find the examples in `directory` sorted by name.
+pub fn discover(directory: &Path) -> std::io::Result<Vec<Example>> {
+    let mut examples = Vec::new();
+    for entry in fs::read_dir(directory)? {
+        let path = entry?.path();
+        let Some(name) = path.file_name().and_then(|name| name.to_str()).map(str::to_owned) else {
+            continue;
+        };
+        if !path.is_dir() {
+            continue;
+        }
+        let source = path.join(format!("{name}.rs"));
+        if source.is_file() {
+            examples.push(Example {
+                ir: path.join(format!("{name}.ll")),
+                golden: path.join(format!("{name}.cairo")),
+                source: Some(source),
+                name,
+            });
+            continue;
+        }
+        // No source, each IR file is an example.
+        for entry in fs::read_dir(&path)? {
+            let ir = entry?.path();
+            let Some(stem) = ir.file_stem().and_then(|stem| stem.to_str()).map(str::to_owned) else {
+                continue;
+            };
+            if ir.is_file() && ir.extension().is_some_and(|extension| extension == "ll") {
+                examples.push(Example {
+                    name: format!("{name}/{stem}"),
+                    source: None,
+                    golden: ir.with_extension("cairo"),
+                    ir,
+                });
+            }
+        }
+    }
+    examples.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(examples)
+}
+
+impl Example {
+    /// Compile the source to LLVM IR. Returns `false` if there is no source or `rustc` isn't
+    /// available.
+    pub fn regenerate_ir(&self) -> Result<bool, String> {
+        let Some(source) = &self.source else {
+            return Ok(false);
+        };
+        // Other tests read the IR while we regenerate it so it's replaced at once.
+        let temporary = self.ir.with_extension("ll.tmp");
+        let output = Command::new("rustc")
+            .args(["--crate-type=lib", "-C", "opt-level=3", "--emit=llvm-ir"])
+            .arg(source)
+            .arg("-o")
+            .arg(&temporary)
+            .output();
+        match output {
+            Ok(output) if output.status.success() => fs::rename(&temporary, &self.ir)
+                .map(|_| true)
+                .map_err(|error| format!("Failed to write {}: {error}", self.ir.display())),
+            Ok(output) => {
+                Err(format!("Failed to compile {}:\n{}", source.display(), String::from_utf8_lossy(&output.stderr)))
+            }
+            Err(error) if error.kind() == ErrorKind::NotFound => Ok(false),
+            Err(error) => Err(format!("Failed to run rustc: {error}")),
+        }
+    }
+
+    /// Regenerate the IR, translate it and compare the translation with the golden file (or update
+    /// it in update mode). The IR that is already there is used when there is no source or `rustc`
+    /// isn't available.
+    pub fn check(&self, update: bool) -> Result<Snapshot, String> {
+        if !self.regenerate_ir()? && !self.ir.is_file() {
+            return Ok(Snapshot::Skipped);
+        }
+        let translation = format!("{}\n", compile(&self.ir.to_string_lossy())?);
+        if update {
+            fs::write(&self.golden, translation)
+                .map_err(|error| format!("Failed to write {}: {error}", self.golden.display()))?;
+            return Ok(Snapshot::Updated);
+        }
+        let golden = fs::read_to_string(&self.golden).map_err(|error| {
+            format!("Failed to read {}: {error}, run with {UPDATE_SNAPSHOTS}=1 to create it", self.golden.display())
+        })?;
+        if golden == translation {
+            return Ok(Snapshot::Matches);
+        }
+        // Show the first line that differs, the whole files are usually too long to be readable.
+        let line_count = golden.lines().count().max(translation.lines().count());
+        let (line, (expected, actual)) = golden
+            .lines()
+            .chain(std::iter::repeat("<end of file>"))
+            .zip(translation.lines().chain(std::iter::repeat("<end of file>")))
+            .take(line_count + 1)
+            .enumerate()
+            .find(|(_, (expected, actual))| expected != actual)
+            .unwrap_or((0, ("", "")));
+        Err(format!(
+            "{} doesn't match the translation at line {}:\nexpected: {expected}\n  actual: {actual}\nrun with \
+             {UPDATE_SNAPSHOTS}=1 to update it",
+            self.golden.display(),
+            line + 1
+        ))
+    }
+}
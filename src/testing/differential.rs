@@ -0,0 +1,531 @@
+//! Differential testing: run the LLVM functions and their cairo translation on the same inputs and
+//! report every input for which they don't agree.
+//!
+//! * The LLVM side runs with `lli`, one process per input. Each input is a small wrapper function
+//!   `i128 @__differential_case_<n>()` appended to the IR that calls the tested function with
+//!   constant arguments and an entry point `i32 @__differential_main_<n>()` that prints its result.
+//!   The rust panic functions exit the process with [PANIC_EXIT_CODE]: a panic can't unwind through
+//!   the JIT-ed frames and a case that crashes doesn't take the test down with it.
+//! * The cairo side runs with `scarb cairo-run`. The translation is written as a Scarb package with
+//!   a `cases` module that has the same wrappers.
+//!
+//! Only the functions that take and return integers (`bool` included) or return nothing are
+//! tested. Results are compared as bit patterns of the width of the LLVM return type so `-1_i8`
+//! and `255` are the same value.
+
+use std::fmt::Display;
+use std::fs;
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use inkwell::context::Context;
+use inkwell::memory_buffer::MemoryBuffer;
+use inkwell::module::Module;
+use inkwell::types::BasicTypeEnum;
+use inkwell::values::FunctionValue;
+use inkwell::AddressSpace;
+
+use crate::ast::printer::FormatOptions;
+use crate::ast::{Block, Expr, Statement};
+use crate::builder::function::panic::is_panic_function;
+use crate::builder::function::{CairoFunction, CairoFunctionBody, CairoFunctionSignature};
+use crate::builder::options::TranslationOptions;
+use crate::builder::types::CairoStructs;
+use crate::builder::{CairoFunctions, CairoModule};
+use crate::compile_with_options;
+use crate::package::ScarbPackage;
+use crate::testing::Rng;
+
+#[derive(Clone, Debug)]
+pub struct DifferentialConfig {
+    /// Number of inputs per function, the edge cases of the range included.
+    pub cases: usize,
+    /// Seed of the random inputs so a failing run can be reproduced.
+    pub seed: u64,
+    /// Range the inputs are drawn from. It's clamped to the range of each parameter type. Keep it
+    /// small for the functions that loop `n` times.
+    pub range: RangeInclusive<i128>,
+    pub options: TranslationOptions,
+    /// `scarb` executable.
+    pub scarb: PathBuf,
+    /// `lli` executable, the LLVM interpreter/JIT of the LLVM version the IR comes from.
+    pub lli: PathBuf,
+    /// Directory the cairo package is written in.
+    pub directory: PathBuf,
+}
+
+impl Default for DifferentialConfig {
+    fn default() -> Self {
+        Self {
+            cases: 8,
+            seed: 0x5eed,
+            range: 0..=32,
+            options: TranslationOptions::default(),
+            scarb: PathBuf::from("scarb"),
+            lli: PathBuf::from("lli"),
+            directory: std::env::temp_dir().join("llvm-to-cairo-differential"),
+        }
+    }
+}
+
+/// What a function did with an input.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Outcome {
+    /// The function returned nothing.
+    Unit,
+    /// The function returned this value, as a bit pattern of the width of its return type.
+    Value(u128),
+    Panic,
+}
+
+impl Display for Outcome {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Outcome::Unit => f.write_str("returned ()"),
+            Outcome::Value(value) => f.write_str(&format!("returned {value}")),
+            Outcome::Panic => f.write_str("panicked"),
+        }
+    }
+}
+
+/// An input for which LLVM and cairo don't agree.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Mismatch {
+    /// LLVM name of the function.
+    pub function: String,
+    pub inputs: Vec<i128>,
+    pub llvm: Outcome,
+    pub cairo: Outcome,
+}
+
+impl Display for Mismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let inputs = self.inputs.iter().map(ToString::to_string).collect::<Vec<_>>().join(", ");
+        f.write_str(&format!("{}({inputs}): llvm {} but cairo {}", self.function, self.llvm, self.cairo))
+    }
+}
+
+/// A function we know how to call: integer parameters and an integer or void return type.
+#[derive(Clone, Debug)]
+pub(crate) struct TestedFunction {
+    /// LLVM symbol.
+    pub(crate) symbol: String,
+    /// Name of the translated function.
+    pub(crate) cairo_name: String,
+    /// Bit width of each parameter.
+    pub(crate) parameters: Vec<u32>,
+    /// Bit width of the return value, `None` for `void`.
+    pub(crate) return_width: Option<u32>,
+}
+
+/// One call of a tested function.
+#[derive(Clone, Debug)]
+pub(crate) struct Case {
+    pub(crate) function: usize,
+    pub(crate) inputs: Vec<i128>,
+}
+
+/// Range of the values of a `width` bits signed integer (cairo integers are signed like rust's
+/// `iN`, `i1` is a `bool`).
+fn type_range(width: u32) -> RangeInclusive<i128> {
+    match width {
+        1 => 0..=1,
+        128.. => i128::MIN..=i128::MAX,
+        _ => -(1 << (width - 1))..=(1 << (width - 1)) - 1,
+    }
+}
+
+fn mask(value: u128, width: u32) -> u128 {
+    if width >= 128 {
+        value
+    } else {
+        value & ((1 << width) - 1)
+    }
+}
+
+/// Bit width of an integer type, `None` for the other types.
+fn int_width(ty: BasicTypeEnum) -> Option<u32> {
+    match ty {
+        BasicTypeEnum::IntType(int_ty) => Some(int_ty.get_bit_width()),
+        _ => None,
+    }
+}
+
+fn tested_function(function: &FunctionValue, cairo_name: &str) -> Option<TestedFunction> {
+    let parameters = function.get_param_iter().map(|param| int_width(param.get_type())).collect::<Option<Vec<_>>>()?;
+    let return_width = match function.get_type().get_return_type() {
+        Some(ty) => Some(int_width(ty)?),
+        None => None,
+    };
+    Some(TestedFunction {
+        symbol: function.get_name().to_string_lossy().into_owned(),
+        cairo_name: cairo_name.to_owned(),
+        parameters,
+        return_width,
+    })
+}
+
+/// Generate the inputs: first the edge cases of the range (both ends, 0 and 1) then random values.
+pub(crate) fn generate_cases(functions: &[TestedFunction], config: &DifferentialConfig) -> Vec<Case> {
+    let mut rng = Rng::new(config.seed);
+    let mut cases = Vec::new();
+    for (index, function) in functions.iter().enumerate() {
+        for case in 0..config.cases {
+            let inputs = function
+                .parameters
+                .iter()
+                .map(|width| {
+                    let ty_range = type_range(*width);
+                    let range = *config.range.start().max(ty_range.start())..=*config.range.end().min(ty_range.end());
+                    // The range doesn't overlap the type, use the whole type.
+                    let range = if range.is_empty() { ty_range } else { range };
+                    let edge_cases = [*range.start(), *range.end(), 0, 1];
+                    match edge_cases.get(case) {
+                        Some(value) if range.contains(value) => *value,
+                        _ => rng.in_range(&range),
+                    }
+                })
+                .collect();
+            cases.push(Case { function: index, inputs });
+        }
+    }
+    cases
+}
+
+/// Typed LLVM constant of an input: `i32 5`, `i1 true`.
+fn llvm_constant(value: i128, width: u32) -> String {
+    match width {
+        1 => format!("i1 {}", value != 0),
+        _ => format!("i{width} {value}"),
+    }
+}
+
+/// Exit code of the LLVM cases that panicked, the one of the rust runtime.
+const PANIC_EXIT_CODE: i32 = 101;
+
+/// `i128 @__differential_case_<n>()` calls the tested function and returns its result extended to
+/// 128 bits.
+fn llvm_case(index: usize, case: &Case, function: &TestedFunction) -> String {
+    let arguments = case
+        .inputs
+        .iter()
+        .zip(function.parameters.iter())
+        .map(|(value, width)| llvm_constant(*value, *width))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let body = match function.return_width {
+        Some(width) if width < 128 => format!(
+            "  %result = call i{width} @\"{}\"({arguments})\n  %extended = zext i{width} %result to i128\n  ret i128 \
+             %extended",
+            function.symbol
+        ),
+        Some(_) => format!("  %result = call i128 @\"{}\"({arguments})\n  ret i128 %result", function.symbol),
+        None => format!("  call void @\"{}\"({arguments})\n  ret i128 0", function.symbol),
+    };
+    format!("\ndefine i128 @__differential_case_{index}() {{\n{body}\n}}\n")
+}
+
+/// Add the entry points of the cases to `module`: `i32 @__differential_main_<n>()` prints the
+/// result of the case as two 64 bits halves (`printf` can't print 128 bits integers). The rust
+/// panic functions that are only declared get a body that exits with [PANIC_EXIT_CODE].
+fn add_llvm_entry_points(module: &Module, cases: &[Case]) {
+    let context = module.get_context();
+    let builder = context.create_builder();
+    let (i32_type, i64_type, i128_type) = (context.i32_type(), context.i64_type(), context.i128_type());
+    let ptr_type = context.ptr_type(AddressSpace::default());
+    // The IR can already declare them.
+    let exit = module
+        .get_function("exit")
+        .unwrap_or_else(|| module.add_function("exit", context.void_type().fn_type(&[i32_type.into()], false), None));
+    let printf = module
+        .get_function("printf")
+        .unwrap_or_else(|| module.add_function("printf", i32_type.fn_type(&[ptr_type.into()], true), None));
+
+    for function in module.get_functions().filter(|function| function.count_basic_blocks() == 0) {
+        if !is_panic_function(&function.get_name().to_string_lossy()) {
+            continue;
+        }
+        builder.position_at_end(context.append_basic_block(function, "start"));
+        builder
+            .build_call(exit, &[i32_type.const_int(PANIC_EXIT_CODE as u64, false).into()], "")
+            .and_then(|_| builder.build_unreachable())
+            .expect("Panic function should be built");
+    }
+
+    for index in 0..cases.len() {
+        let case = module.get_function(&format!("__differential_case_{index}")).expect("Case should be in the module");
+        let main = module.add_function(&format!("__differential_main_{index}"), i32_type.fn_type(&[], false), None);
+        builder.position_at_end(context.append_basic_block(main, "start"));
+        let result = builder
+            .build_call(case, &[], "result")
+            .expect("Case call should be built")
+            .try_as_basic_value()
+            .left()
+            .expect("Cases return an i128")
+            .into_int_value();
+        let high = builder
+            .build_right_shift(result, i128_type.const_int(64, false), false, "shifted")
+            .and_then(|shifted| builder.build_int_truncate(shifted, i64_type, "high"))
+            .expect("High half should be built");
+        let low = builder.build_int_truncate(result, i64_type, "low").expect("Low half should be built");
+        builder
+            .build_global_string_ptr("%llu %llu\n", "format")
+            .and_then(|format| {
+                builder.build_call(printf, &[format.as_pointer_value().into(), high.into(), low.into()], "")
+            })
+            .and_then(|_| builder.build_return(Some(&i32_type.const_zero())))
+            .expect("Main should be built");
+    }
+}
+
+/// Parse the output of a case run with `lli`: the exit code is [PANIC_EXIT_CODE] if it panicked
+/// else it printed the high and low halves of the result.
+pub(crate) fn parse_llvm_output(code: Option<i32>, stdout: &str, return_width: Option<u32>) -> Result<Outcome, String> {
+    match code {
+        Some(PANIC_EXIT_CODE) => return Ok(Outcome::Panic),
+        Some(0) => (),
+        _ => return Err(format!("lli exited with {code:?}:\n{stdout}")),
+    }
+    let halves = stdout.split_whitespace().map(str::parse::<u64>).collect::<Result<Vec<_>, _>>();
+    match (halves.as_deref(), return_width) {
+        (Ok([_, _]), None) => Ok(Outcome::Unit),
+        (Ok([high, low]), Some(width)) => Ok(Outcome::Value(mask((u128::from(*high) << 64) | u128::from(*low), width))),
+        _ => Err(format!("Unexpected lli output:\n{stdout}")),
+    }
+}
+
+/// Run each case with `lli` in its own process.
+fn run_llvm(
+    ir: &str,
+    functions: &[TestedFunction],
+    cases: &[Case],
+    config: &DifferentialConfig,
+) -> Result<Vec<Outcome>, String> {
+    let mut ir = ir.to_owned();
+    for (index, case) in cases.iter().enumerate() {
+        ir.push_str(&llvm_case(index, case, &functions[case.function]));
+    }
+    let context = Context::create();
+    let module = context
+        .create_module_from_ir(MemoryBuffer::create_from_memory_range_copy(ir.as_bytes(), "differential"))
+        .map_err(|error| error.to_string())?;
+    add_llvm_entry_points(&module, cases);
+    let path = config.directory.join("differential.ll");
+    fs::create_dir_all(&config.directory)
+        .and_then(|_| fs::write(&path, module.print_to_string().to_string()))
+        .map_err(|error| format!("Failed to write {}: {error}", path.display()))?;
+    cases
+        .iter()
+        .enumerate()
+        .map(|(index, case)| {
+            let output = Command::new(&config.lli)
+                .arg(format!("--entry-function=__differential_main_{index}"))
+                .arg(&path)
+                .output()
+                .map_err(|error| format!("Failed to run lli: {error}"))?;
+            parse_llvm_output(
+                output.status.code(),
+                &String::from_utf8_lossy(&output.stdout),
+                functions[case.function].return_width,
+            )
+            .map_err(|error| format!("{error}{}", String::from_utf8_lossy(&output.stderr)))
+        })
+        .collect()
+}
+
+/// Cairo literal of an input.
+fn cairo_literal(value: i128, width: u32) -> Expr {
+    match width {
+        1 => Expr::literal((value != 0).to_string()),
+        _ => Expr::literal(format!("{value}_i{width}")),
+    }
+}
+
+/// `fn case_<n>() -> <ty> { return <function>(<inputs>); }`
+fn cairo_cases(functions: &[TestedFunction], cases: &[Case]) -> CairoModule {
+    let mut cairo_functions = CairoFunctions::default();
+    for (index, case) in cases.iter().enumerate() {
+        let function = &functions[case.function];
+        let arguments = case
+            .inputs
+            .iter()
+            .zip(function.parameters.iter())
+            .map(|(value, width)| cairo_literal(*value, *width))
+            .collect();
+        let return_type = match function.return_width {
+            Some(1) => "bool".to_owned(),
+            Some(width) => format!("i{width}"),
+            None => "()".to_owned(),
+        };
+        let mut body = Block::default();
+        body.push(Statement::Return(Some(Expr::call(function.cairo_name.clone(), arguments))));
+        cairo_functions.push_function(CairoFunction {
+            signature: CairoFunctionSignature::new(format!("case_{index}"), Vec::new(), return_type),
+            body: CairoFunctionBody::new(body),
+            ..Default::default()
+        });
+    }
+    CairoModule::new(CairoStructs::default(), cairo_functions)
+}
+
+/// Get the bit pattern of the value printed by `scarb cairo-run`. It's a `felt252` so negative
+/// values are printed as `P - x`. Integers are at most 128 bits so a value that doesn't fit in
+/// 128 bits is negative, and as `P = 1 mod 2^128` its bit pattern is `x - 1 mod 2^128`.
+pub(crate) fn felt_bits(printed: &str) -> Option<u128> {
+    let (digits, radix) = match printed.strip_prefix("0x") {
+        Some(hex) => (hex, 16),
+        None => (printed, 10),
+    };
+    let mut value = 0u128;
+    let mut overflowed = false;
+    for digit in digits.chars() {
+        let digit = u128::from(digit.to_digit(radix)?);
+        let (shifted, overflow_mul) = value.overflowing_mul(radix.into());
+        let (sum, overflow_add) = shifted.overflowing_add(digit);
+        overflowed |= overflow_mul || overflow_add;
+        value = sum;
+    }
+    Some(if overflowed { value.wrapping_sub(1) } else { value })
+}
+
+/// Parse the output of `scarb cairo-run`:
+/// Run completed successfully, returning [55]
+/// Run panicked with [39878429859757942499084499860145094553463, ].
+pub(crate) fn parse_cairo_output(output: &str, return_width: Option<u32>) -> Result<Outcome, String> {
+    if output.contains("Run panicked") {
+        return Ok(Outcome::Panic);
+    }
+    let (_, returned) =
+        output.split_once("returning [").ok_or_else(|| format!("Unexpected cairo-run output:\n{output}"))?;
+    let returned = returned.split(']').next().unwrap_or_default();
+    match return_width {
+        None => Ok(Outcome::Unit),
+        Some(width) => {
+            let value = returned.split([',', ' ']).find(|value| !value.is_empty()).and_then(felt_bits);
+            value
+                .map(|value| Outcome::Value(mask(value, width)))
+                .ok_or_else(|| format!("Unexpected result [{returned}]"))
+        }
+    }
+}
+
+/// Number of steps of a cairo run from the output of `--print-resource-usage`.
+fn parse_cairo_steps(output: &str) -> Option<usize> {
+    output.lines().find_map(|line| line.trim().strip_prefix("steps: ")).and_then(|steps| steps.trim().parse().ok())
+}
+
+/// Result of a case on the cairo side.
+#[derive(Clone, Copy, Debug)]
+struct CairoRun {
+    outcome: Outcome,
+    /// `None` if the run panicked before the resources were printed.
+    steps: Option<usize>,
+}
+
+/// Write the translation of `path` with the cases as a Scarb package and run each case.
+fn run_cairo(
+    path: &str,
+    functions: &[TestedFunction],
+    cases: &[Case],
+    config: &DifferentialConfig,
+) -> Result<Vec<CairoRun>, String> {
+    let mut package = ScarbPackage::new("differential", FormatOptions::default());
+    package.add_module(&module_name(path), compile_with_options(path, config.options.clone())?);
+    package.add_module("cases", cairo_cases(functions, cases));
+    for (file, mut content) in package.files() {
+        if file == Path::new("Scarb.toml") {
+            // The generated loops are bounded by the LLVM code, not by the gas.
+            content.push_str("\n[cairo]\nenable-gas = false\n");
+        }
+        let file = config.directory.join(file);
+        fs::create_dir_all(file.parent().expect("Package files are in the package directory"))
+            .and_then(|_| fs::write(&file, content))
+            .map_err(|error| format!("Failed to write {}: {error}", file.display()))?;
+    }
+    let manifest = config.directory.join("Scarb.toml");
+    cases
+        .iter()
+        .enumerate()
+        .map(|(index, case)| {
+            let mut command = Command::new(&config.scarb);
+            command
+                .arg("--manifest-path")
+                .arg(&manifest)
+                .arg("cairo-run")
+                .arg("--function")
+                .arg(format!("case_{index}"))
+                .arg("--print-resource-usage");
+            // The package only has to be built once.
+            if index > 0 {
+                command.arg("--no-build");
+            }
+            let output = command.output().map_err(|error| format!("Failed to run scarb: {error}"))?;
+            let output =
+                format!("{}{}", String::from_utf8_lossy(&output.stdout), String::from_utf8_lossy(&output.stderr));
+            let outcome = parse_cairo_output(&output, functions[case.function].return_width)?;
+            Ok(CairoRun { outcome, steps: parse_cairo_steps(&output) })
+        })
+        .collect()
+}
+
+fn module_name(path: &str) -> String {
+    Path::new(path).file_stem().and_then(|stem| stem.to_str()).unwrap_or("module").to_owned()
+}
+
+/// Read the LLVM IR file at `path` and find the functions we can test.
+fn tested_functions(path: &str, config: &DifferentialConfig) -> Result<(String, Vec<TestedFunction>), String> {
+    let ir = fs::read_to_string(path).map_err(|error| format!("Failed to read {path}: {error}"))?;
+    let translated = compile_with_options(path, config.options.clone())?;
+
+    let context = Context::create();
+    let module = context
+        .create_module_from_ir(MemoryBuffer::create_from_memory_range_copy(ir.as_bytes(), path))
+        .map_err(|error| error.to_string())?;
+    // Only the public functions can be called from the cases module.
+    let functions = translated
+        .functions()
+        .iter()
+        .filter(|cairo_function| cairo_function.signature.public)
+        .filter_map(|cairo_function| {
+            let function = module.get_function(&cairo_function.symbol)?;
+            tested_function(&function, &cairo_function.signature.name)
+        })
+        .collect::<Vec<_>>();
+    Ok((ir, functions))
+}
+
+/// Run the functions of the LLVM IR file at `path` and their translation on generated inputs.
+/// Returns the inputs for which the results differ.
+pub fn differential_test(path: &str, config: &DifferentialConfig) -> Result<Vec<Mismatch>, String> {
+    let (ir, functions) = tested_functions(path, config)?;
+    let cases = generate_cases(&functions, config);
+    if cases.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let llvm = run_llvm(&ir, &functions, &cases, config)?;
+    let cairo = run_cairo(path, &functions, &cases, config)?;
+    Ok(cases
+        .into_iter()
+        .zip(llvm.into_iter().zip(cairo.into_iter().map(|run| run.outcome)))
+        .filter(|(_, (llvm, cairo))| llvm != cairo)
+        .map(|(case, (llvm, cairo))| Mismatch {
+            function: functions[case.function].symbol.clone(),
+            inputs: case.inputs,
+            llvm,
+            cairo,
+        })
+        .collect())
+}
+
+/// Total number of cairo steps to run the translation of the LLVM IR file at `path` on the inputs
+/// of a differential test with the same `config`. Used to measure the effect of
+/// [TranslationOptions::optimize], the inputs only depend on the seed so two runs with different
+/// options can be compared.
+pub fn cairo_steps(path: &str, config: &DifferentialConfig) -> Result<usize, String> {
+    let (_, functions) = tested_functions(path, config)?;
+    let cases = generate_cases(&functions, config);
+    let runs = run_cairo(path, &functions, &cases, config)?;
+    Ok(runs.iter().filter_map(|run| run.steps).sum())
+}
@@ -0,0 +1,475 @@
+//! Fuzz the translator with random LLVM functions.
+//!
+//! A [FuzzProgram] is a random CFG over a few integer "registers" of the same random width. The
+//! entry block starts with the parameters, every other block starts with one phi per register that
+//! takes the register values at the end of each predecessor, so any CFG is well-formed SSA:
+//! ```text
+//! define i32 @fuzz(i32 %0, i32 %1) {
+//! start:
+//!   %2 = sub i32 %0, 3
+//!   %3 = icmp ult i32 %2, %1
+//!   br i1 %3, label %bb1, label %bb2
+//! bb1:
+//!   %4 = phi i32 [ %2, %start ], [ %6, %bb2 ]
+//!   %5 = phi i32 [ %1, %start ], [ %1, %bb2 ]
+//!   ...
+//! ```
+//! Only the instructions the translator supports are generated (`add`, `sub` and the `icmp`
+//! predicates it translates) so the fuzzer doesn't report the unsupported ones over and over. The
+//! translator doesn't support arbitrary CFGs yet, they are only generated with
+//! [FuzzConfig::max_blocks] > 1.
+//!
+//! Each program is translated and checked for panics and, when a cairo runner is configured and
+//! the CFG has no loop (the program could run forever), for semantic differences with
+//! [differential_test]. A failing program is shrunk to a minimal one that fails the same way and
+//! its IR is saved in `examples/fuzz`, where the snapshot tests of [crate::testing::corpus] replay
+//! it once it's fixed and its golden file is written.
+
+use std::fmt::Display;
+use std::fs;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::path::PathBuf;
+
+use inkwell::context::Context;
+use inkwell::module::Module;
+use inkwell::types::IntType;
+use inkwell::values::{BasicValue, IntValue};
+use inkwell::IntPredicate;
+
+use super::differential::{differential_test, DifferentialConfig, Mismatch};
+use super::Rng;
+use crate::builder::options::TranslationOptions;
+use crate::compile_module;
+
+/// Integer widths of the generated programs.
+const WIDTHS: [u32; 5] = [8, 16, 32, 64, 128];
+
+/// The `icmp` predicates the translator supports.
+const PREDICATES: [IntPredicate; 3] = [IntPredicate::EQ, IntPredicate::NE, IntPredicate::ULT];
+
+#[derive(Clone, Debug)]
+pub struct FuzzConfig {
+    /// Number of programs to generate.
+    pub iterations: usize,
+    pub seed: u64,
+    /// Maximum number of blocks of a program. The translator only supports the CFGs rustc emits
+    /// for simple loops and conditions so the default is straight-line programs.
+    pub max_blocks: usize,
+    /// Maximum number of instructions per block, terminator excluded.
+    pub max_instructions: usize,
+    /// Compare the programs with their translation when set. It needs `scarb`.
+    pub differential: Option<DifferentialConfig>,
+    /// Directory the reproducers are saved in.
+    pub reproducers: PathBuf,
+}
+
+impl Default for FuzzConfig {
+    fn default() -> Self {
+        Self {
+            iterations: 100,
+            seed: 0xf022,
+            max_blocks: 1,
+            max_instructions: 4,
+            differential: None,
+            reproducers: PathBuf::from("examples/fuzz"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Operand {
+    Register(usize),
+    Constant(i64),
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Operation {
+    Add,
+    Sub,
+    /// `icmp` extended to the width of the registers.
+    Compare(IntPredicate),
+}
+
+/// `register[destination] = left <operation> right`
+#[derive(Clone, Debug)]
+struct Instruction {
+    destination: usize,
+    operation: Operation,
+    left: Operand,
+    right: Operand,
+}
+
+#[derive(Clone, Debug)]
+enum Terminator {
+    Return(Operand),
+    Jump(usize),
+    Branch { predicate: IntPredicate, left: Operand, right: Operand, then_block: usize, else_block: usize },
+}
+
+impl Terminator {
+    /// Successors of the block, once per edge.
+    fn targets(&self) -> Vec<usize> {
+        match self {
+            Terminator::Return(_) => Vec::new(),
+            Terminator::Jump(target) => vec![*target],
+            Terminator::Branch { then_block, else_block, .. } => vec![*then_block, *else_block],
+        }
+    }
+
+    fn map_targets(&mut self, map: impl Fn(usize) -> usize) {
+        match self {
+            Terminator::Return(_) => (),
+            Terminator::Jump(target) => *target = map(*target),
+            Terminator::Branch { then_block, else_block, .. } => {
+                *then_block = map(*then_block);
+                *else_block = map(*else_block);
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+struct FuzzBlock {
+    instructions: Vec<Instruction>,
+    terminator: Terminator,
+}
+
+/// A random LLVM function `i<width> @fuzz(i<width> x registers)`.
+#[derive(Clone, Debug)]
+pub struct FuzzProgram {
+    width: u32,
+    registers: usize,
+    blocks: Vec<FuzzBlock>,
+}
+
+/// Why a program failed.
+#[derive(Clone, Debug)]
+pub enum FuzzFailure {
+    /// The generator built invalid IR, that's a bug of the fuzzer.
+    InvalidIr(String),
+    /// The translator panicked with this message.
+    Panic(String),
+    /// The translator returned this error.
+    Error(String),
+    /// The cairo package couldn't be built or run.
+    Differential(String),
+    Mismatches(Vec<Mismatch>),
+}
+
+impl Display for FuzzFailure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FuzzFailure::InvalidIr(error) => f.write_str(&format!("invalid IR: {error}")),
+            FuzzFailure::Panic(message) => f.write_str(&format!("the translator panicked: {message}")),
+            FuzzFailure::Error(error) => f.write_str(&format!("the translation failed: {error}")),
+            FuzzFailure::Differential(error) => f.write_str(&format!("differential test failed: {error}")),
+            FuzzFailure::Mismatches(mismatches) => f.write_str(&format!(
+                "the translation doesn't behave like the IR:\n{}",
+                mismatches.iter().map(ToString::to_string).collect::<Vec<_>>().join("\n")
+            )),
+        }
+    }
+}
+
+impl FuzzFailure {
+    /// Whether two failures are the same kind of failure, whatever their details.
+    pub fn same_kind(&self, other: &FuzzFailure) -> bool {
+        std::mem::discriminant(self) == std::mem::discriminant(other)
+    }
+}
+
+/// A shrunk failing program saved as IR.
+#[derive(Clone, Debug)]
+pub struct Reproducer {
+    pub path: PathBuf,
+    pub failure: FuzzFailure,
+    /// Why the IR couldn't be written in `path`, if it couldn't.
+    pub save_error: Option<String>,
+}
+
+impl Display for Reproducer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.save_error {
+            Some(error) => f.write_str(&format!("{} (failed to save {}: {error})", self.failure, self.path.display())),
+            None => f.write_str(&format!("{} ({})", self.failure, self.path.display())),
+        }
+    }
+}
+
+/// Get the message of a caught panic.
+fn panic_message(payload: Box<dyn std::any::Any + Send>) -> String {
+    payload
+        .downcast_ref::<&str>()
+        .map(|message| message.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "unknown panic".to_owned())
+}
+
+impl FuzzProgram {
+    /// Generate a random program.
+    pub fn generate(rng: &mut Rng, config: &FuzzConfig) -> Self {
+        let width = WIDTHS[rng.below(WIDTHS.len())];
+        let registers = 1 + rng.below(3);
+        let block_count = 1 + rng.below(config.max_blocks.max(1));
+        let operand = |rng: &mut Rng| {
+            if rng.below(3) == 0 {
+                Operand::Constant(rng.below(16) as i64 - 4)
+            } else {
+                Operand::Register(rng.below(registers))
+            }
+        };
+        let blocks = (0..block_count)
+            .map(|_| {
+                let instructions = (0..rng.below(config.max_instructions + 1))
+                    .map(|_| Instruction {
+                        destination: rng.below(registers),
+                        operation: match rng.below(3) {
+                            0 => Operation::Add,
+                            1 => Operation::Sub,
+                            _ => Operation::Compare(PREDICATES[rng.below(PREDICATES.len())]),
+                        },
+                        left: Operand::Register(rng.below(registers)),
+                        right: operand(rng),
+                    })
+                    .collect();
+                // The entry block can't be a target.
+                let target = |rng: &mut Rng| 1 + rng.below(block_count - 1);
+                let terminator = match rng.below(3) {
+                    _ if block_count == 1 => Terminator::Return(operand(rng)),
+                    0 => Terminator::Return(operand(rng)),
+                    1 => Terminator::Jump(target(rng)),
+                    _ => Terminator::Branch {
+                        predicate: PREDICATES[rng.below(PREDICATES.len())],
+                        left: Operand::Register(rng.below(registers)),
+                        right: operand(rng),
+                        then_block: target(rng),
+                        else_block: target(rng),
+                    },
+                };
+                FuzzBlock { instructions, terminator }
+            })
+            .collect();
+        let mut program = Self { width, registers, blocks };
+        program.remove_unreachable_blocks();
+        program
+    }
+
+    /// Remove the blocks that can't be reached from the entry block, LLVM doesn't like phis
+    /// without incoming values.
+    fn remove_unreachable_blocks(&mut self) {
+        let mut reachable = vec![false; self.blocks.len()];
+        let mut stack = vec![0];
+        while let Some(block) = stack.pop() {
+            if !std::mem::replace(&mut reachable[block], true) {
+                stack.extend(self.blocks[block].terminator.targets());
+            }
+        }
+        let mut new_indices = Vec::with_capacity(self.blocks.len());
+        let mut next = 0;
+        for is_reachable in reachable.iter() {
+            new_indices.push(next);
+            next += usize::from(*is_reachable);
+        }
+        let blocks = std::mem::take(&mut self.blocks);
+        self.blocks = blocks
+            .into_iter()
+            .zip(reachable)
+            .filter(|(_, is_reachable)| *is_reachable)
+            .map(|(mut block, _)| {
+                block.terminator.map_targets(|target| new_indices[target]);
+                block
+            })
+            .collect();
+    }
+
+    /// Does the CFG have no loop. Targets are only generated after their block in that case.
+    fn is_acyclic(&self) -> bool {
+        self.blocks
+            .iter()
+            .enumerate()
+            .all(|(index, block)| block.terminator.targets().into_iter().all(|target| target > index))
+    }
+
+    /// Build the LLVM function.
+    pub fn build<'ctx>(&self, context: &'ctx Context) -> Module<'ctx> {
+        let module = context.create_module("fuzz");
+        let ty = context.custom_width_int_type(self.width);
+        let function = module.add_function("fuzz", ty.fn_type(&vec![ty.into(); self.registers], false), None);
+        let builder = context.create_builder();
+        let blocks = (0..self.blocks.len())
+            .map(|index| {
+                let name = if index == 0 { "start".to_owned() } else { format!("bb{index}") };
+                context.append_basic_block(function, &name)
+            })
+            .collect::<Vec<_>>();
+        // The phis have to be created before the values that use them, they get their incoming
+        // values once all the blocks are built.
+        let phis = blocks
+            .iter()
+            .enumerate()
+            .map(|(index, bb)| {
+                builder.position_at_end(*bb);
+                let count = if index == 0 { 0 } else { self.registers };
+                (0..count).map(|_| builder.build_phi(ty, "").expect("Phi should be built")).collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        let value = |registers: &[IntValue<'ctx>], operand: Operand, ty: IntType<'ctx>| match operand {
+            Operand::Register(register) => registers[register % registers.len()],
+            Operand::Constant(constant) => ty.const_int(constant as u64, true),
+        };
+        let mut exits = Vec::with_capacity(self.blocks.len());
+        for (index, block) in self.blocks.iter().enumerate() {
+            builder.position_at_end(blocks[index]);
+            let mut registers = if index == 0 {
+                function.get_param_iter().map(|param| param.into_int_value()).collect::<Vec<_>>()
+            } else {
+                phis[index].iter().map(|phi| phi.as_basic_value().into_int_value()).collect()
+            };
+            for instruction in block.instructions.iter() {
+                let left = value(&registers, instruction.left, ty);
+                let right = value(&registers, instruction.right, ty);
+                let result = match instruction.operation {
+                    Operation::Add => builder.build_int_add(left, right, ""),
+                    Operation::Sub => builder.build_int_sub(left, right, ""),
+                    Operation::Compare(predicate) => builder
+                        .build_int_compare(predicate, left, right, "")
+                        .and_then(|comparison| builder.build_int_z_extend(comparison, ty, "")),
+                };
+                registers[instruction.destination % self.registers] = result.expect("Instruction should be built");
+            }
+            match &block.terminator {
+                Terminator::Return(operand) => builder.build_return(Some(&value(&registers, *operand, ty))),
+                Terminator::Jump(target) => builder.build_unconditional_branch(blocks[*target]),
+                Terminator::Branch { predicate, left, right, then_block, else_block } => {
+                    let condition = builder
+                        .build_int_compare(*predicate, value(&registers, *left, ty), value(&registers, *right, ty), "")
+                        .expect("Condition should be built");
+                    builder.build_conditional_branch(condition, blocks[*then_block], blocks[*else_block])
+                }
+            }
+            .expect("Terminator should be built");
+            exits.push(registers);
+        }
+        for (index, block) in self.blocks.iter().enumerate() {
+            for target in block.terminator.targets() {
+                for (phi, register) in phis[target].iter().zip(exits[index].iter()) {
+                    phi.add_incoming(&[(register as &dyn BasicValue, blocks[index])]);
+                }
+            }
+        }
+        module
+    }
+
+    /// Printed LLVM IR of the program.
+    pub fn to_ir(&self) -> String {
+        let context = Context::create();
+        self.build(&context).print_to_string().to_string()
+    }
+
+    /// Translate the program and check the translation.
+    pub fn check(&self, config: &FuzzConfig) -> Option<FuzzFailure> {
+        let context = Context::create();
+        let module = self.build(&context);
+        if let Err(error) = module.verify() {
+            return Some(FuzzFailure::InvalidIr(error.to_string()));
+        }
+        match catch_unwind(AssertUnwindSafe(|| compile_module(&module, TranslationOptions::default()))) {
+            Ok(Ok(_)) => (),
+            Ok(Err(error)) => return Some(FuzzFailure::Error(error)),
+            Err(payload) => return Some(FuzzFailure::Panic(panic_message(payload))),
+        }
+        // A loop could run forever.
+        let differential = config.differential.as_ref().filter(|_| self.is_acyclic())?;
+        let path = differential.directory.join("fuzz.ll");
+        if let Err(error) = fs::create_dir_all(&differential.directory).and_then(|_| fs::write(&path, self.to_ir())) {
+            return Some(FuzzFailure::Differential(error.to_string()));
+        }
+        match differential_test(&path.to_string_lossy(), differential) {
+            Ok(mismatches) if mismatches.is_empty() => None,
+            Ok(mismatches) => Some(FuzzFailure::Mismatches(mismatches)),
+            Err(error) => Some(FuzzFailure::Differential(error)),
+        }
+    }
+
+    /// Programs that are one step simpler than this one.
+    fn simplifications(&self) -> Vec<Self> {
+        let mut candidates = Vec::new();
+        // Remove a block, the jumps to it become returns.
+        for removed in 1..self.blocks.len() {
+            let mut candidate = self.clone();
+            candidate.blocks.remove(removed);
+            for block in candidate.blocks.iter_mut() {
+                if block.terminator.targets().contains(&removed) {
+                    block.terminator = Terminator::Return(Operand::Register(0));
+                } else {
+                    block.terminator.map_targets(|target| if target > removed { target - 1 } else { target });
+                }
+            }
+            candidates.push(candidate);
+        }
+        for (index, block) in self.blocks.iter().enumerate() {
+            // Simplify the terminator.
+            let simpler = match &block.terminator {
+                Terminator::Return(Operand::Register(0)) => Vec::new(),
+                Terminator::Return(_) | Terminator::Jump(_) => vec![Terminator::Return(Operand::Register(0))],
+                Terminator::Branch { then_block, else_block, .. } => {
+                    vec![Terminator::Jump(*then_block), Terminator::Jump(*else_block)]
+                }
+            };
+            for terminator in simpler {
+                let mut candidate = self.clone();
+                candidate.blocks[index].terminator = terminator;
+                candidates.push(candidate);
+            }
+            // Remove an instruction.
+            for instruction in 0..block.instructions.len() {
+                let mut candidate = self.clone();
+                candidate.blocks[index].instructions.remove(instruction);
+                candidates.push(candidate);
+            }
+        }
+        if self.width > WIDTHS[0] {
+            candidates.push(Self { width: WIDTHS[0], ..self.clone() });
+        }
+        candidates.iter_mut().for_each(Self::remove_unreachable_blocks);
+        candidates
+    }
+
+    /// Simplify a failing program as long as it keeps failing the same way: a simpler program
+    /// that fails for another reason (the shrinking made the IR invalid, the panic became a
+    /// mismatch...) is a different bug.
+    pub fn shrink(self, failure: FuzzFailure, config: &FuzzConfig) -> (Self, FuzzFailure) {
+        let (mut program, mut failure) = (self, failure);
+        while let Some((simpler, simpler_failure)) = program.simplifications().into_iter().find_map(|candidate| {
+            candidate
+                .check(config)
+                .filter(|candidate_failure| failure.same_kind(candidate_failure))
+                .map(|failure| (candidate, failure))
+        }) {
+            program = simpler;
+            failure = simpler_failure;
+        }
+        (program, failure)
+    }
+}
+
+/// Generate and check `config.iterations` programs. The first failing program is shrunk and saved
+/// in `config.reproducers`, the reproducer tells if it couldn't be.
+pub fn fuzz(config: &FuzzConfig) -> Result<(), Reproducer> {
+    for iteration in 0..config.iterations {
+        // Each program has its own seed so it can be regenerated alone.
+        let mut rng = Rng::new(config.seed.wrapping_add(iteration as u64));
+        let program = FuzzProgram::generate(&mut rng, config);
+        let Some(failure) = program.check(config) else {
+            continue;
+        };
+        let (program, failure) = program.shrink(failure, config);
+        let path = config.reproducers.join(format!("fuzz_{}_{iteration}.ll", config.seed));
+        let save_error = fs::create_dir_all(&config.reproducers)
+            .and_then(|_| fs::write(&path, program.to_ir()))
+            .err()
+            .map(|error| error.to_string());
+        return Err(Reproducer { path, failure, save_error });
+    }
+    Ok(())
+}
@@ -0,0 +1,37 @@
+//! Tools to check that the translation behaves like the LLVM code it comes from.
+
+use std::ops::RangeInclusive;
+
+pub mod corpus;
+pub mod differential;
+pub mod fuzz;
+
+/// Small xorshift generator so the generated inputs and programs are reproducible without pulling
+/// a dependency.
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        // xorshift is stuck at 0.
+        Self(seed.max(1))
+    }
+
+    pub fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    /// Random value in `0..bound`.
+    pub fn below(&mut self, bound: usize) -> usize {
+        (self.next() % bound as u64) as usize
+    }
+
+    /// Random value of `range`.
+    pub fn in_range(&mut self, range: &RangeInclusive<i128>) -> i128 {
+        let span = range.end().abs_diff(*range.start()).saturating_add(1);
+        let offset = ((u128::from(self.next()) << 64) | u128::from(self.next())) % span;
+        range.start().wrapping_add_unsigned(offset)
+    }
+}